@@ -0,0 +1,114 @@
+// captures and saves encounters without starting the meter ui
+// usage: loa-logs-headless [--dir <resource dir>] [--replay <file or directory> [--speed <multiplier>|max]]
+// ctrl-c stops capturing and saves the encounter in progress before exiting
+use std::{
+    env,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use app::db::setup_db;
+use app::parser::meter_data::load_meter_data;
+use app::parser::replay::get_replay_source;
+use app::parser::sink::{EventHandler, EventSink};
+use app::parser::{self, get_capture_source};
+use app::read_settings;
+use flexi_logger::Logger;
+use hashbrown::HashMap;
+use log::{info, warn};
+use serde_json::Value;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    Logger::try_with_str("info")?.log_to_stdout().start()?;
+
+    let args: Vec<String> = env::args().collect();
+    let resource_path = match args
+        .iter()
+        .position(|arg| arg == "--dir")
+        .and_then(|i| args.get(i + 1))
+    {
+        Some(dir) => PathBuf::from(dir),
+        // same place the app keeps its resources
+        None => env::current_exe()?
+            .parent()
+            .context("could not get executable dir")?
+            .to_path_buf(),
+    };
+    info!("starting headless, using {}", resource_path.display());
+
+    let settings = read_settings(&resource_path).ok();
+    load_meter_data(&resource_path);
+    let source = match get_replay_source(&args) {
+        Some(source) => source,
+        None => get_capture_source(settings.clone()),
+    };
+
+    // nothing to do if encounters can't be saved
+    setup_db(resource_path.clone()).map_err(anyhow::Error::msg)?;
+
+    let sink = Arc::new(HeadlessSink {
+        resource_path,
+        handlers: Mutex::new(HashMap::new()),
+    });
+
+    // stop the packet source on ctrl-c so the encounter in progress is still saved
+    tokio::spawn({
+        let sink = sink.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                info!("ctrl-c received, saving and exiting");
+                sink.trigger("stop-request");
+            }
+        }
+    });
+
+    tokio::task::spawn_blocking(move || parser::start(sink, source, settings))
+        .await?
+        .map_err(|e| {
+            warn!("unexpected error occurred in parser: {}", e);
+            e
+        })
+}
+
+// logs the events the meter window would have received
+struct HeadlessSink {
+    resource_path: PathBuf,
+    handlers: Mutex<HashMap<String, Vec<EventHandler>>>,
+}
+
+impl HeadlessSink {
+    fn trigger(&self, event: &str) {
+        if let Some(handlers) = self.handlers.lock().unwrap().get(event) {
+            for handler in handlers {
+                handler(None);
+            }
+        }
+    }
+}
+
+impl EventSink for HeadlessSink {
+    fn emit(&self, event: &str, payload: Value) -> Result<()> {
+        match event {
+            // sent every few hundred ms, too noisy to log
+            "encounter-update" | "party-update" | "identity-update" | "stagger-update" => {}
+            _ => info!("{}: {}", event, payload),
+        }
+        Ok(())
+    }
+
+    // there is no window, only the process itself sends events (stop-request on ctrl-c)
+    fn listen(&self, event: &str, handler: EventHandler) {
+        self.handlers
+            .lock()
+            .unwrap()
+            .entry(event.to_string())
+            .or_default()
+            .push(handler);
+    }
+
+    fn resource_dir(&self) -> PathBuf {
+        self.resource_path.clone()
+    }
+}
@@ -0,0 +1,138 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::parser::encounter_state::get_class_from_id;
+use crate::parser::models::{
+    Character, CharacterName, EncounterEntity, EntityType, GearScorePoint,
+};
+
+// keeps the character tables in step with a saved player, keyed on character id rather than name
+// name and class only move forward in time so an older log can't overwrite the current ones
+pub fn update_character(
+    conn: &Connection,
+    encounter_id: i64,
+    fight_start: i64,
+    entity: &EncounterEntity,
+) -> Result<()> {
+    if entity.entity_type != EntityType::PLAYER || entity.character_id == 0 {
+        return Ok(());
+    }
+
+    conn.prepare_cached(
+        "
+    INSERT INTO character (id, name, class_id, first_seen, last_seen)
+    VALUES (?1, ?2, ?3, ?4, ?4)
+    ON CONFLICT (id) DO UPDATE SET
+        name = IIF(excluded.last_seen >= last_seen, excluded.name, name),
+        class_id = IIF(excluded.last_seen >= last_seen, excluded.class_id, class_id),
+        first_seen = MIN(first_seen, excluded.first_seen),
+        last_seen = MAX(last_seen, excluded.last_seen)",
+    )?
+    .execute(params![
+        entity.character_id,
+        entity.name,
+        entity.class_id,
+        fight_start
+    ])?;
+
+    conn.prepare_cached(
+        "
+    INSERT INTO character_name (character_id, name, first_seen, last_seen)
+    VALUES (?1, ?2, ?3, ?3)
+    ON CONFLICT (character_id, name) DO UPDATE SET
+        first_seen = MIN(first_seen, excluded.first_seen),
+        last_seen = MAX(last_seen, excluded.last_seen)",
+    )?
+    .execute(params![entity.character_id, entity.name, fight_start])?;
+
+    if entity.gear_score > 0.0 {
+        conn.prepare_cached(
+            "
+    INSERT OR REPLACE INTO character_gear_score (character_id, encounter_id, fight_start, gear_score)
+    VALUES (?1, ?2, ?3, ?4)",
+        )?
+        .execute(params![
+            entity.character_id,
+            encounter_id,
+            fight_start,
+            entity.gear_score
+        ])?;
+    }
+
+    Ok(())
+}
+
+// every character that has gone by this name, most recently seen first
+pub fn get_characters(conn: &Connection, name: &str) -> Result<Vec<Character>> {
+    let mut stmt = conn.prepare_cached(
+        "
+    SELECT id, name, class_id, first_seen, last_seen
+    FROM character
+    WHERE id IN (SELECT character_id FROM character_name WHERE name = ?)
+    ORDER BY last_seen DESC",
+    )?;
+    let mut characters = stmt
+        .query_map(params![name], |row| {
+            let class_id: u32 = row.get::<_, Option<u32>>(2)?.unwrap_or_default();
+            Ok(Character {
+                character_id: row.get(0)?,
+                name: row.get(1)?,
+                class_id,
+                class: get_class_from_id(&class_id),
+                first_seen: row.get(3)?,
+                last_seen: row.get(4)?,
+                ..Default::default()
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut names_stmt = conn.prepare_cached(
+        "
+    SELECT name, first_seen, last_seen
+    FROM character_name
+    WHERE character_id = ?
+    ORDER BY first_seen ASC",
+    )?;
+    let mut gear_score_stmt = conn.prepare_cached(
+        "
+    SELECT encounter_id, fight_start, gear_score
+    FROM character_gear_score
+    WHERE character_id = ?
+    ORDER BY fight_start ASC",
+    )?;
+    for character in characters.iter_mut() {
+        character.names = names_stmt
+            .query_map(params![character.character_id], |row| {
+                Ok(CharacterName {
+                    name: row.get(0)?,
+                    first_seen: row.get(1)?,
+                    last_seen: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        character.gear_scores = gear_score_stmt
+            .query_map(params![character.character_id], |row| {
+                Ok(GearScorePoint {
+                    encounter_id: row.get(0)?,
+                    fight_start: row.get(1)?,
+                    gear_score: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+    }
+
+    Ok(characters)
+}
+
+// the character last seen going by this name, None if only logs from before character ids have it
+pub fn get_character_id(conn: &Connection, name: &str) -> Result<Option<u64>> {
+    conn.prepare_cached(
+        "
+    SELECT character_id
+    FROM character_name
+    WHERE name = ?
+    ORDER BY last_seen DESC
+    LIMIT 1",
+    )?
+    .query_row(params![name], |row| row.get(0))
+    .optional()
+}
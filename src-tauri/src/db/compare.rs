@@ -0,0 +1,188 @@
+use std::collections::BTreeSet;
+
+use rusqlite::Connection;
+
+use crate::db::encounters::load_encounter;
+use crate::parser::models::*;
+
+// loads both encounters the same way the log page does and diffs one player between them
+pub fn compare_encounters(
+    conn: &Connection,
+    base_id: i32,
+    other_id: i32,
+    player: &str,
+) -> Result<EncounterComparison, String> {
+    let base = load_encounter(conn, &base_id.to_string());
+    let other = load_encounter(conn, &other_id.to_string());
+    let mut comparison = compare(&base, &other, player)?;
+    comparison.base.encounter_id = base_id;
+    comparison.other.encounter_id = other_id;
+    Ok(comparison)
+}
+
+pub fn compare(
+    base: &Encounter,
+    other: &Encounter,
+    player: &str,
+) -> Result<EncounterComparison, String> {
+    let base_entity = find_player(base, player)?;
+    let other_entity = find_player(other, player)?;
+
+    let skill_ids: BTreeSet<i32> = base_entity
+        .skills
+        .keys()
+        .chain(other_entity.skills.keys())
+        .copied()
+        .collect();
+    let mut skills: Vec<SkillComparison> = skill_ids
+        .into_iter()
+        .map(|skill_id| {
+            let base_skill = base_entity.skills.get(&skill_id);
+            let other_skill = other_entity.skills.get(&skill_id);
+            let skill = base_skill.or(other_skill).unwrap();
+            SkillComparison {
+                skill_id,
+                name: skill.name.clone(),
+                icon: skill.icon.clone(),
+                base: base_skill.map(|s| summarize_skill(s, base_entity, base.duration)),
+                other: other_skill.map(|s| summarize_skill(s, other_entity, other.duration)),
+            }
+        })
+        .collect();
+    // biggest skills first, by whichever encounter they did more in
+    skills.sort_by_key(|s| {
+        let damage = |summary: &Option<SkillSummary>| summary.as_ref().map_or(0, |s| s.damage);
+        -damage(&s.base).max(damage(&s.other))
+    });
+
+    let mut buffs = compare_buffs(base, base_entity, other, other_entity, false);
+    buffs.extend(compare_buffs(base, base_entity, other, other_entity, true));
+
+    let base_summary = summarize_player(base, base_entity);
+    let other_summary = summarize_player(other, other_entity);
+    Ok(EncounterComparison {
+        player: player.to_string(),
+        dps_delta: other_summary.dps - base_summary.dps,
+        base: base_summary,
+        other: other_summary,
+        skills,
+        buffs,
+    })
+}
+
+fn find_player<'a>(encounter: &'a Encounter, player: &str) -> Result<&'a EncounterEntity, String> {
+    encounter
+        .entities
+        .get(player)
+        .filter(|e| e.entity_type == EntityType::PLAYER)
+        .ok_or_else(|| {
+            format!(
+                "{} is not in the {} encounter",
+                player, encounter.current_boss_name
+            )
+        })
+}
+
+fn summarize_player(encounter: &Encounter, entity: &EncounterEntity) -> PlayerSummary {
+    let stats = &entity.skill_stats;
+    PlayerSummary {
+        boss_name: encounter.current_boss_name.clone(),
+        fight_start: encounter.fight_start,
+        duration: encounter.duration,
+        class: entity.class.clone(),
+        damage_dealt: entity.damage_stats.damage_dealt,
+        dps: entity.damage_stats.dps,
+        casts_per_minute: per_minute(stats.casts, encounter.duration),
+        crit_rate: ratio(stats.crits, stats.hits),
+        back_attack_rate: ratio(stats.back_attacks, stats.hits),
+        front_attack_rate: ratio(stats.front_attacks, stats.hits),
+        identity_average: stats
+            .identity_stats
+            .as_ref()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+            .and_then(|v| v["average"].as_f64()),
+        ..Default::default()
+    }
+}
+
+fn summarize_skill(skill: &Skill, entity: &EncounterEntity, duration: i64) -> SkillSummary {
+    SkillSummary {
+        damage: skill.total_damage,
+        damage_share: ratio(skill.total_damage, entity.damage_stats.damage_dealt),
+        dps: skill.dps,
+        casts: skill.casts,
+        casts_per_minute: per_minute(skill.casts, duration),
+        crit_rate: ratio(skill.crits, skill.hits),
+        back_attack_rate: ratio(skill.back_attacks, skill.hits),
+        front_attack_rate: ratio(skill.front_attacks, skill.hits),
+    }
+}
+
+fn compare_buffs(
+    base: &Encounter,
+    base_entity: &EncounterEntity,
+    other: &Encounter,
+    other_entity: &EncounterEntity,
+    debuff: bool,
+) -> Vec<BuffComparison> {
+    let (base_buffed_by, other_buffed_by, base_effects, other_effects) = if debuff {
+        (
+            &base_entity.damage_stats.debuffed_by,
+            &other_entity.damage_stats.debuffed_by,
+            &base.encounter_damage_stats.debuffs,
+            &other.encounter_damage_stats.debuffs,
+        )
+    } else {
+        (
+            &base_entity.damage_stats.buffed_by,
+            &other_entity.damage_stats.buffed_by,
+            &base.encounter_damage_stats.buffs,
+            &other.encounter_damage_stats.buffs,
+        )
+    };
+
+    let buff_ids: BTreeSet<i32> = base_buffed_by
+        .keys()
+        .chain(other_buffed_by.keys())
+        .copied()
+        .collect();
+    buff_ids
+        .into_iter()
+        .map(|buff_id| {
+            let name = base_effects
+                .get(&buff_id)
+                .or_else(|| other_effects.get(&buff_id))
+                .map(|effect| effect.source.name.clone())
+                .unwrap_or_default();
+            BuffComparison {
+                buff_id,
+                name,
+                debuff,
+                base: ratio(
+                    base_buffed_by.get(&buff_id).copied().unwrap_or_default(),
+                    base_entity.damage_stats.damage_dealt,
+                ),
+                other: ratio(
+                    other_buffed_by.get(&buff_id).copied().unwrap_or_default(),
+                    other_entity.damage_stats.damage_dealt,
+                ),
+            }
+        })
+        .collect()
+}
+
+fn ratio(value: i64, total: i64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        value as f64 / total as f64
+    }
+}
+
+fn per_minute(value: i64, duration_ms: i64) -> f64 {
+    if duration_ms <= 0 {
+        0.0
+    } else {
+        value as f64 / (duration_ms as f64 / 60_000.0)
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, TimeZone};
+use rusqlite::Connection;
+
+use crate::db::encounters::load_existing_encounter;
+use crate::db::format::percent;
+use crate::parser::models::*;
+
+pub const PLAYERS_FILE: &str = "players.csv";
+pub const SKILLS_FILE: &str = "skills.csv";
+
+const PLAYER_HEADER: [&str; 19] = [
+    "encounter_id",
+    "date",
+    "boss",
+    "difficulty",
+    "cleared",
+    "duration_s",
+    "name",
+    "class",
+    "gear_score",
+    "damage",
+    "damage_%",
+    "dps",
+    "crit_%",
+    "back_attack_%",
+    "front_attack_%",
+    "support_buffed_%",
+    "deaths",
+    "counters",
+    "casts",
+];
+
+const SKILL_HEADER: [&str; 17] = [
+    "encounter_id",
+    "date",
+    "boss",
+    "difficulty",
+    "name",
+    "class",
+    "skill_id",
+    "skill",
+    "damage",
+    "damage_%",
+    "dps",
+    "max_hit",
+    "casts",
+    "hits",
+    "crit_%",
+    "back_attack_%",
+    "front_attack_%",
+];
+
+// writes players.csv and skills.csv into dir, one row per player or player skill per encounter
+// encounters are written oldest first whatever order the ids come in
+pub fn export_csv(
+    conn: &Connection,
+    encounter_ids: &[i32],
+    dir: &Path,
+) -> Result<(PathBuf, PathBuf), String> {
+    let mut encounters = Vec::new();
+    for &id in encounter_ids {
+        encounters.push((id, load_existing_encounter(conn, id)?));
+    }
+    encounters.sort_by_key(|(id, encounter)| (encounter.fight_start, *id));
+
+    let mut players = vec![row(PLAYER_HEADER)];
+    let mut skills = vec![row(SKILL_HEADER)];
+    for (id, encounter) in encounters {
+        let (player_rows, skill_rows) = encounter_rows(id, &encounter);
+        players.extend(player_rows);
+        skills.extend(skill_rows);
+    }
+
+    let players_path = dir.join(PLAYERS_FILE);
+    let skills_path = dir.join(SKILLS_FILE);
+    for (path, rows) in [(&players_path, players), (&skills_path, skills)] {
+        fs::write(path, rows.concat()).map_err(|e| format!("could not write {:?}: {}", path, e))?;
+    }
+    Ok((players_path, skills_path))
+}
+
+// players are sorted by damage, and each player's skills by damage under them
+pub fn encounter_rows(id: i32, encounter: &Encounter) -> (Vec<String>, Vec<String>) {
+    let date = Local
+        .timestamp_millis_opt(encounter.fight_start)
+        .single()
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    let difficulty = encounter.difficulty.clone().unwrap_or_default();
+    let total_damage = encounter.encounter_damage_stats.total_damage_dealt;
+
+    let mut entities: Vec<&EncounterEntity> = encounter
+        .entities
+        .values()
+        .filter(|e| e.entity_type == EntityType::PLAYER)
+        .collect();
+    entities.sort_by_key(|e| -e.damage_stats.damage_dealt);
+
+    let mut players = Vec::new();
+    let mut skills = Vec::new();
+    for entity in entities {
+        let damage_stats = &entity.damage_stats;
+        let skill_stats = &entity.skill_stats;
+        players.push(row([
+            id.to_string(),
+            date.clone(),
+            encounter.current_boss_name.clone(),
+            difficulty.clone(),
+            encounter.cleared.to_string(),
+            (encounter.duration / 1000).to_string(),
+            entity.name.clone(),
+            entity.class.clone(),
+            format!("{:.2}", entity.gear_score),
+            damage_stats.damage_dealt.to_string(),
+            percent(damage_stats.damage_dealt, total_damage),
+            damage_stats.dps.to_string(),
+            percent(skill_stats.crits, skill_stats.hits),
+            percent(skill_stats.back_attacks, skill_stats.hits),
+            percent(skill_stats.front_attacks, skill_stats.hits),
+            percent(damage_stats.buffed_by_support, damage_stats.damage_dealt),
+            damage_stats.deaths.to_string(),
+            skill_stats.counters.to_string(),
+            skill_stats.casts.to_string(),
+        ]));
+
+        let mut entity_skills: Vec<&Skill> = entity.skills.values().collect();
+        entity_skills.sort_by_key(|s| (-s.total_damage, s.id));
+        for skill in entity_skills {
+            skills.push(row([
+                id.to_string(),
+                date.clone(),
+                encounter.current_boss_name.clone(),
+                difficulty.clone(),
+                entity.name.clone(),
+                entity.class.clone(),
+                skill.id.to_string(),
+                skill.name.clone(),
+                skill.total_damage.to_string(),
+                percent(skill.total_damage, damage_stats.damage_dealt),
+                skill.dps.to_string(),
+                skill.max_damage.to_string(),
+                skill.casts.to_string(),
+                skill.hits.to_string(),
+                percent(skill.crits, skill.hits),
+                percent(skill.back_attacks, skill.hits),
+                percent(skill.front_attacks, skill.hits),
+            ]));
+        }
+    }
+
+    (players, skills)
+}
+
+fn row<T: AsRef<str>, const N: usize>(fields: [T; N]) -> String {
+    let fields: Vec<String> = fields.iter().map(|f| escape(f.as_ref())).collect();
+    fields.join(",") + "\r\n"
+}
+
+// quotes fields the way spreadsheets expect, names and skill names can contain commas
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,298 @@
+use std::str::FromStr;
+
+use hashbrown::HashMap;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::json;
+
+use crate::db::characters::update_character;
+use crate::db::search::index_encounter;
+use crate::db::stats::insert_entity_stats;
+use crate::parser::models::*;
+
+// loads an encounter for the exports, unlike load_encounter an unknown id is an error
+pub fn load_existing_encounter(conn: &Connection, id: i32) -> Result<Encounter, String> {
+    let exists: Option<i32> = conn
+        .query_row("SELECT id FROM encounter WHERE id = ?", [id], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if exists.is_none() {
+        return Err(format!("encounter {} does not exist", id));
+    }
+
+    Ok(load_encounter(conn, &id.to_string()))
+}
+
+// loads a saved encounter with its entities, missing or unreadable fields fall back to defaults
+pub fn load_encounter(conn: &Connection, id: &str) -> Encounter {
+    let mut encounter_stmt = conn
+        .prepare_cached(
+            "
+    SELECT last_combat_packet,
+       fight_start,
+       local_player,
+       current_boss,
+       duration,
+       total_damage_dealt,
+       top_damage_dealt,
+       total_damage_taken,
+       top_damage_taken,
+       dps,
+       buffs,
+       debuffs,
+       misc,
+       difficulty,
+       favorite,
+       cleared,
+       boss_only_damage,
+       notes,
+       imported,
+       total_shielding,
+       total_effective_shielding,
+       applied_shield_buffs,
+       damage_by_target,
+       version
+    FROM encounter
+    WHERE id = ?
+    ;",
+        )
+        .unwrap();
+
+    let mut encounter = encounter_stmt
+        .query_row(params![id], |row| {
+            let buff_str = row.get(10).unwrap_or_else(|_| "".to_string());
+            let buffs = serde_json::from_str::<HashMap<i32, StatusEffect>>(buff_str.as_str())
+                .unwrap_or_else(|_| HashMap::new());
+
+            let debuff_str = row.get(11).unwrap_or_else(|_| "".to_string());
+            let debuffs = serde_json::from_str::<HashMap<i32, StatusEffect>>(debuff_str.as_str())
+                .unwrap_or_else(|_| HashMap::new());
+
+            let shield_buff_str = row.get(21).unwrap_or_else(|_| "".to_string());
+            let applied_shield_buffs =
+                serde_json::from_str::<HashMap<i32, StatusEffect>>(shield_buff_str.as_str())
+                    .unwrap_or_else(|_| HashMap::new());
+
+            let damage_by_target_str = row.get(22).unwrap_or_else(|_| "".to_string());
+            let damage_by_target =
+                serde_json::from_str::<HashMap<u32, TargetDamage>>(damage_by_target_str.as_str())
+                    .unwrap_or_else(|_| HashMap::new());
+
+            let misc_str = row.get(12).unwrap_or_else(|_| "".to_string());
+            let misc = serde_json::from_str::<EncounterMisc>(misc_str.as_str())
+                .map(Some)
+                .unwrap_or_else(|_| None);
+
+            Ok(Encounter {
+                last_combat_packet: row.get(0)?,
+                fight_start: row.get(1)?,
+                local_player: row.get(2)?,
+                current_boss_name: row.get(3)?,
+                duration: row.get(4)?,
+                encounter_damage_stats: EncounterDamageStats {
+                    total_damage_dealt: row.get(5)?,
+                    top_damage_dealt: row.get(6)?,
+                    total_damage_taken: row.get(7)?,
+                    top_damage_taken: row.get(8)?,
+                    dps: row.get(9)?,
+                    buffs,
+                    debuffs,
+                    misc,
+                    total_shielding: row.get(19)?,
+                    total_effective_shielding: row.get(20)?,
+                    applied_shield_buffs,
+                    damage_by_target,
+                    ..Default::default()
+                },
+                difficulty: row.get(13)?,
+                favorite: row.get(14)?,
+                cleared: row.get(15)?,
+                boss_only_damage: row.get(16)?,
+                notes: row.get(17)?,
+                imported: row.get(18)?,
+                version: row.get(23)?,
+                ..Default::default()
+            })
+        })
+        .unwrap_or_else(|_| Encounter::default());
+
+    let mut entity_stmt = conn
+        .prepare_cached(
+            "
+    SELECT name,
+        class_id,
+        class,
+        gear_score,
+        current_hp,
+        max_hp,
+        is_dead,
+        skills,
+        damage_stats,
+        skill_stats,
+        last_update,
+        entity_type,
+        npc_id,
+        character_id
+    FROM entity
+    WHERE encounter_id = ?;
+    ",
+        )
+        .unwrap();
+
+    let entity_iter = entity_stmt
+        .query_map(params![id], |row| {
+            let skill_str = row.get(7).unwrap_or_else(|_| "".to_string());
+            let skills = serde_json::from_str::<HashMap<i32, Skill>>(skill_str.as_str())
+                .unwrap_or_else(|_| HashMap::new());
+
+            let damage_stats_str = row.get(8).unwrap_or_else(|_| "".to_string());
+
+            let damage_stats = serde_json::from_str::<DamageStats>(damage_stats_str.as_str())
+                .unwrap_or_else(|_| DamageStats::default());
+
+            let skill_stats_str = row.get(9).unwrap_or_else(|_| "".to_string());
+            let skill_stats = serde_json::from_str::<SkillStats>(skill_stats_str.as_str())
+                .unwrap_or_else(|_| SkillStats::default());
+
+            let entity_type = row.get(11).unwrap_or_else(|_| "".to_string());
+
+            Ok(EncounterEntity {
+                name: row.get(0)?,
+                class_id: row.get(1)?,
+                class: row.get(2)?,
+                gear_score: row.get(3)?,
+                current_hp: row.get(4)?,
+                max_hp: row.get(5)?,
+                is_dead: row.get(6)?,
+                skills,
+                damage_stats,
+                skill_stats,
+                entity_type: EntityType::from_str(entity_type.as_str())
+                    .unwrap_or(EntityType::UNKNOWN),
+                npc_id: row.get(12)?,
+                character_id: row.get::<_, Option<u64>>(13)?.unwrap_or_default(),
+                ..Default::default()
+            })
+        })
+        .unwrap();
+
+    let mut entities: HashMap<String, EncounterEntity> = HashMap::new();
+    for entity in entity_iter.flatten() {
+        entities.insert(entity.name.to_string(), entity);
+    }
+
+    encounter.entities = entities;
+
+    encounter
+}
+
+// saves the encounter with its entities and derived rows, shared by live saves and imports.
+// records are left to the caller so a live save can keep the encounter if they fail
+pub fn insert_encounter(
+    tx: &Transaction,
+    encounter: &Encounter,
+    imported: bool,
+) -> rusqlite::Result<i64> {
+    let stats = &encounter.encounter_damage_stats;
+    tx.prepare_cached(
+        "
+    INSERT INTO encounter (
+        last_combat_packet,
+        fight_start,
+        local_player,
+        current_boss,
+        duration,
+        total_damage_dealt,
+        top_damage_dealt,
+        total_damage_taken,
+        top_damage_taken,
+        dps,
+        buffs,
+        debuffs,
+        misc,
+        difficulty,
+        cleared,
+        boss_only_damage,
+        version,
+        notes,
+        total_shielding,
+        total_effective_shielding,
+        applied_shield_buffs,
+        damage_by_target,
+        imported
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
+    )?
+    .execute(params![
+        encounter.last_combat_packet,
+        encounter.fight_start,
+        encounter.local_player,
+        encounter.current_boss_name,
+        encounter.duration,
+        stats.total_damage_dealt,
+        stats.top_damage_dealt,
+        stats.total_damage_taken,
+        stats.top_damage_taken,
+        stats.dps,
+        json!(stats.buffs),
+        json!(stats.debuffs),
+        json!(stats.misc.clone().unwrap_or_default()),
+        encounter.difficulty,
+        encounter.cleared,
+        encounter.boss_only_damage,
+        encounter.version,
+        encounter.notes,
+        stats.total_shielding,
+        stats.total_effective_shielding,
+        json!(stats.applied_shield_buffs),
+        json!(stats.damage_by_target),
+        imported
+    ])?;
+    let encounter_id = tx.last_insert_rowid();
+
+    let mut entity_stmt = tx.prepare_cached(
+        "
+    INSERT INTO entity (
+        name,
+        encounter_id,
+        npc_id,
+        entity_type,
+        class_id,
+        class,
+        gear_score,
+        current_hp,
+        max_hp,
+        is_dead,
+        skills,
+        damage_stats,
+        skill_stats,
+        dps,
+        character_id
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+    )?;
+    for entity in encounter.entities.values() {
+        entity_stmt.execute(params![
+            entity.name,
+            encounter_id,
+            entity.npc_id,
+            entity.entity_type.to_string(),
+            entity.class_id,
+            entity.class,
+            entity.gear_score,
+            entity.current_hp,
+            entity.max_hp,
+            entity.is_dead,
+            json!(entity.skills),
+            json!(entity.damage_stats),
+            json!(entity.skill_stats),
+            entity.damage_stats.dps,
+            (entity.character_id > 0).then_some(entity.character_id)
+        ])?;
+        insert_entity_stats(tx, encounter_id, entity)?;
+        update_character(tx, encounter_id, encounter.fight_start, entity)?;
+    }
+
+    index_encounter(tx, encounter_id)?;
+
+    Ok(encounter_id)
+}
@@ -0,0 +1,176 @@
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::Value;
+
+use crate::db::encounters::{insert_encounter, load_existing_encounter};
+use crate::db::records::update_records;
+use crate::parser::models::*;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// writes the encounter as a standalone json document, gzipped when the path ends in .gz
+pub fn export_encounter(
+    conn: &Connection,
+    id: i32,
+    path: &Path,
+    app_version: &str,
+) -> Result<(), String> {
+    let export = EncounterExport {
+        format_version: EXPORT_FORMAT_VERSION,
+        app_version: app_version.to_string(),
+        db_version: DB_VERSION,
+        raid_data_version: RAID_DATA_VERSION,
+        exported_at: Utc::now().timestamp_millis(),
+        encounter: load_existing_encounter(conn, id)?,
+    };
+
+    let json = serde_json::to_vec(&export).map_err(|e| e.to_string())?;
+    let write = |path: &Path| -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        if path.extension() == Some(OsStr::new("gz")) {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(&json)?;
+            encoder.finish()?;
+        } else {
+            file.write_all(&json)?;
+        }
+        Ok(())
+    };
+    write(path).map_err(|e| format!("could not write {:?}: {}", path, e))
+}
+
+// reads an export, plain or gzipped, and saves it as a new imported encounter
+pub fn import_encounter(conn: &mut Connection, path: &Path) -> Result<i64, String> {
+    let bytes = fs::read(path).map_err(|e| format!("could not read {:?}: {}", path, e))?;
+    let export = parse_export(&bytes)?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let id = insert_export(&tx, export)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+pub fn parse_export(bytes: &[u8]) -> Result<EncounterExport, String> {
+    let json = if bytes.starts_with(&GZIP_MAGIC) {
+        let mut json = Vec::new();
+        GzDecoder::new(bytes)
+            .read_to_end(&mut json)
+            .map_err(|e| format!("could not decompress export: {}", e))?;
+        json
+    } else {
+        bytes.to_vec()
+    };
+
+    let mut export: EncounterExport = serde_json::from_slice(&json)
+        .map_err(|e| format!("not a valid encounter export: {}", e))?;
+
+    if export.format_version == 0 {
+        return Err("export is missing its format version".to_string());
+    }
+    if export.format_version > EXPORT_FORMAT_VERSION {
+        return Err(format!(
+            "export format version {} is newer than supported version {}, update the meter to import it",
+            export.format_version, EXPORT_FORMAT_VERSION
+        ));
+    }
+    let encounter = &export.encounter;
+    if encounter.current_boss_name.is_empty()
+        || encounter.fight_start <= 0
+        || encounter.entities.is_empty()
+    {
+        return Err("export does not contain an encounter".to_string());
+    }
+
+    let mut encounter = serde_json::to_value(&export.encounter).map_err(|e| e.to_string())?;
+    sanitize(&mut encounter);
+    export.encounter = serde_json::from_value(encounter)
+        .map_err(|e| format!("not a valid encounter export: {}", e))?;
+
+    Ok(export)
+}
+
+// names, skills and buff text from someone else's file end up in html tooltips,
+// so markup is stripped everywhere and icons have to be plain file names
+fn sanitize(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = strip_markup(text),
+        Value::Array(values) => values.iter_mut().for_each(sanitize),
+        Value::Object(map) => {
+            for (key, mut value) in std::mem::take(map) {
+                if key == "icon" || key.ends_with("Icon") {
+                    if let Value::String(icon) = &mut value {
+                        if !is_file_name(icon) {
+                            icon.clear();
+                        }
+                    }
+                } else {
+                    sanitize(&mut value);
+                }
+                map.insert(strip_markup(&key), value);
+            }
+        }
+        _ => {}
+    }
+}
+
+// game data wraps some names in font tags, those keep their text
+fn strip_markup(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            '"' => {}
+            _ if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn is_file_name(icon: &str) -> bool {
+    !icon.contains("..")
+        && icon
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn insert_export(tx: &Transaction, export: EncounterExport) -> Result<i64, String> {
+    let mut encounter = export.encounter;
+
+    // the same log imported twice, or a log that was exported from this meter
+    let existing: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM encounter WHERE fight_start = ? AND current_boss = ? AND local_player = ?",
+            params![
+                encounter.fight_start,
+                encounter.current_boss_name,
+                encounter.local_player
+            ],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(existing) = existing {
+        return Err(format!("encounter is already saved as {}", existing));
+    }
+
+    // exports from before the version was carried over were at most the exporter's db version
+    if encounter.version == 0 {
+        encounter.version = export.db_version;
+    }
+
+    insert_encounter(tx, &encounter, true)
+        // records are rebuilt from every saved encounter, so imports count the same way here
+        .and_then(|id| update_records(tx, id).map(|_| id))
+        .map_err(|e| format!("could not save encounter: {}", e))
+}
@@ -0,0 +1,16 @@
+// number formatting shared by the csv, report and summary exports
+
+// m:ss, fights never run long enough to need hours
+pub fn format_duration(duration_ms: i64) -> String {
+    let seconds = duration_ms / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// value as a percentage of total with one decimal, 0.0 when there is no total
+pub fn percent(value: i64, total: i64) -> String {
+    if total == 0 {
+        "0.0".to_string()
+    } else {
+        format!("{:.1}", value as f64 / total as f64 * 100.0)
+    }
+}
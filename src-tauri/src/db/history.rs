@@ -0,0 +1,125 @@
+use rusqlite::{params_from_iter, types::Value, Connection, Result};
+
+use crate::db::characters::get_character_id;
+use crate::parser::models::{HistoryFilter, HistoryPoint};
+
+pub const DEFAULT_ROLLING_WINDOW: usize = 5;
+
+// one point per saved encounter the character was in, oldest first
+pub fn get_character_history(
+    conn: &Connection,
+    character: &str,
+    filter: &HistoryFilter,
+) -> Result<Vec<HistoryPoint>> {
+    let mut conditions = vec!["ent.entity_type = 'PLAYER'".to_string()];
+    let mut params = vec![];
+
+    // follow the character through renames, logs from before character ids were stored only match by name
+    // a name on its own resolves to the character last seen with it, not everyone who has had it
+    let character_id = match filter.character_id {
+        Some(character_id) => Some(character_id),
+        None => get_character_id(conn, character)?,
+    };
+    if let Some(character_id) = character_id {
+        conditions.push(
+            "(ent.character_id = ? OR (ent.character_id IS NULL AND ent.name = ?))".to_string(),
+        );
+        params.push(Value::Integer(character_id as i64));
+    } else {
+        conditions.push("ent.name = ?".to_string());
+    }
+    params.push(Value::Text(character.to_string()));
+
+    if !filter.bosses.is_empty() {
+        conditions.push(format!(
+            "e.current_boss IN ({})",
+            vec!["?"; filter.bosses.len()].join(",")
+        ));
+        params.extend(filter.bosses.iter().map(|boss| Value::Text(boss.clone())));
+    }
+
+    if !filter.difficulty.is_empty() {
+        conditions.push("e.difficulty = ?".to_string());
+        params.push(Value::Text(filter.difficulty.clone()));
+    }
+
+    if filter.cleared {
+        conditions.push("e.cleared = 1".to_string());
+    }
+
+    let query = format!(
+        "
+    SELECT
+        e.id,
+        e.fight_start,
+        e.current_boss,
+        e.difficulty,
+        e.cleared,
+        ent.class,
+        ent.gear_score,
+        ent.dps,
+        json_extract(ent.damage_stats, '$.damageDealt'),
+        e.total_damage_dealt,
+        json_extract(ent.damage_stats, '$.deaths'),
+        json_extract(ent.damage_stats, '$.buffedBySupport')
+    FROM entity ent
+    JOIN encounter e ON e.id = ent.encounter_id
+    WHERE {}
+    ORDER BY e.fight_start ASC
+    ",
+        conditions.join(" AND ")
+    );
+
+    let mut stmt = conn.prepare_cached(&query)?;
+    let mut points = stmt
+        .query_map(params_from_iter(params), |row| {
+            let damage_dealt: i64 = row.get::<_, Option<i64>>(8)?.unwrap_or_default();
+            let total_damage_dealt: i64 = row.get::<_, Option<i64>>(9)?.unwrap_or_default();
+            let buffed_by_support: i64 = row.get::<_, Option<i64>>(11)?.unwrap_or_default();
+            Ok(HistoryPoint {
+                encounter_id: row.get(0)?,
+                fight_start: row.get(1)?,
+                boss_name: row.get(2)?,
+                difficulty: row.get(3)?,
+                cleared: row.get::<_, Option<bool>>(4)?.unwrap_or_default(),
+                class: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                gear_score: row.get::<_, Option<f64>>(6)?.unwrap_or_default(),
+                dps: row.get::<_, Option<i64>>(7)?.unwrap_or_default(),
+                damage_percent: percent(damage_dealt, total_damage_dealt),
+                deaths: row.get::<_, Option<i64>>(10)?.unwrap_or_default(),
+                support_buffed_percent: percent(buffed_by_support, damage_dealt),
+                ..Default::default()
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let window = if filter.rolling_window == 0 {
+        DEFAULT_ROLLING_WINDOW
+    } else {
+        filter.rolling_window
+    };
+    for i in 0..points.len() {
+        let recent = &points[i.saturating_sub(window - 1)..=i];
+        let average = |value: fn(&HistoryPoint) -> f64| {
+            recent.iter().map(value).sum::<f64>() / recent.len() as f64
+        };
+        let rolling_dps = average(|p| p.dps as f64);
+        let rolling_damage_percent = average(|p| p.damage_percent);
+        let rolling_support_buffed_percent = average(|p| p.support_buffed_percent);
+
+        let point = &mut points[i];
+        point.rolling_dps = rolling_dps;
+        point.rolling_damage_percent = rolling_damage_percent;
+        point.rolling_support_buffed_percent = rolling_support_buffed_percent;
+    }
+
+    Ok(points)
+}
+
+fn percent(value: i64, total: i64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        value as f64 / total as f64 * 100.0
+    }
+}
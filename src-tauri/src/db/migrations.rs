@@ -0,0 +1,596 @@
+use log::info;
+use rusqlite::{Result, Transaction};
+
+use crate::parser::models::DB_VERSION;
+
+// migrations are applied in order and the db's user_version is set to the last one applied
+// never edit a released migration, add a new one to the end instead
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> Result<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create encounter and entity tables",
+        up: create_tables,
+    },
+    Migration {
+        version: 2,
+        description: "backfill encounter cleared",
+        up: backfill_cleared,
+    },
+    Migration {
+        version: 3,
+        description: "backfill entity dps",
+        up: backfill_entity_dps,
+    },
+    Migration {
+        version: 4,
+        description: "add encounter notes and full text search",
+        up: create_encounter_search,
+    },
+    Migration {
+        version: 5,
+        description: "add entity_skill and entity_buff tables",
+        up: create_entity_stats,
+    },
+    Migration {
+        version: 6,
+        description: "add personal records",
+        up: create_records,
+    },
+    Migration {
+        version: 7,
+        description: "add entity character id and character tables",
+        up: create_characters,
+    },
+    Migration {
+        version: 8,
+        description: "add encounter imported flag",
+        up: add_imported,
+    },
+    Migration {
+        version: 9,
+        description: "add encounter shield totals",
+        up: add_shields,
+    },
+    Migration {
+        version: 10,
+        description: "add encounter damage by target",
+        up: add_damage_by_target,
+    },
+    Migration {
+        version: 11,
+        description: "key personal records by character id",
+        up: key_records_by_character_id,
+    },
+];
+
+pub const SCHEMA_VERSION: i32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+// dbs from before migrations existed are at user_version 0 and may be missing
+// any of the columns that used to be added on startup, so this one has to be idempotent
+fn create_tables(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(&format!(
+        "
+    CREATE TABLE IF NOT EXISTS encounter (
+        id INTEGER PRIMARY KEY,
+        last_combat_packet INTEGER,
+        fight_start INTEGER,
+        local_player TEXT,
+        current_boss TEXT,
+        duration INTEGER,
+        total_damage_dealt INTEGER,
+        top_damage_dealt INTEGER,
+        total_damage_taken INTEGER,
+        top_damage_taken INTEGER,
+        dps INTEGER,
+        buffs TEXT,
+        debuffs TEXT,
+        misc TEXT,
+        difficulty TEXT,
+        favorite BOOLEAN NOT NULL DEFAULT 0,
+        cleared BOOLEAN,
+        version INTEGER NOT NULL DEFAULT {},
+        boss_only_damage BOOLEAN NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS entity (
+        name TEXT,
+        encounter_id INTEGER NOT NULL,
+        npc_id INTEGER,
+        entity_type TEXT,
+        class_id INTEGER,
+        class TEXT,
+        gear_score REAL,
+        current_hp INTEGER,
+        max_hp INTEGER,
+        is_dead INTEGER,
+        skills TEXT,
+        damage_stats TEXT,
+        dps INTEGER,
+        skill_stats TEXT,
+        last_update INTEGER,
+        PRIMARY KEY (name, encounter_id),
+        FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+    );
+    ",
+        DB_VERSION
+    ))?;
+
+    add_column(tx, "encounter", "misc", "TEXT")?;
+    add_column(tx, "encounter", "difficulty", "TEXT")?;
+    add_column(tx, "encounter", "favorite", "BOOLEAN DEFAULT 0")?;
+    add_column(
+        tx,
+        "encounter",
+        "version",
+        &format!("INTEGER DEFAULT {}", DB_VERSION),
+    )?;
+    add_column(tx, "encounter", "cleared", "BOOLEAN")?;
+    add_column(
+        tx,
+        "encounter",
+        "boss_only_damage",
+        "BOOLEAN NOT NULL DEFAULT 0",
+    )?;
+    add_column(tx, "entity", "dps", "INTEGER")?;
+
+    tx.execute_batch(
+        "
+    CREATE INDEX IF NOT EXISTS encounter_fight_start_index
+    ON encounter (fight_start desc);
+    CREATE INDEX IF NOT EXISTS encounter_current_boss_index
+    ON encounter (current_boss);
+    CREATE INDEX IF NOT EXISTS encounter_favorite_index
+    ON encounter (favorite);
+    CREATE INDEX IF NOT EXISTS entity_encounter_id_index
+    ON entity (encounter_id desc);
+    CREATE INDEX IF NOT EXISTS entity_name_index
+    ON entity (name);
+    CREATE INDEX IF NOT EXISTS entity_class_index
+    ON entity (class);
+    ",
+    )
+}
+
+fn backfill_cleared(tx: &Transaction) -> Result<()> {
+    let updated = tx.execute(
+        "
+    UPDATE encounter
+    SET cleared = CASE
+            WHEN json_extract(misc, '$.raidClear') IS NULL THEN 0
+            ELSE 1
+            END
+    WHERE cleared IS NULL
+    ",
+        [],
+    )?;
+    info!("updated {} encounters", updated);
+    Ok(())
+}
+
+fn backfill_entity_dps(tx: &Transaction) -> Result<()> {
+    let updated = tx.execute(
+        "
+    UPDATE entity
+    SET dps = json_extract(damage_stats, '$.dps')
+    WHERE dps IS NULL
+    ",
+        [],
+    )?;
+    info!("updated {} entities", updated);
+    Ok(())
+}
+
+// rowid is the encounter id, rows for new encounters are written by index_encounter
+fn create_encounter_search(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+    ALTER TABLE encounter ADD COLUMN notes TEXT;
+    CREATE VIRTUAL TABLE encounter_search USING fts5(
+        boss,
+        players,
+        classes,
+        difficulty,
+        notes
+    );
+    CREATE TRIGGER encounter_search_delete AFTER DELETE ON encounter
+    BEGIN
+        DELETE FROM encounter_search WHERE rowid = old.id;
+    END;
+    CREATE TRIGGER encounter_search_update AFTER UPDATE OF current_boss, difficulty, notes ON encounter
+    BEGIN
+        UPDATE encounter_search
+        SET boss = new.current_boss, difficulty = new.difficulty, notes = new.notes
+        WHERE rowid = new.id;
+    END;
+    ",
+    )?;
+
+    let indexed = tx.execute(
+        "
+    INSERT INTO encounter_search (rowid, boss, players, classes, difficulty, notes)
+    SELECT
+        e.id,
+        e.current_boss,
+        (SELECT GROUP_CONCAT(name, ' ') FROM entity WHERE encounter_id = e.id AND entity_type = 'PLAYER'),
+        (SELECT GROUP_CONCAT(class, ' ') FROM entity WHERE encounter_id = e.id AND entity_type = 'PLAYER'),
+        e.difficulty,
+        e.notes
+    FROM encounter e
+    ",
+        [],
+    )?;
+    info!("indexed {} encounters for search", indexed);
+    Ok(())
+}
+
+// per player skill and buff rows, backfilled from the json in entity
+fn create_entity_stats(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+    CREATE TABLE entity_skill (
+        encounter_id INTEGER NOT NULL,
+        player TEXT NOT NULL,
+        skill_id INTEGER NOT NULL,
+        skill_name TEXT,
+        total_damage INTEGER,
+        max_damage INTEGER,
+        hits INTEGER,
+        crits INTEGER,
+        crit_damage INTEGER,
+        casts INTEGER,
+        back_attacks INTEGER,
+        front_attacks INTEGER,
+        back_attack_damage INTEGER,
+        front_attack_damage INTEGER,
+        buffed_by_support INTEGER,
+        debuffed_by_support INTEGER,
+        dps INTEGER,
+        PRIMARY KEY (encounter_id, player, skill_id),
+        FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+    );
+    CREATE INDEX entity_skill_skill_id_index
+    ON entity_skill (skill_id);
+    CREATE INDEX entity_skill_player_index
+    ON entity_skill (player);
+
+    CREATE TABLE entity_buff (
+        encounter_id INTEGER NOT NULL,
+        player TEXT NOT NULL,
+        buff_id INTEGER NOT NULL,
+        debuff BOOLEAN NOT NULL,
+        damage INTEGER,
+        PRIMARY KEY (encounter_id, player, buff_id, debuff),
+        FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+    );
+    CREATE INDEX entity_buff_buff_id_index
+    ON entity_buff (buff_id);
+    ",
+    )?;
+
+    let skills = tx.execute(
+        "
+    INSERT OR IGNORE INTO entity_skill
+    SELECT
+        en.encounter_id,
+        en.name,
+        CAST(s.key AS INTEGER),
+        json_extract(s.value, '$.name'),
+        json_extract(s.value, '$.totalDamage'),
+        json_extract(s.value, '$.maxDamage'),
+        json_extract(s.value, '$.hits'),
+        json_extract(s.value, '$.crits'),
+        json_extract(s.value, '$.critDamage'),
+        json_extract(s.value, '$.casts'),
+        json_extract(s.value, '$.backAttacks'),
+        json_extract(s.value, '$.frontAttacks'),
+        json_extract(s.value, '$.backAttackDamage'),
+        json_extract(s.value, '$.frontAttackDamage'),
+        json_extract(s.value, '$.buffedBySupport'),
+        json_extract(s.value, '$.debuffedBySupport'),
+        json_extract(s.value, '$.dps')
+    FROM entity en, json_each(IIF(json_valid(en.skills), en.skills, '{}')) s
+    WHERE en.entity_type = 'PLAYER'
+    ",
+        [],
+    )?;
+
+    let buffs = tx.execute(
+        "
+    INSERT OR IGNORE INTO entity_buff
+    SELECT en.encounter_id, en.name, CAST(b.key AS INTEGER), 0, b.value
+    FROM entity en, json_each(IIF(json_valid(en.damage_stats), en.damage_stats, '{}'), '$.buffedBy') b
+    WHERE en.entity_type = 'PLAYER'
+    UNION ALL
+    SELECT en.encounter_id, en.name, CAST(b.key AS INTEGER), 1, b.value
+    FROM entity en, json_each(IIF(json_valid(en.damage_stats), en.damage_stats, '{}'), '$.debuffedBy') b
+    WHERE en.entity_type = 'PLAYER'
+    ",
+        [],
+    )?;
+    info!("backfilled {} skill and {} buff rows", skills, buffs);
+    Ok(())
+}
+
+// best value per character, boss, difficulty and kind
+// the backfill is a frozen copy of the record queries as they were when this shipped,
+// later changes to db::records must not change what this migration does
+fn create_records(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+    CREATE TABLE record (
+        character TEXT NOT NULL,
+        boss TEXT NOT NULL,
+        difficulty TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        value REAL NOT NULL,
+        encounter_id INTEGER NOT NULL,
+        skill_id INTEGER,
+        fight_start INTEGER,
+        PRIMARY KEY (character, boss, difficulty, kind)
+    );
+    CREATE INDEX record_encounter_id_index
+    ON record (encounter_id);
+    ",
+    )?;
+
+    let candidates = [
+        (
+            "dps",
+            "DESC",
+            "
+        SELECT ent.name AS character, e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            ent.dps AS value, e.id AS encounter_id, NULL AS skill_id, e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity ent ON ent.encounter_id = e.id AND ent.entity_type = 'PLAYER'
+        WHERE e.cleared = 1 AND ent.dps IS NOT NULL",
+        ),
+        (
+            "max_hit",
+            "DESC",
+            "
+        SELECT s.player AS character, e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            s.max_damage AS value, e.id AS encounter_id, s.skill_id AS skill_id, e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity_skill s ON s.encounter_id = e.id
+        WHERE e.cleared = 1 AND s.max_damage > 0
+            AND s.skill_id = (
+                SELECT s2.skill_id FROM entity_skill s2
+                WHERE s2.encounter_id = s.encounter_id AND s2.player = s.player
+                ORDER BY s2.max_damage DESC
+                LIMIT 1)",
+        ),
+        (
+            "crit_rate",
+            "DESC",
+            "
+        SELECT s.player AS character, e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            CAST(SUM(s.crits) AS REAL) / SUM(s.hits) AS value, e.id AS encounter_id, NULL AS skill_id,
+            e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity_skill s ON s.encounter_id = e.id
+        WHERE e.cleared = 1
+        GROUP BY e.id, s.player
+        HAVING SUM(s.hits) > 0",
+        ),
+        (
+            "clear_time",
+            "ASC",
+            "
+        SELECT ent.name AS character, e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            e.duration AS value, e.id AS encounter_id, NULL AS skill_id, e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity ent ON ent.encounter_id = e.id AND ent.entity_type = 'PLAYER'
+        WHERE e.cleared = 1 AND e.duration > 0",
+        ),
+    ];
+    for (kind, order, candidates) in candidates {
+        tx.execute(
+            &format!(
+                "INSERT INTO record
+                (character, boss, difficulty, kind, value, encounter_id, skill_id, fight_start)
+                SELECT character, boss, difficulty, ?, value, encounter_id, skill_id, fight_start
+                FROM (
+                    SELECT *, ROW_NUMBER() OVER (
+                        PARTITION BY character, boss, difficulty
+                        ORDER BY value {}, fight_start ASC
+                    ) AS rank
+                    FROM ({})
+                )
+                WHERE rank = 1",
+                order, candidates
+            ),
+            [kind],
+        )?;
+    }
+    Ok(())
+}
+
+// older logs never stored the character id so they can't be backfilled, their entities keep a null character_id
+fn create_characters(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+    ALTER TABLE entity ADD COLUMN character_id INTEGER;
+    CREATE INDEX entity_character_id_index
+    ON entity (character_id);
+
+    CREATE TABLE character (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        class_id INTEGER,
+        first_seen INTEGER NOT NULL,
+        last_seen INTEGER NOT NULL
+    );
+
+    CREATE TABLE character_name (
+        character_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        first_seen INTEGER NOT NULL,
+        last_seen INTEGER NOT NULL,
+        PRIMARY KEY (character_id, name),
+        FOREIGN KEY (character_id) REFERENCES character (id) ON DELETE CASCADE
+    );
+    CREATE INDEX character_name_name_index
+    ON character_name (name);
+
+    CREATE TABLE character_gear_score (
+        character_id INTEGER NOT NULL,
+        encounter_id INTEGER NOT NULL,
+        fight_start INTEGER NOT NULL,
+        gear_score REAL NOT NULL,
+        PRIMARY KEY (character_id, encounter_id),
+        FOREIGN KEY (character_id) REFERENCES character (id) ON DELETE CASCADE,
+        FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+    );
+    ",
+    )
+}
+
+fn add_imported(tx: &Transaction) -> Result<()> {
+    tx.execute_batch("ALTER TABLE encounter ADD COLUMN imported BOOLEAN NOT NULL DEFAULT 0;")
+}
+
+// per player shield stats live in entity damage_stats, older logs simply have none
+fn add_shields(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+    ALTER TABLE encounter ADD COLUMN total_shielding INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE encounter ADD COLUMN total_effective_shielding INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE encounter ADD COLUMN applied_shield_buffs TEXT;
+    ",
+    )
+}
+
+// per player and per skill splits live in the entity json, older logs have none
+fn add_damage_by_target(tx: &Transaction) -> Result<()> {
+    tx.execute_batch("ALTER TABLE encounter ADD COLUMN damage_by_target TEXT;")
+}
+
+// records were keyed by name so same-name characters shared one and renames started over,
+// character is only the name for logs from before character ids were stored (character_id 0)
+// the rebuild is a frozen copy of the record queries as they were when this shipped
+fn key_records_by_character_id(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+    DROP TABLE record;
+    CREATE TABLE record (
+        character_id INTEGER NOT NULL,
+        character TEXT NOT NULL,
+        boss TEXT NOT NULL,
+        difficulty TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        value REAL NOT NULL,
+        encounter_id INTEGER NOT NULL,
+        skill_id INTEGER,
+        fight_start INTEGER,
+        PRIMARY KEY (character_id, character, boss, difficulty, kind)
+    );
+    CREATE INDEX record_encounter_id_index
+    ON record (encounter_id);
+    ",
+    )?;
+
+    let candidates = [
+        (
+            "dps",
+            "DESC",
+            "
+        SELECT COALESCE(ent.character_id, 0) AS character_id,
+            CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character,
+            e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            ent.dps AS value, e.id AS encounter_id, NULL AS skill_id, e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity ent ON ent.encounter_id = e.id AND ent.entity_type = 'PLAYER'
+        WHERE e.cleared = 1 AND ent.dps IS NOT NULL",
+        ),
+        (
+            "max_hit",
+            "DESC",
+            "
+        SELECT COALESCE(ent.character_id, 0) AS character_id,
+            CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character,
+            e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            s.max_damage AS value, e.id AS encounter_id, s.skill_id AS skill_id, e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity_skill s ON s.encounter_id = e.id
+        JOIN entity ent ON ent.encounter_id = e.id AND ent.name = s.player
+        WHERE e.cleared = 1 AND s.max_damage > 0
+            AND s.skill_id = (
+                SELECT s2.skill_id FROM entity_skill s2
+                WHERE s2.encounter_id = s.encounter_id AND s2.player = s.player
+                ORDER BY s2.max_damage DESC
+                LIMIT 1)",
+        ),
+        (
+            "crit_rate",
+            "DESC",
+            "
+        SELECT COALESCE(ent.character_id, 0) AS character_id,
+            CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character,
+            e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            CAST(SUM(s.crits) AS REAL) / SUM(s.hits) AS value, e.id AS encounter_id, NULL AS skill_id,
+            e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity_skill s ON s.encounter_id = e.id
+        JOIN entity ent ON ent.encounter_id = e.id AND ent.name = s.player
+        WHERE e.cleared = 1
+        GROUP BY e.id, s.player
+        HAVING SUM(s.hits) > 0",
+        ),
+        (
+            "clear_time",
+            "ASC",
+            "
+        SELECT COALESCE(ent.character_id, 0) AS character_id,
+            CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character,
+            e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+            e.duration AS value, e.id AS encounter_id, NULL AS skill_id, e.fight_start AS fight_start
+        FROM encounter e
+        JOIN entity ent ON ent.encounter_id = e.id AND ent.entity_type = 'PLAYER'
+        WHERE e.cleared = 1 AND e.duration > 0",
+        ),
+    ];
+    for (kind, order, candidates) in candidates {
+        tx.execute(
+            &format!(
+                "INSERT INTO record
+                (character_id, character, boss, difficulty, kind, value, encounter_id, skill_id, fight_start)
+                SELECT character_id, character, boss, difficulty, ?, value, encounter_id, skill_id, fight_start
+                FROM (
+                    SELECT *, ROW_NUMBER() OVER (
+                        PARTITION BY character_id, character, boss, difficulty
+                        ORDER BY value {}, fight_start ASC
+                    ) AS rank
+                    FROM ({})
+                )
+                WHERE rank = 1",
+                order, candidates
+            ),
+            [kind],
+        )?;
+    }
+    Ok(())
+}
+
+// only needed for dbs created before migrations, new columns should go in their own migration
+fn add_column(tx: &Transaction, table: &str, column: &str, definition: &str) -> Result<()> {
+    let count: u32 = tx.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?",
+            table
+        ),
+        [column],
+        |row| row.get(0),
+    )?;
+    if count == 0 {
+        tx.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
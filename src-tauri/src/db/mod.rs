@@ -0,0 +1,100 @@
+pub mod characters;
+pub mod compare;
+pub mod csv;
+pub mod encounters;
+pub mod export;
+pub mod format;
+pub mod history;
+pub mod migrations;
+pub mod records;
+pub mod report;
+pub mod search;
+pub mod stats;
+pub mod summary;
+#[cfg(test)]
+mod tests;
+
+use std::path::{Path, PathBuf};
+
+use log::info;
+use rusqlite::Connection;
+
+use self::migrations::{MIGRATIONS, SCHEMA_VERSION};
+
+pub fn get_db_connection(resource_path: &Path) -> Result<Connection, String> {
+    let mut path = resource_path.to_path_buf();
+    path.push("encounters.db");
+    if !path.exists() {
+        setup_db(resource_path.to_path_buf())?;
+    }
+    open_db(&path)
+}
+
+// every connection that writes or deletes encounters must come from here
+pub fn open_db(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    // deleting encounters relies on cascading to entity, entity_skill, entity_buff and character_gear_score
+    conn.pragma_update(None, "foreign_keys", true)
+        .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+pub fn setup_db(resource_path: PathBuf) -> Result<(), String> {
+    let mut path = resource_path;
+    path.push("encounters.db");
+    let mut conn = match Connection::open(path) {
+        Ok(conn) => conn,
+        Err(e) => {
+            return Err(e.to_string());
+        }
+    };
+
+    migrate(&mut conn)
+}
+
+// checks the db was not written by a newer version of the app without touching it
+pub fn check_db_version(resource_path: &Path) -> Result<(), String> {
+    let conn = get_db_connection(resource_path)?;
+    let version = get_schema_version(&conn)?;
+    check_schema_version(version)
+}
+
+pub fn get_schema_version(conn: &Connection) -> Result<i32, String> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("failed to read database version: {}", e))
+}
+
+// runs every migration newer than the db's user_version, each in its own transaction
+pub fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let current = get_schema_version(conn)?;
+    check_schema_version(current)?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        (migration.up)(&tx).map_err(|e| {
+            format!(
+                "migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            )
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        info!(
+            "applied database migration {} ({})",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+fn check_schema_version(version: i32) -> Result<(), String> {
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "encounters.db is version {} but this version of the app only supports up to version {}, please update the app",
+            version, SCHEMA_VERSION
+        ));
+    }
+    Ok(())
+}
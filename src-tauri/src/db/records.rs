@@ -0,0 +1,264 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Result, ToSql};
+
+use crate::db::characters::get_character_id;
+use crate::parser::models::{CharacterRecord, PersonalBest, RecordKind};
+
+pub const RECORD_KINDS: [RecordKind; 4] = [
+    RecordKind::Dps,
+    RecordKind::MaxHit,
+    RecordKind::CritRate,
+    RecordKind::ClearTime,
+];
+
+impl RecordKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordKind::Dps => "dps",
+            RecordKind::MaxHit => "max_hit",
+            RecordKind::CritRate => "crit_rate",
+            RecordKind::ClearTime => "clear_time",
+        }
+    }
+
+    // clear time is the only record where lower is better
+    fn is_better(&self, value: f64, previous: f64) -> bool {
+        match self {
+            RecordKind::ClearTime => value < previous,
+            _ => value > previous,
+        }
+    }
+
+    fn sql_order(&self) -> &'static str {
+        match self {
+            RecordKind::ClearTime => "ASC",
+            _ => "DESC",
+        }
+    }
+
+    // one row per player per encounter: character id, character, name, boss, difficulty, value,
+    // encounter id, skill id. players are keyed by character id, character is only their name for
+    // logs from before character ids were stored
+    // only cleared encounters count so resets and wipes don't set records
+    fn candidates(&self) -> &'static str {
+        match self {
+            RecordKind::Dps => {
+                "
+            SELECT COALESCE(ent.character_id, 0) AS character_id,
+                CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character, ent.name AS name,
+                e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+                ent.dps AS value, e.id AS encounter_id, NULL AS skill_id, e.fight_start AS fight_start
+            FROM encounter e
+            JOIN entity ent ON ent.encounter_id = e.id AND ent.entity_type = 'PLAYER'
+            WHERE e.cleared = 1 AND ent.dps IS NOT NULL"
+            }
+            RecordKind::MaxHit => {
+                "
+            SELECT COALESCE(ent.character_id, 0) AS character_id,
+                CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character, ent.name AS name,
+                e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+                s.max_damage AS value, e.id AS encounter_id, s.skill_id AS skill_id, e.fight_start AS fight_start
+            FROM encounter e
+            JOIN entity_skill s ON s.encounter_id = e.id
+            JOIN entity ent ON ent.encounter_id = e.id AND ent.name = s.player
+            WHERE e.cleared = 1 AND s.max_damage > 0
+                AND s.skill_id = (
+                    SELECT s2.skill_id FROM entity_skill s2
+                    WHERE s2.encounter_id = s.encounter_id AND s2.player = s.player
+                    ORDER BY s2.max_damage DESC
+                    LIMIT 1)"
+            }
+            RecordKind::CritRate => {
+                "
+            SELECT COALESCE(ent.character_id, 0) AS character_id,
+                CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character, ent.name AS name,
+                e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+                CAST(SUM(s.crits) AS REAL) / SUM(s.hits) AS value, e.id AS encounter_id, NULL AS skill_id,
+                e.fight_start AS fight_start
+            FROM encounter e
+            JOIN entity_skill s ON s.encounter_id = e.id
+            JOIN entity ent ON ent.encounter_id = e.id AND ent.name = s.player
+            WHERE e.cleared = 1
+            GROUP BY e.id, s.player
+            HAVING SUM(s.hits) > 0"
+            }
+            RecordKind::ClearTime => {
+                "
+            SELECT COALESCE(ent.character_id, 0) AS character_id,
+                CASE WHEN ent.character_id IS NULL THEN ent.name ELSE '' END AS character, ent.name AS name,
+                e.current_boss AS boss, COALESCE(e.difficulty, '') AS difficulty,
+                e.duration AS value, e.id AS encounter_id, NULL AS skill_id, e.fight_start AS fight_start
+            FROM encounter e
+            JOIN entity ent ON ent.encounter_id = e.id AND ent.entity_type = 'PLAYER'
+            WHERE e.cleared = 1 AND e.duration > 0"
+            }
+        }
+    }
+}
+
+impl ToSql for RecordKind {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for RecordKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let kind = value.as_str()?;
+        RECORD_KINDS
+            .iter()
+            .copied()
+            .find(|k| k.as_str() == kind)
+            .ok_or(FromSqlError::InvalidType)
+    }
+}
+
+// checks a newly saved encounter against the record table, returns the records it set
+pub fn update_records(conn: &Connection, encounter_id: i64) -> Result<Vec<PersonalBest>> {
+    let mut new_records = Vec::new();
+
+    for kind in RECORD_KINDS {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT character_id, character, name, boss, difficulty, value, skill_id, fight_start
+            FROM ({}) WHERE encounter_id = ?",
+            kind.candidates()
+        ))?;
+        let candidates = stmt
+            .query_map(params![encounter_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, f64>(5)?,
+                    row.get::<_, Option<i64>>(6)?,
+                    row.get::<_, i64>(7)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        for (character_id, character, name, boss, difficulty, value, skill_id, fight_start) in
+            candidates
+        {
+            let previous: Option<f64> = conn
+                .query_row(
+                    "SELECT value FROM record
+                    WHERE character_id = ? AND character = ? AND boss = ? AND difficulty = ? AND kind = ?",
+                    params![character_id, character, boss, difficulty, kind],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            if previous.map_or(true, |previous| kind.is_better(value, previous)) {
+                conn.execute(
+                    "INSERT OR REPLACE INTO record
+                    (character_id, character, boss, difficulty, kind, value, encounter_id, skill_id, fight_start)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    params![
+                        character_id,
+                        character,
+                        boss,
+                        difficulty,
+                        kind,
+                        value,
+                        encounter_id,
+                        skill_id,
+                        fight_start
+                    ],
+                )?;
+                new_records.push(PersonalBest {
+                    character: name,
+                    boss,
+                    difficulty,
+                    kind,
+                    value,
+                    previous,
+                    encounter_id,
+                    skill_id,
+                });
+            }
+        }
+    }
+
+    Ok(new_records)
+}
+
+// recomputes every record from scratch, used after encounters are deleted
+pub fn rebuild_records(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM record", [])?;
+    for kind in RECORD_KINDS {
+        conn.execute(
+            &format!(
+                "INSERT INTO record
+                (character_id, character, boss, difficulty, kind, value, encounter_id, skill_id, fight_start)
+                SELECT character_id, character, boss, difficulty, ?, value, encounter_id, skill_id, fight_start
+                FROM (
+                    SELECT *, ROW_NUMBER() OVER (
+                        PARTITION BY character_id, character, boss, difficulty
+                        ORDER BY value {}, fight_start ASC
+                    ) AS rank
+                    FROM ({})
+                )
+                WHERE rank = 1",
+                kind.sql_order(),
+                kind.candidates()
+            ),
+            params![kind],
+        )?;
+    }
+    Ok(())
+}
+
+// records for the character with this id, or the one last seen with this name when it isn't known
+// logs from before character ids were stored count under the name, the better record wins
+pub fn get_character_records(
+    conn: &Connection,
+    character: &str,
+    character_id: Option<u64>,
+) -> Result<Vec<CharacterRecord>> {
+    let character_id = match character_id {
+        Some(character_id) => Some(character_id),
+        None => get_character_id(conn, character)?,
+    };
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT boss, difficulty, kind, value, encounter_id, skill_id, fight_start
+        FROM record
+        WHERE (character_id = ?1 AND ?1 != 0) OR (character_id = 0 AND character = ?2)
+        ORDER BY boss, difficulty, kind, fight_start",
+    )?;
+    let rows = stmt
+        .query_map(
+            params![character_id.unwrap_or_default(), character],
+            |row| {
+                Ok(CharacterRecord {
+                    boss: row.get(0)?,
+                    difficulty: row.get(1)?,
+                    kind: row.get(2)?,
+                    value: row.get(3)?,
+                    encounter_id: row.get(4)?,
+                    skill_id: row.get(5)?,
+                    fight_start: row.get(6)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut records: Vec<CharacterRecord> = Vec::new();
+    for record in rows {
+        match records.last_mut() {
+            Some(last)
+                if last.boss == record.boss
+                    && last.difficulty == record.difficulty
+                    && last.kind == record.kind =>
+            {
+                if record.kind.is_better(record.value, last.value) {
+                    *last = record;
+                }
+            }
+            _ => records.push(record),
+        }
+    }
+    Ok(records)
+}
@@ -0,0 +1,520 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, TimeZone};
+use hashbrown::HashMap;
+use rusqlite::Connection;
+
+use crate::db::encounters::load_existing_encounter;
+use crate::db::format::{format_duration, percent};
+use crate::parser::models::*;
+
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 220.0;
+const CHART_PADDING: f64 = 40.0;
+const COLORS: [&str; 8] = [
+    "#e05d5d", "#5d9de0", "#6ed07a", "#e0c25d", "#b06ee0", "#5dd6d6", "#e0905d", "#d05da8",
+];
+
+const STYLE: &str = "
+body { background: #27272a; color: #e4e4e7; font-family: sans-serif; font-size: 13px; margin: 24px; }
+h1 { font-size: 20px; margin: 0 0 4px; }
+h2 { font-size: 16px; margin: 24px 0 8px; }
+table { border-collapse: collapse; margin-bottom: 12px; }
+th, td { padding: 3px 8px; text-align: right; white-space: nowrap; }
+th { background: #18181b; font-weight: normal; color: #a1a1aa; }
+tr:nth-child(even) td { background: #303036; }
+td.name, th.name { text-align: left; }
+details { margin-bottom: 6px; }
+summary { cursor: pointer; }
+.icon { display: inline-block; width: 20px; height: 20px; background-size: cover; vertical-align: middle; margin-right: 4px; }
+.muted { color: #a1a1aa; }
+svg { background: #18181b; margin-bottom: 12px; }
+svg text { fill: #a1a1aa; font-size: 11px; }
+";
+
+// renders the saved encounter to a single html file, icons are read from the images dir and inlined
+pub fn write_report(
+    conn: &Connection,
+    id: i32,
+    path: &Path,
+    images_dir: &Path,
+) -> Result<(), String> {
+    let encounter = load_existing_encounter(conn, id)?;
+    let html = render_report(id, &encounter, images_dir);
+    fs::write(path, html).map_err(|e| format!("could not write {:?}: {}", path, e))
+}
+
+pub fn render_report(id: i32, encounter: &Encounter, images_dir: &Path) -> String {
+    let mut icons = Icons::new(images_dir);
+    let mut body = String::new();
+
+    let mut players: Vec<&EncounterEntity> = encounter
+        .entities
+        .values()
+        .filter(|e| e.entity_type == EntityType::PLAYER)
+        .collect();
+    players.sort_by_key(|e| -e.damage_stats.damage_dealt);
+
+    let date = Local
+        .timestamp_millis_opt(encounter.fight_start)
+        .single()
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    let difficulty = encounter
+        .difficulty
+        .as_ref()
+        .map_or(String::new(), |d| format!("[{}] ", d));
+    let _ = write!(
+        body,
+        "<h1>#{} {}{}</h1><div class=\"muted\">{} &middot; {} &middot; {} &middot; {} damage &middot; {} dps</div>",
+        id,
+        escape(&difficulty),
+        escape(&encounter.current_boss_name),
+        date,
+        format_duration(encounter.duration),
+        if encounter.cleared { "cleared" } else { "not cleared" },
+        format_number(encounter.encounter_damage_stats.total_damage_dealt),
+        format_number(encounter.encounter_damage_stats.dps),
+    );
+
+    render_parties(&mut body, &mut icons, encounter, &players);
+    render_skills(&mut body, &mut icons, &players);
+    let mut bosses: Vec<&EncounterEntity> = encounter
+        .entities
+        .values()
+        .filter(|e| e.entity_type == EntityType::BOSS)
+        .collect();
+    bosses.sort_by(|a, b| a.name.cmp(&b.name));
+
+    render_uptime(
+        &mut body,
+        &mut icons,
+        "Party buff uptime",
+        &players,
+        &encounter.encounter_damage_stats.buffs,
+        false,
+    );
+    render_uptime(
+        &mut body,
+        &mut icons,
+        "Debuff uptime",
+        &bosses,
+        &encounter.encounter_damage_stats.debuffs,
+        true,
+    );
+    render_timelines(&mut body, encounter, &players);
+
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>#{} {}</title><style>{}{}</style></head><body>{}</body></html>\n",
+        id,
+        escape(&encounter.current_boss_name),
+        STYLE,
+        icons.css(),
+        body
+    )
+}
+
+fn render_parties(
+    body: &mut String,
+    icons: &mut Icons,
+    encounter: &Encounter,
+    players: &[&EncounterEntity],
+) {
+    let total_damage = encounter.encounter_damage_stats.total_damage_dealt;
+    let groups = party_groups(encounter, players);
+
+    for (title, members) in groups.iter() {
+        let _ = write!(
+            body,
+            "<h2>{}</h2><table><tr><th class=\"name\">Name</th><th>Gear</th><th>Damage</th><th>Damage %</th><th>DPS</th><th>Crit %</th><th>Back %</th><th>Front %</th><th>Support %</th><th>Deaths</th><th>Counters</th></tr>",
+            title
+        );
+        for player in members {
+            let damage_stats = &player.damage_stats;
+            let skill_stats = &player.skill_stats;
+            let _ = write!(
+                body,
+                "<tr><td class=\"name\">{}{} <span class=\"muted\">{}</span></td><td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                icons.img(&format!("classes/{}.png", player.class_id), &player.class),
+                escape(&player.name),
+                escape(&player.class),
+                player.gear_score,
+                format_number(damage_stats.damage_dealt),
+                percent(damage_stats.damage_dealt, total_damage),
+                format_number(damage_stats.dps),
+                percent(skill_stats.crits, skill_stats.hits),
+                percent(skill_stats.back_attacks, skill_stats.hits),
+                percent(skill_stats.front_attacks, skill_stats.hits),
+                percent(damage_stats.buffed_by_support, damage_stats.damage_dealt),
+                damage_stats.deaths,
+                skill_stats.counters,
+            );
+        }
+        body.push_str("</table>");
+    }
+}
+
+// splits the players by misc.party_info, keeping their order inside each party
+// players the party info doesn't know about still get listed, in their own group
+pub fn party_groups<'a>(
+    encounter: &Encounter,
+    players: &[&'a EncounterEntity],
+) -> Vec<(String, Vec<&'a EncounterEntity>)> {
+    let party_info = encounter
+        .encounter_damage_stats
+        .misc
+        .as_ref()
+        .and_then(|misc| misc.party_info.clone())
+        .unwrap_or_default();
+
+    let mut parties: Vec<(i32, Vec<String>)> = party_info.into_iter().collect();
+    parties.sort_by_key(|(party, _)| *party);
+    let mut groups: Vec<(String, Vec<&EncounterEntity>)> = parties
+        .iter()
+        .map(|(party, names)| {
+            let members = players
+                .iter()
+                .filter(|p| names.contains(&p.name))
+                .copied()
+                .collect();
+            (format!("Party {}", party + 1), members)
+        })
+        .collect();
+    let ungrouped: Vec<&EncounterEntity> = players
+        .iter()
+        .filter(|p| !parties.iter().any(|(_, names)| names.contains(&p.name)))
+        .copied()
+        .collect();
+    if !ungrouped.is_empty() {
+        let title = if groups.is_empty() {
+            "Players"
+        } else {
+            "Other"
+        };
+        groups.push((title.to_string(), ungrouped));
+    }
+    groups.retain(|(_, members)| !members.is_empty());
+    groups
+}
+
+fn render_skills(body: &mut String, icons: &mut Icons, players: &[&EncounterEntity]) {
+    body.push_str("<h2>Skills</h2>");
+    for player in players {
+        let mut skills: Vec<&Skill> = player.skills.values().collect();
+        skills.sort_by_key(|s| (-s.total_damage, s.id));
+        let _ = write!(
+            body,
+            "<details><summary>{}{}</summary><table><tr><th class=\"name\">Skill</th><th>Damage</th><th>Damage %</th><th>DPS</th><th>Casts</th><th>Hits</th><th>Crit %</th><th>Back %</th><th>Front %</th><th>Max Hit</th></tr>",
+            icons.img(&format!("classes/{}.png", player.class_id), &player.class),
+            escape(&player.name),
+        );
+        for skill in skills {
+            let _ = write!(
+                body,
+                "<tr><td class=\"name\">{}{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                icons.img(&skill_icon(&skill.icon), &skill.name),
+                escape(&skill.name),
+                format_number(skill.total_damage),
+                percent(skill.total_damage, player.damage_stats.damage_dealt),
+                format_number(skill.dps),
+                skill.casts,
+                skill.hits,
+                percent(skill.crits, skill.hits),
+                percent(skill.back_attacks, skill.hits),
+                percent(skill.front_attacks, skill.hits),
+                format_number(skill.max_damage),
+            );
+        }
+        body.push_str("</table></details>");
+    }
+}
+
+// how much of the fight each buff was up on the players, or each debuff on the bosses
+fn render_uptime(
+    body: &mut String,
+    icons: &mut Icons,
+    title: &str,
+    rows: &[&EncounterEntity],
+    effects: &HashMap<i32, StatusEffect>,
+    debuff: bool,
+) {
+    let mut columns: Vec<(&i32, &StatusEffect)> = effects
+        .iter()
+        .filter(|(id, effect)| {
+            (debuff || effect.target == StatusEffectTarget::PARTY)
+                && rows.iter().any(|r| r.damage_stats.uptime.contains_key(*id))
+        })
+        .collect();
+    if columns.is_empty() {
+        return;
+    }
+    columns.sort_by(|(a_id, a), (b_id, b)| {
+        a.source
+            .name
+            .cmp(&b.source.name)
+            .then_with(|| a_id.cmp(b_id))
+    });
+
+    let _ = write!(
+        body,
+        "<h2>{}</h2><table><tr><th class=\"name\">Name</th>",
+        title
+    );
+    for (_, effect) in columns.iter() {
+        let _ = write!(
+            body,
+            "<th title=\"{}\">{}</th>",
+            escape(&effect.source.name),
+            icons.img(&skill_icon(&effect.source.icon), &effect.source.name)
+        );
+    }
+    body.push_str("</tr>");
+    for row in rows {
+        let _ = write!(body, "<tr><td class=\"name\">{}</td>", escape(&row.name));
+        for (id, _) in columns.iter() {
+            let uptime = row
+                .damage_stats
+                .uptime
+                .get(*id)
+                .copied()
+                .unwrap_or_default();
+            let _ = write!(body, "<td>{:.1}</td>", uptime * 100.0);
+        }
+        body.push_str("</tr>");
+    }
+    body.push_str("</table>");
+}
+
+fn render_timelines(body: &mut String, encounter: &Encounter, players: &[&EncounterEntity]) {
+    let series = |values: fn(&EncounterEntity) -> &Vec<i64>, step: f64| -> Vec<Series> {
+        players
+            .iter()
+            .enumerate()
+            .map(|(i, player)| Series {
+                name: player.name.clone(),
+                color: COLORS[i % COLORS.len()],
+                points: values(player)
+                    .iter()
+                    .enumerate()
+                    .map(|(t, v)| (t as f64 * step, *v as f64))
+                    .collect(),
+            })
+            .collect()
+    };
+
+    body.push_str("<h2>Average DPS</h2>");
+    body.push_str(&line_chart(
+        &series(|p| &p.damage_stats.dps_average, 5.0),
+        None,
+    ));
+    body.push_str("<h2>Rolling DPS (10s)</h2>");
+    body.push_str(&line_chart(
+        &series(|p| &p.damage_stats.dps_rolling_10s_avg, 1.0),
+        None,
+    ));
+
+    let misc = match encounter.encounter_damage_stats.misc.as_ref() {
+        Some(misc) => misc,
+        None => return,
+    };
+    if !misc.boss_hp_log.is_empty() {
+        let mut bosses: Vec<(&String, &Vec<BossHpLog>)> = misc.boss_hp_log.iter().collect();
+        bosses.sort_by_key(|(_, log)| log.first().map_or(0, |l| l.time));
+        let series: Vec<Series> = bosses
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, log))| Series {
+                name: name.clone(),
+                color: COLORS[i % COLORS.len()],
+                points: log
+                    .iter()
+                    .map(|l| (l.time as f64, l.p as f64 * 100.0))
+                    .collect(),
+            })
+            .collect();
+        body.push_str("<h2>Boss HP %</h2>");
+        body.push_str(&line_chart(&series, Some(100.0)));
+    }
+    if let Some(stagger) = misc.stagger_stats.as_ref() {
+        let series = Series {
+            name: format!("Stagger (average {:.1}%)", stagger.average),
+            color: COLORS[3],
+            points: stagger
+                .log
+                .iter()
+                .map(|(t, p)| (*t as f64, *p as f64))
+                .collect(),
+        };
+        body.push_str("<h2>Stagger %</h2>");
+        body.push_str(&line_chart(&[series], Some(100.0)));
+    }
+}
+
+struct Series {
+    name: String,
+    color: &'static str,
+    points: Vec<(f64, f64)>,
+}
+
+// a plain svg line chart, x is seconds into the fight
+fn line_chart(series: &[Series], y_max: Option<f64>) -> String {
+    let points = series.iter().flat_map(|s| s.points.iter());
+    let x_max = points.clone().map(|p| p.0).fold(0.0, f64::max);
+    let y_max = y_max.unwrap_or_else(|| points.map(|p| p.1).fold(0.0, f64::max));
+    if x_max <= 0.0 || y_max <= 0.0 {
+        return "<div class=\"muted\">no data</div>".to_string();
+    }
+
+    let plot_width = CHART_WIDTH - CHART_PADDING * 2.0;
+    let plot_height = CHART_HEIGHT - CHART_PADDING * 2.0;
+    let x = |v: f64| CHART_PADDING + v / x_max * plot_width;
+    let y = |v: f64| CHART_HEIGHT - CHART_PADDING - v / y_max * plot_height;
+
+    let mut svg = format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" xmlns=\"http://www.w3.org/2000/svg\">",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT
+    );
+    let _ = write!(
+        svg,
+        "<line x1=\"{l}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#52525b\"/><line x1=\"{l}\" y1=\"{t}\" x2=\"{l}\" y2=\"{b}\" stroke=\"#52525b\"/>",
+        l = CHART_PADDING,
+        r = CHART_WIDTH - CHART_PADDING,
+        t = CHART_PADDING,
+        b = CHART_HEIGHT - CHART_PADDING
+    );
+    let _ = write!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text><text x=\"{}\" y=\"{}\" text-anchor=\"end\">0</text><text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+        CHART_PADDING - 4.0,
+        CHART_PADDING + 4.0,
+        format_number(y_max as i64),
+        CHART_PADDING - 4.0,
+        CHART_HEIGHT - CHART_PADDING + 4.0,
+        CHART_WIDTH - CHART_PADDING,
+        CHART_HEIGHT - CHART_PADDING + 16.0,
+        format_duration(x_max as i64 * 1000),
+    );
+
+    for (i, s) in series.iter().enumerate() {
+        let polyline: Vec<String> = s
+            .points
+            .iter()
+            .map(|&(px, py)| format!("{:.1},{:.1}", x(px), y(py)))
+            .collect();
+        let _ = write!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"><title>{}</title></polyline>",
+            s.color,
+            polyline.join(" "),
+            escape(&s.name)
+        );
+        let _ = write!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" style=\"fill: {}\">{}</text>",
+            CHART_PADDING + (i % 4) as f64 * (plot_width / 4.0),
+            14 + (i / 4) * 14,
+            s.color,
+            escape(&s.name)
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+// each image is embedded once as a css class, rows then reference the class
+struct Icons {
+    images_dir: PathBuf,
+    classes: HashMap<String, Option<String>>,
+    css: String,
+}
+
+impl Icons {
+    fn new(images_dir: &Path) -> Self {
+        Self {
+            images_dir: images_dir.to_path_buf(),
+            classes: HashMap::new(),
+            css: String::new(),
+        }
+    }
+
+    fn img(&mut self, relative_path: &str, title: &str) -> String {
+        let next = self.classes.len();
+        let class = self
+            .classes
+            .entry(relative_path.to_string())
+            .or_insert_with(|| {
+                let bytes = fs::read(self.images_dir.join(relative_path)).ok()?;
+                let class = format!("i{}", next);
+                let _ = writeln!(
+                    self.css,
+                    ".{} {{ background-image: url(data:image/png;base64,{}); }}",
+                    class,
+                    base64(&bytes)
+                );
+                Some(class)
+            });
+        match class {
+            Some(class) => format!(
+                "<span class=\"icon {}\" title=\"{}\"></span>",
+                class,
+                escape(title)
+            ),
+            None => String::new(),
+        }
+    }
+
+    fn css(&self) -> &str {
+        &self.css
+    }
+}
+
+fn skill_icon(icon: &str) -> String {
+    format!(
+        "skills/{}",
+        if icon.is_empty() { "unknown.png" } else { icon }
+    )
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len() * 4 / 3 + 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn format_number(value: i64) -> String {
+    let digits = value.abs().to_string();
+    let groups: Vec<&str> = digits
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        .map(|group| std::str::from_utf8(group).unwrap_or_default())
+        .collect();
+    if value < 0 {
+        format!("-{}", groups.join(","))
+    } else {
+        groups.join(",")
+    }
+}
@@ -0,0 +1,332 @@
+use rusqlite::{params, params_from_iter, types::Value, Connection, Result};
+
+use crate::parser::models::{
+    EncounterPreview, EncounterSort, EncountersOverview, SearchFilter, SortOrder,
+};
+
+// where clause and bound params for the logs search, shared by the page and count queries
+// everything user supplied is bound, only the shape of the query is formatted in
+pub struct EncounterQuery {
+    conditions: Vec<String>,
+    params: Vec<Value>,
+    order_by: String,
+}
+
+impl EncounterQuery {
+    pub fn new(search: &str, filter: &SearchFilter) -> Self {
+        let mut query = Self {
+            conditions: Vec::new(),
+            params: Vec::new(),
+            order_by: order_by(filter.sort, filter.order),
+        };
+
+        query.push(
+            "e.duration > ?",
+            [Value::Integer(filter.min_duration as i64 * 1000)],
+        );
+
+        if let Some(expression) = match_expression(search) {
+            query.push(
+                "e.id IN (SELECT rowid FROM encounter_search WHERE encounter_search MATCH ?)",
+                [Value::Text(expression)],
+            );
+        }
+
+        if filter.max_duration > 0 {
+            query.push(
+                "e.duration <= ?",
+                [Value::Integer(filter.max_duration as i64 * 1000)],
+            );
+        }
+
+        if !filter.bosses.is_empty() {
+            query.push_in("current_boss", &filter.bosses);
+        }
+
+        if !filter.classes.is_empty() {
+            query.push_in("ent.class", &filter.classes);
+        }
+
+        if filter.cleared {
+            query.push("cleared = 1", []);
+        }
+
+        if filter.favorite {
+            query.push("favorite = 1", []);
+        }
+
+        if filter.boss_only_damage {
+            query.push("boss_only_damage = 1", []);
+        }
+
+        if !filter.difficulty.is_empty() {
+            query.push("difficulty = ?", [Value::Text(filter.difficulty.clone())]);
+        }
+
+        if let Some(from_date) = filter.from_date {
+            query.push("e.fight_start >= ?", [Value::Integer(from_date)]);
+        }
+
+        if let Some(to_date) = filter.to_date {
+            query.push("e.fight_start <= ?", [Value::Integer(to_date)]);
+        }
+
+        if filter.local_player_only {
+            query.push("ent.name = e.local_player", []);
+        }
+
+        if !filter.party_members.is_empty() {
+            let placeholders = vec!["?"; filter.party_members.len()].join(",");
+            let mut params: Vec<Value> = filter
+                .party_members
+                .iter()
+                .map(|name| Value::Text(name.clone()))
+                .collect();
+            params.push(Value::Integer(filter.party_members.len() as i64));
+            query.push(
+                &format!(
+                    "e.id IN (
+                    SELECT encounter_id
+                    FROM entity
+                    WHERE entity_type = 'PLAYER' AND name IN ({})
+                    GROUP BY encounter_id
+                    HAVING COUNT(DISTINCT name) = ?)",
+                    placeholders
+                ),
+                params,
+            );
+        }
+
+        query
+    }
+
+    fn push(&mut self, condition: &str, params: impl IntoIterator<Item = Value>) {
+        self.conditions.push(condition.to_string());
+        self.params.extend(params);
+    }
+
+    fn push_in(&mut self, column: &str, values: &[String]) {
+        let placeholders = vec!["?"; values.len()].join(",");
+        self.push(
+            &format!("({} IN ({}))", column, placeholders),
+            values.iter().map(|value| Value::Text(value.clone())),
+        );
+    }
+
+    pub fn where_clause(&self) -> String {
+        self.conditions.join(" AND ")
+    }
+
+    pub fn load_page(
+        &self,
+        conn: &Connection,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<EncounterPreview>> {
+        let query = format!(
+            "SELECT
+        e.id,
+        e.fight_start,
+        e.current_boss,
+        e.duration,
+        e.difficulty,
+        e.favorite,
+        e.cleared,
+        e.local_player,
+        (
+            SELECT GROUP_CONCAT(ordered_classes.class_info, ',')
+            FROM (
+                SELECT en.class_id || ':' || en.name AS class_info
+                FROM entity en
+                WHERE en.encounter_id = e.id AND en.entity_type = 'PLAYER'
+                ORDER BY dps DESC
+            ) AS ordered_classes
+        ) AS classes
+        FROM encounter e
+        JOIN entity ent ON e.id = ent.encounter_id
+        WHERE {}
+        GROUP BY encounter_id
+        ORDER BY {}
+        LIMIT ?
+        OFFSET ?",
+            self.where_clause(),
+            self.order_by
+        );
+
+        let mut stmt = conn.prepare_cached(&query)?;
+
+        let offset = (page - 1) * page_size;
+        let mut params = self.params.clone();
+        params.push(Value::Integer(page_size as i64));
+        params.push(Value::Integer(offset as i64));
+
+        let encounter_iter = stmt.query_map(params_from_iter(params), |row| {
+            let classes = row.get(8).unwrap_or_else(|_| "".to_string());
+
+            let (classes, names) = classes
+                .split(',')
+                .map(|s| {
+                    let info: Vec<&str> = s.split(':').collect();
+                    (info[0].parse::<i32>().unwrap_or(101), info[1].to_string())
+                })
+                .unzip();
+
+            Ok(EncounterPreview {
+                id: row.get(0)?,
+                fight_start: row.get(1)?,
+                boss_name: row.get(2)?,
+                duration: row.get(3)?,
+                classes,
+                names,
+                difficulty: row.get(4)?,
+                favorite: row.get(5)?,
+                cleared: row.get(6)?,
+                local_player: row.get(7)?,
+            })
+        })?;
+
+        encounter_iter.collect()
+    }
+
+    pub fn count(&self, conn: &Connection) -> Result<i32> {
+        let query = format!(
+            "
+        SELECT COUNT(*)
+        FROM (SELECT encounter_id
+            FROM encounter e
+            JOIN entity ent ON e.id = ent.encounter_id
+            WHERE {}
+            GROUP BY encounter_id)
+            ",
+            self.where_clause()
+        );
+
+        conn.query_row_and_then(&query, params_from_iter(self.params.iter()), |row| {
+            row.get(0)
+        })
+    }
+}
+
+fn order_by(sort: EncounterSort, order: SortOrder) -> String {
+    let column = match sort {
+        EncounterSort::Date => "e.fight_start",
+        EncounterSort::Duration => "e.duration",
+        EncounterSort::Dps => "e.dps",
+        EncounterSort::LocalPlayerDps => {
+            "(SELECT lp.dps FROM entity lp WHERE lp.encounter_id = e.id AND lp.name = e.local_player)"
+        }
+    };
+    let direction = match order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    // newest first when the sort key ties
+    format!("{} {}, e.fight_start DESC", column, direction)
+}
+
+pub fn load_encounters_preview(
+    conn: &Connection,
+    page: i32,
+    page_size: i32,
+    search: &str,
+    filter: &SearchFilter,
+) -> Result<EncountersOverview> {
+    let query = EncounterQuery::new(search, filter);
+    Ok(EncountersOverview {
+        encounters: query.load_page(conn, page, page_size)?,
+        total_encounters: query.count(conn)?,
+    })
+}
+
+// search terms are prefix matched against every column unless they name one,
+// e.g. `boss:Thaemine class:Bard player:Foo` or `boss:"Phantom Legion"`
+pub fn match_expression(search: &str) -> Option<String> {
+    let terms: Vec<String> = split_terms(search)
+        .into_iter()
+        .filter_map(|term| {
+            let (column, value) = match term.split_once(':') {
+                Some((key, value)) => match search_column(key) {
+                    Some(column) => (Some(column), value.to_string()),
+                    None => (None, term.clone()),
+                },
+                None => (None, term.clone()),
+            };
+            let value = value.trim_matches('"');
+            if !value.chars().any(char::is_alphanumeric) {
+                return None;
+            }
+            // quoted so fts syntax in the search box is treated as text
+            let phrase = format!("\"{}\"*", value.replace('"', "\"\""));
+            Some(match column {
+                Some(column) => format!("{} : {}", column, phrase),
+                None => phrase,
+            })
+        })
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" AND "))
+    }
+}
+
+fn search_column(key: &str) -> Option<&'static str> {
+    match key.to_lowercase().as_str() {
+        "boss" => Some("boss"),
+        "player" | "name" => Some("players"),
+        "class" => Some("classes"),
+        "difficulty" | "diff" => Some("difficulty"),
+        "note" | "notes" => Some("notes"),
+        _ => None,
+    }
+}
+
+// splits on whitespace, keeping double quoted sections together
+fn split_terms(search: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut term = String::new();
+    let mut quoted = false;
+    for c in search.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                term.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !term.is_empty() {
+                    terms.push(std::mem::take(&mut term));
+                }
+            }
+            c => term.push(c),
+        }
+    }
+    if !term.is_empty() {
+        terms.push(term);
+    }
+    terms
+}
+
+// (re)builds the search row for an encounter after it and its entities are inserted
+pub fn index_encounter(conn: &Connection, encounter_id: i64) -> Result<()> {
+    conn.execute(
+        "DELETE FROM encounter_search WHERE rowid = ?",
+        params![encounter_id],
+    )?;
+    conn.execute(
+        "
+    INSERT INTO encounter_search (rowid, boss, players, classes, difficulty, notes)
+    SELECT
+        e.id,
+        e.current_boss,
+        (SELECT GROUP_CONCAT(name, ' ') FROM entity WHERE encounter_id = e.id AND entity_type = 'PLAYER'),
+        (SELECT GROUP_CONCAT(class, ' ') FROM entity WHERE encounter_id = e.id AND entity_type = 'PLAYER'),
+        e.difficulty,
+        e.notes
+    FROM encounter e
+    WHERE e.id = ?
+    ",
+        params![encounter_id],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::parser::models::{EncounterEntity, EntityType};
+
+// writes a player's skills and buffs into entity_skill and entity_buff, alongside the json in entity
+pub fn insert_entity_stats(
+    conn: &Connection,
+    encounter_id: i64,
+    entity: &EncounterEntity,
+) -> Result<()> {
+    if entity.entity_type != EntityType::PLAYER {
+        return Ok(());
+    }
+
+    let mut skill_stmt = conn.prepare_cached(
+        "
+    INSERT INTO entity_skill (
+        encounter_id,
+        player,
+        skill_id,
+        skill_name,
+        total_damage,
+        max_damage,
+        hits,
+        crits,
+        crit_damage,
+        casts,
+        back_attacks,
+        front_attacks,
+        back_attack_damage,
+        front_attack_damage,
+        buffed_by_support,
+        debuffed_by_support,
+        dps
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+    )?;
+    for (skill_id, skill) in entity.skills.iter() {
+        skill_stmt.execute(params![
+            encounter_id,
+            entity.name,
+            skill_id,
+            skill.name,
+            skill.total_damage,
+            skill.max_damage,
+            skill.hits,
+            skill.crits,
+            skill.crit_damage,
+            skill.casts,
+            skill.back_attacks,
+            skill.front_attacks,
+            skill.back_attack_damage,
+            skill.front_attack_damage,
+            skill.buffed_by_support,
+            skill.debuffed_by_support,
+            skill.dps
+        ])?;
+    }
+
+    let mut buff_stmt = conn.prepare_cached(
+        "
+    INSERT INTO entity_buff (encounter_id, player, buff_id, debuff, damage)
+    VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (buff_id, damage) in entity.damage_stats.buffed_by.iter() {
+        buff_stmt.execute(params![encounter_id, entity.name, buff_id, false, damage])?;
+    }
+    for (debuff_id, damage) in entity.damage_stats.debuffed_by.iter() {
+        buff_stmt.execute(params![encounter_id, entity.name, debuff_id, true, damage])?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::fmt::Write;
+
+use rusqlite::Connection;
+
+use crate::db::encounters::load_existing_encounter;
+use crate::db::format::{format_duration, percent};
+use crate::db::report::party_groups;
+use crate::parser::models::*;
+
+// renders a saved encounter as markdown that can be pasted into chat
+pub fn encounter_summary(
+    conn: &Connection,
+    id: i32,
+    template: SummaryTemplate,
+    split_parties: bool,
+) -> Result<String, String> {
+    let encounter = load_existing_encounter(conn, id)?;
+    Ok(format_summary(&encounter, template, split_parties))
+}
+
+pub fn format_summary(
+    encounter: &Encounter,
+    template: SummaryTemplate,
+    split_parties: bool,
+) -> String {
+    let mut players: Vec<&EncounterEntity> = encounter
+        .entities
+        .values()
+        .filter(|e| e.entity_type == EntityType::PLAYER)
+        .collect();
+    players.sort_by_key(|e| (-e.damage_stats.damage_dealt, e.name.clone()));
+
+    let mut out = String::new();
+    let difficulty = encounter
+        .difficulty
+        .as_ref()
+        .map_or(String::new(), |d| format!("[{}] ", d));
+    let _ = writeln!(
+        out,
+        "**{}{}** · {} · {}",
+        markdown(&difficulty),
+        markdown(&encounter.current_boss_name),
+        format_duration(encounter.duration),
+        if encounter.cleared { "Cleared" } else { "Wipe" }
+    );
+    let _ = writeln!(
+        out,
+        "Total {} damage · {} dps",
+        abbreviate(encounter.encounter_damage_stats.total_damage_dealt),
+        abbreviate(encounter.encounter_damage_stats.dps)
+    );
+
+    let groups = if split_parties {
+        party_groups(encounter, &players)
+    } else {
+        vec![(String::new(), players)]
+    };
+    let total_damage = encounter.encounter_damage_stats.total_damage_dealt;
+    // ranks stay encounter wide when the players are split into parties
+    let mut rank = 0;
+    for (title, members) in groups {
+        out.push('\n');
+        if !title.is_empty() {
+            let _ = writeln!(out, "__{}__", title);
+        }
+        match template {
+            SummaryTemplate::Compact => {
+                for player in members {
+                    rank += 1;
+                    let damage_stats = &player.damage_stats;
+                    let _ = write!(
+                        out,
+                        "{}. **{}** ({}) {} dps · {}% · sup {}%",
+                        rank,
+                        markdown(&player.name),
+                        player.class,
+                        abbreviate(damage_stats.dps),
+                        percent(damage_stats.damage_dealt, total_damage),
+                        percent(damage_stats.buffed_by_support, damage_stats.damage_dealt)
+                    );
+                    if damage_stats.deaths > 0 {
+                        let _ = write!(out, " · {}", deaths(damage_stats.deaths));
+                    }
+                    out.push('\n');
+                }
+            }
+            SummaryTemplate::Table => {
+                let rows: Vec<[String; 7]> = members
+                    .iter()
+                    .map(|player| {
+                        rank += 1;
+                        let damage_stats = &player.damage_stats;
+                        [
+                            rank.to_string(),
+                            player.name.clone(),
+                            player.class.clone(),
+                            abbreviate(damage_stats.dps),
+                            percent(damage_stats.damage_dealt, total_damage),
+                            percent(damage_stats.buffed_by_support, damage_stats.damage_dealt),
+                            damage_stats.deaths.to_string(),
+                        ]
+                    })
+                    .collect();
+                out.push_str("```\n");
+                out.push_str(&table(
+                    ["#", "Name", "Class", "DPS", "Dmg%", "Sup%", "Deaths"],
+                    &rows,
+                ));
+                out.push_str("```\n");
+            }
+            SummaryTemplate::Detailed => {
+                for player in members {
+                    rank += 1;
+                    let damage_stats = &player.damage_stats;
+                    let skill_stats = &player.skill_stats;
+                    let _ = writeln!(
+                        out,
+                        "{}. **{}** ({}, {:.2})",
+                        rank,
+                        markdown(&player.name),
+                        player.class,
+                        player.gear_score
+                    );
+                    let _ = writeln!(
+                        out,
+                        "  - {} damage ({}%) · {} dps",
+                        abbreviate(damage_stats.damage_dealt),
+                        percent(damage_stats.damage_dealt, total_damage),
+                        abbreviate(damage_stats.dps)
+                    );
+                    let _ = writeln!(
+                        out,
+                        "  - crit {}% · support {}% · {} · {} counters",
+                        percent(skill_stats.crits, skill_stats.hits),
+                        percent(damage_stats.buffed_by_support, damage_stats.damage_dealt),
+                        deaths(damage_stats.deaths),
+                        skill_stats.counters
+                    );
+                }
+            }
+        }
+    }
+
+    out
+}
+
+// pads every column to its widest cell, numbers are right aligned
+fn table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+    let mut widths = header.map(|h| h.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    let header = header.map(|h| h.to_string());
+    for row in std::iter::once(&header).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                // the name and class columns read better left aligned
+                if i == 1 || i == 2 {
+                    format!("{:<width$}", cell, width = width)
+                } else {
+                    format!("{:>width$}", cell, width = width)
+                }
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+// the same short form the meter shows, 1234567 is 1.2m
+pub fn abbreviate(value: i64) -> String {
+    let n = value as f64;
+    let (divisor, suffix) = match value {
+        v if v >= 1_000_000_000_000 => (1e12, "t"),
+        v if v >= 1_000_000_000 => (1e9, "b"),
+        v if v >= 1_000_000 => (1e6, "m"),
+        v if v >= 1_000 => (1e3, "k"),
+        _ => return value.to_string(),
+    };
+    format!("{:.1}{}", n / divisor, suffix)
+}
+
+fn deaths(count: i64) -> String {
+    if count == 1 {
+        "1 death".to_string()
+    } else {
+        format!("{} deaths", count)
+    }
+}
+
+// names can contain underscores, which discord would otherwise turn into italics
+pub fn markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,88 @@
+use rusqlite::Connection;
+
+use crate::db::characters::{get_characters, update_character};
+use crate::db::migrate;
+use crate::parser::models::{EncounterEntity, EntityType};
+
+fn setup() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+    conn.execute_batch("INSERT INTO encounter (id) VALUES (1), (2), (3);")
+        .unwrap();
+    conn
+}
+
+fn player(character_id: u64, name: &str, class_id: u32, gear_score: f32) -> EncounterEntity {
+    EncounterEntity {
+        character_id,
+        name: name.to_string(),
+        entity_type: EntityType::PLAYER,
+        class_id,
+        gear_score,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn renamed_character_keeps_its_history() {
+    let conn = setup();
+    update_character(&conn, 1, 1000, &player(42, "Alice", 204, 1600.0)).unwrap();
+    update_character(&conn, 2, 2000, &player(42, "Alice", 204, 1610.0)).unwrap();
+    update_character(&conn, 3, 3000, &player(42, "Alicia", 204, 1620.0)).unwrap();
+
+    let characters = get_characters(&conn, "Alice").unwrap();
+    assert_eq!(characters.len(), 1);
+    let character = &characters[0];
+    assert_eq!(character.character_id, 42);
+    assert_eq!(character.name, "Alicia");
+    assert_eq!(character.class, "Bard");
+    assert_eq!((character.first_seen, character.last_seen), (1000, 3000));
+
+    let names: Vec<(&str, i64, i64)> = character
+        .names
+        .iter()
+        .map(|n| (n.name.as_str(), n.first_seen, n.last_seen))
+        .collect();
+    assert_eq!(names, vec![("Alice", 1000, 2000), ("Alicia", 3000, 3000)]);
+
+    let gear_scores: Vec<f64> = character.gear_scores.iter().map(|g| g.gear_score).collect();
+    assert_eq!(gear_scores, vec![1600.0, 1610.0, 1620.0]);
+}
+
+#[test]
+fn older_log_does_not_overwrite_current_name() {
+    let conn = setup();
+    update_character(&conn, 2, 2000, &player(42, "Alicia", 204, 1610.0)).unwrap();
+    update_character(&conn, 1, 1000, &player(42, "Alice", 204, 1600.0)).unwrap();
+
+    let character = &get_characters(&conn, "Alice").unwrap()[0];
+    assert_eq!(character.name, "Alicia");
+    assert_eq!((character.first_seen, character.last_seen), (1000, 2000));
+    assert_eq!(character.gear_scores[0].encounter_id, 1);
+}
+
+#[test]
+fn same_name_different_characters_stay_apart() {
+    let conn = setup();
+    update_character(&conn, 1, 1000, &player(42, "Alice", 204, 1600.0)).unwrap();
+    update_character(&conn, 2, 2000, &player(77, "Alice", 102, 1500.0)).unwrap();
+
+    let characters = get_characters(&conn, "Alice").unwrap();
+    let ids: Vec<u64> = characters.iter().map(|c| c.character_id).collect();
+    assert_eq!(ids, vec![77, 42]);
+    assert_eq!(characters[0].gear_scores.len(), 1);
+    assert_eq!(characters[1].gear_scores.len(), 1);
+}
+
+#[test]
+fn skips_npcs_and_unknown_ids() {
+    let conn = setup();
+    update_character(&conn, 1, 1000, &player(0, "Alice", 204, 1600.0)).unwrap();
+    let boss = EncounterEntity {
+        entity_type: EntityType::BOSS,
+        ..player(42, "Alice", 0, 0.0)
+    };
+    update_character(&conn, 1, 1000, &boss).unwrap();
+
+    assert!(get_characters(&conn, "Alice").unwrap().is_empty());
+}
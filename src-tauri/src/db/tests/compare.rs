@@ -0,0 +1,96 @@
+use hashbrown::HashMap;
+
+use crate::db::compare::compare;
+use crate::parser::models::*;
+
+fn skill(id: i32, damage: i64, casts: i64, hits: i64, crits: i64, back_attacks: i64) -> Skill {
+    Skill {
+        id,
+        name: format!("skill {}", id),
+        total_damage: damage,
+        casts,
+        hits,
+        crits,
+        back_attacks,
+        ..Default::default()
+    }
+}
+
+fn encounter(duration: i64, dps: i64, skills: Vec<Skill>, buffed_by: i64) -> Encounter {
+    let damage_dealt = skills.iter().map(|s| s.total_damage).sum();
+    let hits = skills.iter().map(|s| s.hits).sum();
+    let player = EncounterEntity {
+        name: "Alice".to_string(),
+        entity_type: EntityType::PLAYER,
+        class: "Berserker".to_string(),
+        skills: skills.into_iter().map(|s| (s.id, s)).collect(),
+        damage_stats: DamageStats {
+            damage_dealt,
+            dps,
+            buffed_by: HashMap::from([(999001, buffed_by)]),
+            ..Default::default()
+        },
+        skill_stats: SkillStats {
+            casts: 10,
+            hits,
+            identity_stats: Some("{\"average\": 1.5, \"log\": []}".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    Encounter {
+        current_boss_name: "Valtan".to_string(),
+        duration,
+        entities: HashMap::from([("Alice".to_string(), player)]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn compares_player_between_encounters() {
+    let base = encounter(
+        120_000,
+        1000,
+        vec![skill(1, 600, 4, 10, 5, 2), skill(2, 400, 2, 4, 0, 0)],
+        500,
+    );
+    let other = encounter(
+        60_000,
+        1500,
+        vec![skill(1, 900, 4, 10, 8, 10), skill(3, 100, 1, 1, 1, 0)],
+        250,
+    );
+
+    let comparison = compare(&base, &other, "Alice").unwrap();
+    assert_eq!(comparison.dps_delta, 500);
+    assert_eq!(comparison.base.casts_per_minute, 5.0);
+    assert_eq!(comparison.other.casts_per_minute, 10.0);
+    assert_eq!(comparison.base.identity_average, Some(1.5));
+
+    let ids: Vec<i32> = comparison.skills.iter().map(|s| s.skill_id).collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+
+    let skill_1 = &comparison.skills[0];
+    let base_skill = skill_1.base.as_ref().unwrap();
+    let other_skill = skill_1.other.as_ref().unwrap();
+    assert_eq!(base_skill.damage_share, 0.6);
+    assert_eq!(other_skill.damage_share, 0.9);
+    assert_eq!(base_skill.crit_rate, 0.5);
+    assert_eq!(other_skill.back_attack_rate, 1.0);
+    assert_eq!(base_skill.casts_per_minute, 2.0);
+
+    assert!(comparison.skills[1].other.is_none());
+    assert!(comparison.skills[2].base.is_none());
+
+    assert_eq!(comparison.buffs.len(), 1);
+    assert_eq!(comparison.buffs[0].base, 0.5);
+    assert_eq!(comparison.buffs[0].other, 0.25);
+}
+
+#[test]
+fn missing_player_is_an_error() {
+    let base = encounter(60_000, 1000, vec![skill(1, 100, 1, 1, 0, 0)], 0);
+    let other = Encounter::default();
+    assert!(compare(&base, &other, "Alice").is_err());
+    assert!(compare(&base, &base, "Bob").is_err());
+}
@@ -0,0 +1,160 @@
+use std::{env, fs, process};
+
+use hashbrown::HashMap;
+use rusqlite::Connection;
+
+use crate::db::csv::{encounter_rows, export_csv};
+use crate::db::migrate;
+use crate::parser::models::*;
+
+fn player(name: &str, damage: i64, skills: &[(i32, &str, i64)]) -> EncounterEntity {
+    EncounterEntity {
+        name: name.to_string(),
+        entity_type: EntityType::PLAYER,
+        class: "Bard".to_string(),
+        gear_score: 1620.5,
+        damage_stats: DamageStats {
+            damage_dealt: damage,
+            dps: damage / 100,
+            buffed_by_support: damage / 2,
+            deaths: 1,
+            ..Default::default()
+        },
+        skill_stats: SkillStats {
+            hits: 10,
+            crits: 5,
+            back_attacks: 2,
+            front_attacks: 1,
+            counters: 3,
+            casts: 8,
+            ..Default::default()
+        },
+        skills: skills
+            .iter()
+            .map(|&(id, name, damage)| {
+                (
+                    id,
+                    Skill {
+                        id,
+                        name: name.to_string(),
+                        total_damage: damage,
+                        hits: 4,
+                        crits: 1,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn encounter() -> Encounter {
+    let mut entities = HashMap::new();
+    entities.insert(
+        "Alice".to_string(),
+        player(
+            "Alice",
+            1000,
+            &[(1, "Sound Shock", 400), (2, "Sonatina", 600)],
+        ),
+    );
+    entities.insert(
+        "Bob, the Second".to_string(),
+        player("Bob, the Second", 3000, &[(3, "\"Big\" Hit", 3000)]),
+    );
+    entities.insert(
+        "Valtan".to_string(),
+        EncounterEntity {
+            name: "Valtan".to_string(),
+            entity_type: EntityType::BOSS,
+            ..Default::default()
+        },
+    );
+    Encounter {
+        current_boss_name: "Valtan".to_string(),
+        difficulty: Some("Hard".to_string()),
+        cleared: true,
+        duration: 100_500,
+        entities,
+        encounter_damage_stats: EncounterDamageStats {
+            total_damage_dealt: 4000,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn fields(row: &str) -> Vec<&str> {
+    row.trim_end_matches("\r\n").split(',').collect()
+}
+
+#[test]
+fn player_rows_are_sorted_by_damage() {
+    let (players, _) = encounter_rows(7, &encounter());
+    assert_eq!(players.len(), 2);
+    assert!(players[0].contains("\"Bob, the Second\""));
+
+    let alice = fields(&players[1]);
+    assert_eq!(alice[0], "7");
+    assert_eq!(
+        &alice[2..],
+        [
+            "Valtan", "Hard", "true", "100", "Alice", "Bard", "1620.50", "1000", "25.0", "10",
+            "50.0", "20.0", "10.0", "50.0", "1", "3", "8",
+        ]
+    );
+}
+
+#[test]
+fn skill_rows_are_escaped() {
+    let (_, skills) = encounter_rows(7, &encounter());
+    let names: Vec<String> = skills
+        .iter()
+        .map(|row| {
+            let start = row.find("Valtan,Hard,").unwrap() + "Valtan,Hard,".len();
+            row[start..].trim_end().to_string()
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "\"Bob, the Second\",Bard,3,\"\"\"Big\"\" Hit\",3000,100.0,0,0,0,4,25.0,0.0,0.0",
+            "Alice,Bard,2,Sonatina,600,60.0,0,0,0,4,25.0,0.0,0.0",
+            "Alice,Bard,1,Sound Shock,400,40.0,0,0,0,4,25.0,0.0,0.0",
+        ]
+    );
+}
+
+#[test]
+fn export_writes_both_files_oldest_first() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+    conn.execute_batch(
+        "
+        INSERT INTO encounter (id, fight_start, last_combat_packet, duration, local_player, current_boss,
+            total_damage_dealt, top_damage_dealt, total_damage_taken, top_damage_taken, dps, cleared)
+        VALUES (1, 2000, 0, 0, '', 'Vykas', 0, 0, 0, 0, 0, 1), (2, 1000, 0, 0, '', 'Valtan', 0, 0, 0, 0, 0, 1);
+        INSERT INTO entity (name, encounter_id, entity_type, npc_id, class_id, class, gear_score,
+            current_hp, max_hp, is_dead, skills, damage_stats, skill_stats)
+        VALUES ('Alice', 1, 'PLAYER', 0, 204, 'Bard', 1620, 0, 0, 0, '{}', '{}', '{}'),
+            ('Alice', 2, 'PLAYER', 0, 204, 'Bard', 1620, 0, 0, 0, '{}', '{}', '{}');
+        ",
+    )
+    .unwrap();
+
+    let dir = env::temp_dir().join(format!("loa-logs-csv-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (players, skills) = export_csv(&conn, &[1, 2], &dir).unwrap();
+
+    let players = fs::read_to_string(players).unwrap();
+    let lines: Vec<&str> = players.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("encounter_id,date,boss"));
+    assert!(lines[1].starts_with("2,"));
+    assert!(lines[2].starts_with("1,"));
+    assert_eq!(fs::read_to_string(skills).unwrap().lines().count(), 1);
+
+    assert!(export_csv(&conn, &[3], &dir).is_err());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,165 @@
+use std::{env, fs, path::PathBuf, process};
+
+use rusqlite::Connection;
+
+use crate::db::encounters::load_encounter;
+use crate::db::export::{export_encounter, import_encounter, parse_export};
+use crate::db::migrate;
+
+fn setup() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+    conn.execute_batch(
+        r#"
+        INSERT INTO encounter (id, fight_start, last_combat_packet, duration, local_player, current_boss,
+            difficulty, cleared, total_damage_dealt, top_damage_dealt, total_damage_taken, top_damage_taken,
+            dps, buffs, debuffs, misc, damage_by_target, version)
+        VALUES (1, 1000, 301000, 300000, 'Alice', 'Valtan', 'Hard', 1, 5000, 5000, 0, 0, 16,
+            '{"999001": {"target": "PARTY", "category": "buff", "buffCategory": "classskill", "buffType": 1,
+                "uniqueGroup": 0, "source": {"name": "Serenade", "desc": "", "icon": ""}}}',
+            '{}', '{"raidClear": true}', '{"480005": {"name": "Valtan", "damage": 4000}, "480010": {"name": "Demon Dog", "damage": 1000}}', 3);
+        INSERT INTO entity (name, encounter_id, entity_type, npc_id, class_id, class, gear_score,
+            current_hp, max_hp, is_dead, character_id, dps, skills, damage_stats, skill_stats)
+        VALUES ('Alice', 1, 'PLAYER', 0, 204, 'Bard', 1620.0, 100, 100, 0, 42, 16,
+            '{"21020": {"id": 21020, "name": "Sound Shock", "totalDamage": 5000, "maxDamage": 900, "hits": 10, "crits": 4}}',
+            '{"damageDealt": 5000, "buffedBy": {"999001": 2500}}', '{"hits": 10, "crits": 4}');
+        INSERT INTO entity (name, encounter_id, entity_type, npc_id, class_id, class, gear_score,
+            current_hp, max_hp, is_dead, skills, damage_stats, skill_stats)
+        VALUES ('Valtan', 1, 'BOSS', 480010, 0, '', 0, 0, 1000000, 1, '{}', '{}', '{}');
+        "#,
+    )
+    .unwrap();
+    conn
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("loa-logs-export-{}-{}", process::id(), name))
+}
+
+fn round_trip(file_name: &str) {
+    let conn = setup();
+    let path = temp_path(file_name);
+    export_encounter(&conn, 1, &path, "1.2.3").unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    let export = parse_export(&bytes).unwrap();
+    assert_eq!(export.app_version, "1.2.3");
+    assert_eq!(export.encounter.entities.len(), 2);
+
+    // a teammate's meter has its own db
+    let mut other = Connection::open_in_memory().unwrap();
+    migrate(&mut other).unwrap();
+    let id = import_encounter(&mut other, &path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let imported = load_encounter(&other, &id.to_string());
+    assert!(imported.imported);
+    assert!(!load_encounter(&conn, "1").imported);
+    assert_eq!(imported.current_boss_name, "Valtan");
+    assert_eq!(imported.difficulty.as_deref(), Some("Hard"));
+    // saved by an older meter, so its version is kept rather than the importer's
+    assert_eq!(imported.version, 3);
+    assert!(imported.cleared);
+    assert_eq!(
+        imported.encounter_damage_stats.buffs[&999001].source.name,
+        "Serenade"
+    );
+    assert_eq!(
+        imported.encounter_damage_stats.damage_by_target[&480010].damage,
+        1000
+    );
+    assert_eq!(
+        imported.encounter_damage_stats.misc.unwrap().raid_clear,
+        Some(true)
+    );
+
+    let alice = &imported.entities["Alice"];
+    assert_eq!(alice.character_id, 42);
+    assert_eq!(alice.skills[&21020].total_damage, 5000);
+    assert_eq!(alice.damage_stats.buffed_by[&999001], 2500);
+
+    // the derived tables are filled in the same way as a live save
+    let skills: i64 = other
+        .query_row("SELECT COUNT(*) FROM entity_skill", [], |row| row.get(0))
+        .unwrap();
+    let characters: i64 = other
+        .query_row("SELECT COUNT(*) FROM character WHERE id = 42", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    let searchable: i64 = other
+        .query_row(
+            "SELECT COUNT(*) FROM encounter_search WHERE encounter_search MATCH 'Alice'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!((skills, characters, searchable), (1, 1, 1));
+}
+
+#[test]
+fn json_round_trip() {
+    round_trip("plain.json");
+}
+
+#[test]
+fn gzip_round_trip() {
+    round_trip("compressed.json.gz");
+    let conn = setup();
+    let path = temp_path("magic.json.gz");
+    export_encounter(&conn, 1, &path, "1.2.3").unwrap();
+    assert!(fs::read(&path).unwrap().starts_with(&[0x1f, 0x8b]));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn importing_twice_is_rejected() {
+    let mut conn = setup();
+    let path = temp_path("twice.json");
+    export_encounter(&conn, 1, &path, "1.2.3").unwrap();
+
+    // exported from this db, so it is already saved
+    assert!(import_encounter(&mut conn, &path).is_err());
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM encounter", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 1);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn invalid_exports_are_rejected() {
+    assert!(parse_export(b"not json").is_err());
+    assert!(parse_export(b"{}").is_err());
+    assert!(parse_export(br#"{"formatVersion": 99, "encounter": {}}"#).is_err());
+    assert!(parse_export(br#"{"formatVersion": 1, "encounter": {}}"#).is_err());
+
+    let conn = setup();
+    assert!(export_encounter(&conn, 2, &temp_path("missing.json"), "1.2.3").is_err());
+}
+
+#[test]
+fn imported_markup_is_stripped() {
+    let conn = setup();
+    conn.execute_batch(
+        r#"
+        UPDATE entity SET name = '<FONT COLOR=''#8DF901''>Valtan</FONT>' WHERE name = 'Valtan';
+        UPDATE encounter SET current_boss = '<FONT COLOR=''#8DF901''>Valtan</FONT>';
+        UPDATE entity SET skills = '{"21020": {"id": 21020, "name": "<img src=x onerror=alert(1)>Sound Shock",
+            "icon": "../../evil.png\" onerror=\"alert(1)", "totalDamage": 5000}}'
+        WHERE name = 'Alice';
+        "#,
+    )
+    .unwrap();
+    let path = temp_path("markup.json");
+    export_encounter(&conn, 1, &path, "1.2.3").unwrap();
+    let export = parse_export(&fs::read(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let encounter = export.encounter;
+    assert_eq!(encounter.current_boss_name, "Valtan");
+    assert_eq!(encounter.entities["Valtan"].name, "Valtan");
+    let skill = &encounter.entities["Alice"].skills[&21020];
+    assert_eq!(skill.name, "Sound Shock");
+    assert_eq!(skill.icon, "");
+}
@@ -0,0 +1,129 @@
+use rusqlite::{params, Connection};
+
+use crate::db::history::get_character_history;
+use crate::db::migrate;
+use crate::parser::models::HistoryFilter;
+
+// id, boss, difficulty, cleared, total damage, alice's dps, alice's damage, alice's support buffed damage
+type Row = (i32, &'static str, &'static str, bool, i64, i64, i64, i64);
+
+const ENCOUNTERS: &[Row] = &[
+    (1, "Valtan", "Hard", true, 1000, 100, 250, 100),
+    (2, "Valtan", "Hard", false, 1000, 200, 500, 250),
+    (3, "Vykas", "Hard", true, 1000, 300, 100, 0),
+    (4, "Valtan", "Normal", true, 1000, 400, 1000, 500),
+];
+
+fn setup() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+    for (id, boss, difficulty, cleared, total, dps, damage, buffed) in ENCOUNTERS {
+        conn.execute(
+            "INSERT INTO encounter (id, current_boss, difficulty, cleared, fight_start, total_damage_dealt)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            params![id, boss, difficulty, cleared, id * 1000, total],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO entity (name, encounter_id, entity_type, class, gear_score, dps, damage_stats)
+            VALUES ('Alice', ?, 'PLAYER', 'Bard', ?, ?, json_object('damageDealt', ?, 'deaths', 1, 'buffedBySupport', ?))",
+            params![id, 1600.0 + *id as f64, dps, damage, buffed],
+        )
+        .unwrap();
+    }
+    conn
+}
+
+#[test]
+fn history_is_oldest_first_with_rolling_averages() {
+    let conn = setup();
+    let filter = HistoryFilter {
+        rolling_window: 2,
+        ..Default::default()
+    };
+    let history = get_character_history(&conn, "Alice", &filter).unwrap();
+
+    let ids: Vec<i32> = history.iter().map(|p| p.encounter_id).collect();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+    assert_eq!(history[0].damage_percent, 25.0);
+    assert_eq!(history[0].support_buffed_percent, 40.0);
+    assert_eq!(history[0].deaths, 1);
+    assert_eq!(history[0].gear_score, 1601.0);
+    assert_eq!(history[0].rolling_dps, 100.0);
+    assert_eq!(history[1].rolling_dps, 150.0);
+    assert_eq!(history[3].rolling_dps, 350.0);
+    assert_eq!(history[3].rolling_damage_percent, 55.0);
+}
+
+#[test]
+fn history_filters() {
+    let conn = setup();
+    let filter = HistoryFilter {
+        bosses: vec!["Valtan".to_string()],
+        difficulty: "Hard".to_string(),
+        ..Default::default()
+    };
+    let ids: Vec<i32> = get_character_history(&conn, "Alice", &filter)
+        .unwrap()
+        .iter()
+        .map(|p| p.encounter_id)
+        .collect();
+    assert_eq!(ids, vec![1, 2]);
+
+    let filter = HistoryFilter {
+        cleared: true,
+        ..Default::default()
+    };
+    let ids: Vec<i32> = get_character_history(&conn, "Alice", &filter)
+        .unwrap()
+        .iter()
+        .map(|p| p.encounter_id)
+        .collect();
+    assert_eq!(ids, vec![1, 3, 4]);
+
+    assert!(
+        get_character_history(&conn, "Bob", &HistoryFilter::default())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn history_follows_renames_by_character_id() {
+    let conn = setup();
+    conn.execute_batch(
+        "
+        UPDATE entity SET character_id = 42 WHERE encounter_id IN (2, 3);
+        UPDATE entity SET name = 'Alicia' WHERE encounter_id = 3;
+        INSERT INTO character (id, name, first_seen, last_seen) VALUES (42, 'Alicia', 2000, 3000);
+        INSERT INTO character_name (character_id, name, first_seen, last_seen)
+        VALUES (42, 'Alice', 2000, 2000), (42, 'Alicia', 3000, 3000);
+        INSERT INTO character (id, name, first_seen, last_seen) VALUES (77, 'Alice', 5000, 5000);
+        INSERT INTO character_name (character_id, name, first_seen, last_seen)
+        VALUES (77, 'Alice', 5000, 5000);
+        INSERT INTO encounter (id, current_boss, fight_start, total_damage_dealt)
+        VALUES (5, 'Valtan', 5000, 1000);
+        INSERT INTO entity (name, encounter_id, entity_type, character_id, dps)
+        VALUES ('Alice', 5, 'PLAYER', 77, 500);
+        ",
+    )
+    .unwrap();
+
+    let ids = |character: &str, filter: &HistoryFilter| -> Vec<i32> {
+        get_character_history(&conn, character, filter)
+            .unwrap()
+            .iter()
+            .map(|p| p.encounter_id)
+            .collect()
+    };
+    assert_eq!(ids("Alicia", &HistoryFilter::default()), vec![2, 3]);
+    // a bare name is whoever goes by it now, the renamed Alicia's old logs aren't merged in
+    assert_eq!(ids("Alice", &HistoryFilter::default()), vec![1, 4, 5]);
+
+    // pinning the character id picks the earlier Alice and keeps her logs from before ids were stored
+    let filter = HistoryFilter {
+        character_id: Some(42),
+        ..Default::default()
+    };
+    assert_eq!(ids("Alice", &filter), vec![1, 2, 3, 4]);
+}
@@ -0,0 +1,442 @@
+use std::{env, fs, process};
+
+use rusqlite::Connection;
+
+use crate::db::migrations::{MIGRATIONS, SCHEMA_VERSION};
+use crate::db::*;
+
+// schema as it was before misc, difficulty, favorite, cleared, boss_only_damage and entity dps
+const LEGACY_SCHEMA: &str = "
+    CREATE TABLE encounter (
+        id INTEGER PRIMARY KEY,
+        last_combat_packet INTEGER,
+        fight_start INTEGER,
+        local_player TEXT,
+        current_boss TEXT,
+        duration INTEGER,
+        total_damage_dealt INTEGER,
+        top_damage_dealt INTEGER,
+        total_damage_taken INTEGER,
+        top_damage_taken INTEGER,
+        dps INTEGER,
+        buffs TEXT,
+        debuffs TEXT
+    );
+    CREATE TABLE entity (
+        name TEXT,
+        encounter_id INTEGER NOT NULL,
+        npc_id INTEGER,
+        entity_type TEXT,
+        class_id INTEGER,
+        class TEXT,
+        gear_score REAL,
+        current_hp INTEGER,
+        max_hp INTEGER,
+        is_dead INTEGER,
+        skills TEXT,
+        damage_stats TEXT,
+        skill_stats TEXT,
+        last_update INTEGER,
+        PRIMARY KEY (name, encounter_id),
+        FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+    );
+    ";
+
+fn columns(conn: &Connection, table: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))
+        .unwrap();
+    let names = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<Vec<String>, _>>()
+        .unwrap();
+    names
+}
+
+// applies migrations after the current version up to and including version
+fn migrate_to(conn: &mut Connection, version: i32) {
+    let current = get_schema_version(conn).unwrap();
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current && m.version <= version)
+    {
+        let tx = conn.transaction().unwrap();
+        (migration.up)(&tx).unwrap();
+        tx.pragma_update(None, "user_version", migration.version)
+            .unwrap();
+        tx.commit().unwrap();
+    }
+}
+
+#[test]
+fn migration_versions_are_ordered() {
+    for (i, migration) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(migration.version, i as i32 + 1, "{}", migration.description);
+    }
+    assert_eq!(SCHEMA_VERSION, MIGRATIONS.len() as i32);
+}
+
+#[test]
+fn fresh_db_is_migrated_to_latest() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+
+    assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    assert!(columns(&conn, "encounter").contains(&"boss_only_damage".to_string()));
+    assert!(columns(&conn, "entity").contains(&"dps".to_string()));
+
+    // running again is a no-op
+    migrate(&mut conn).unwrap();
+    assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+}
+
+#[test]
+fn create_tables_upgrades_legacy_db() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(LEGACY_SCHEMA).unwrap();
+    conn.execute(
+        "INSERT INTO encounter (id, current_boss) VALUES (1, 'Valtan')",
+        [],
+    )
+    .unwrap();
+
+    migrate_to(&mut conn, 1);
+
+    let encounter_columns = columns(&conn, "encounter");
+    for column in [
+        "misc",
+        "difficulty",
+        "favorite",
+        "version",
+        "cleared",
+        "boss_only_damage",
+    ] {
+        assert!(
+            encounter_columns.contains(&column.to_string()),
+            "{}",
+            column
+        );
+    }
+    assert!(columns(&conn, "entity").contains(&"dps".to_string()));
+
+    let (favorite, boss_only_damage): (bool, bool) = conn
+        .query_row(
+            "SELECT favorite, boss_only_damage FROM encounter WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert!(!favorite);
+    assert!(!boss_only_damage);
+}
+
+#[test]
+fn backfill_cleared_uses_raid_clear() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_to(&mut conn, 1);
+    conn.execute_batch(
+        "
+        INSERT INTO encounter (id, misc) VALUES (1, '{\"raidClear\": true}');
+        INSERT INTO encounter (id, misc) VALUES (2, '{}');
+        INSERT INTO encounter (id, misc, cleared) VALUES (3, '{}', 1);
+        ",
+    )
+    .unwrap();
+
+    migrate_to(&mut conn, 2);
+
+    let mut stmt = conn
+        .prepare("SELECT cleared FROM encounter ORDER BY id")
+        .unwrap();
+    let cleared = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<Vec<bool>, _>>()
+        .unwrap();
+    assert_eq!(cleared, vec![true, false, true]);
+}
+
+#[test]
+fn backfill_entity_dps_uses_damage_stats() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_to(&mut conn, 2);
+    conn.execute_batch(
+        "
+        INSERT INTO encounter (id) VALUES (1);
+        INSERT INTO entity (name, encounter_id, damage_stats) VALUES ('a', 1, '{\"dps\": 1234}');
+        INSERT INTO entity (name, encounter_id, damage_stats, dps) VALUES ('b', 1, '{\"dps\": 1}', 99);
+        ",
+    )
+    .unwrap();
+
+    migrate_to(&mut conn, 3);
+
+    let mut stmt = conn
+        .prepare("SELECT dps FROM entity ORDER BY name")
+        .unwrap();
+    let dps = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<Vec<i64>, _>>()
+        .unwrap();
+    assert_eq!(dps, vec![1234, 99]);
+}
+
+#[test]
+fn create_encounter_search_indexes_existing_encounters() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_to(&mut conn, 3);
+    conn.execute_batch(
+        "
+        INSERT INTO encounter (id, current_boss, difficulty) VALUES (1, 'Valtan', 'Hard');
+        INSERT INTO entity (name, encounter_id, entity_type, class) VALUES ('Alice', 1, 'PLAYER', 'Bard');
+        INSERT INTO entity (name, encounter_id, entity_type, class) VALUES ('Valtan', 1, 'BOSS', '');
+        ",
+    )
+    .unwrap();
+
+    migrate_to(&mut conn, 4);
+
+    let row: (i64, String, String, String) = conn
+        .query_row(
+            "SELECT rowid, boss, players, classes FROM encounter_search",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        row,
+        (
+            1,
+            "Valtan".to_string(),
+            "Alice".to_string(),
+            "Bard".to_string()
+        )
+    );
+}
+
+#[test]
+fn create_entity_stats_backfills_players() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_to(&mut conn, 4);
+    conn.execute_batch(
+        r#"
+        INSERT INTO encounter (id, current_boss) VALUES (1, 'Valtan');
+        INSERT INTO entity (name, encounter_id, entity_type, skills, damage_stats) VALUES (
+            'Alice', 1, 'PLAYER',
+            '{"16030": {"id": 16030, "name": "Whirlwind", "totalDamage": 5000, "hits": 4, "crits": 2, "casts": 1}}',
+            '{"buffedBy": {"999001": 3000}, "debuffedBy": {"999002": 1000}}'
+        );
+        INSERT INTO entity (name, encounter_id, entity_type, skills, damage_stats) VALUES (
+            'Valtan', 1, 'BOSS', '{"1": {"id": 1, "totalDamage": 10}}', '{}'
+        );
+        INSERT INTO entity (name, encounter_id, entity_type, skills, damage_stats) VALUES (
+            'Broken', 1, 'PLAYER', 'not json', 'not json'
+        );
+        "#,
+    )
+    .unwrap();
+
+    migrate_to(&mut conn, 5);
+
+    let skill: (String, i64, String, i64, i64, i64) = conn
+        .query_row(
+            "SELECT player, skill_id, skill_name, total_damage, hits, crits FROM entity_skill",
+            [],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        skill,
+        (
+            "Alice".to_string(),
+            16030,
+            "Whirlwind".to_string(),
+            5000,
+            4,
+            2
+        )
+    );
+
+    let mut stmt = conn
+        .prepare("SELECT buff_id, debuff, damage FROM entity_buff ORDER BY buff_id")
+        .unwrap();
+    let buffs = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<Vec<(i64, bool, i64)>, _>>()
+        .unwrap();
+    assert_eq!(buffs, vec![(999001, false, 3000), (999002, true, 1000)]);
+}
+
+#[test]
+fn create_records_backfills_cleared_encounters() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_to(&mut conn, 5);
+    conn.execute_batch(
+        "
+        INSERT INTO encounter (id, current_boss, difficulty, cleared, duration, fight_start)
+        VALUES (1, 'Valtan', 'Hard', 1, 300000, 1000), (2, 'Valtan', 'Hard', 1, 250000, 2000),
+            (3, 'Valtan', 'Hard', 0, 100000, 3000);
+        INSERT INTO entity (name, encounter_id, entity_type, dps)
+        VALUES ('Alice', 1, 'PLAYER', 500), ('Alice', 2, 'PLAYER', 400), ('Alice', 3, 'PLAYER', 900);
+        INSERT INTO entity_skill (encounter_id, player, skill_id, skill_name, total_damage, max_damage, hits, crits)
+        VALUES (1, 'Alice', 16030, 'Whirlwind', 5000, 900, 10, 5), (2, 'Alice', 16030, 'Whirlwind', 4000, 1200, 10, 2);
+        ",
+    )
+    .unwrap();
+
+    migrate_to(&mut conn, 6);
+
+    let mut stmt = conn
+        .prepare("SELECT kind, value, encounter_id FROM record ORDER BY kind")
+        .unwrap();
+    let records = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<Vec<(String, f64, i64)>, _>>()
+        .unwrap();
+    assert_eq!(
+        records,
+        vec![
+            ("clear_time".to_string(), 250000.0, 2),
+            ("crit_rate".to_string(), 0.5, 1),
+            ("dps".to_string(), 500.0, 1),
+            ("max_hit".to_string(), 1200.0, 2),
+        ]
+    );
+}
+
+#[test]
+fn key_records_by_character_id_splits_same_name_characters() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_to(&mut conn, 10);
+    conn.execute_batch(
+        "
+        INSERT INTO encounter (id, current_boss, difficulty, cleared, duration, fight_start)
+        VALUES (1, 'Valtan', 'Hard', 1, 300000, 1000), (2, 'Valtan', 'Hard', 1, 250000, 2000);
+        INSERT INTO entity (name, encounter_id, entity_type, dps, character_id)
+        VALUES ('Alice', 1, 'PLAYER', 500, NULL), ('Alice', 2, 'PLAYER', 400, 42);
+        INSERT INTO record (character, boss, difficulty, kind, value, encounter_id)
+        VALUES ('Alice', 'Valtan', 'Hard', 'dps', 500, 1);
+        ",
+    )
+    .unwrap();
+
+    migrate_to(&mut conn, 11);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT character_id, character, value, encounter_id FROM record
+            WHERE kind = 'dps' ORDER BY character_id",
+        )
+        .unwrap();
+    let records = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<Vec<(i64, String, f64, i64)>, _>>()
+        .unwrap();
+    assert_eq!(
+        records,
+        vec![
+            (0, "Alice".to_string(), 500.0, 1),
+            (42, String::new(), 400.0, 2)
+        ]
+    );
+}
+
+#[test]
+fn newer_db_is_rejected() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+        .unwrap();
+
+    let err = migrate(&mut conn).unwrap_err();
+    assert!(err.contains("please update"), "{}", err);
+    assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION + 1);
+}
+
+#[test]
+fn failed_migration_is_rolled_back() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_to(&mut conn, 1);
+    // misc that isn't json makes the cleared backfill fail
+    conn.execute(
+        "INSERT INTO encounter (id, misc) VALUES (1, 'not json')",
+        [],
+    )
+    .unwrap();
+
+    assert!(migrate(&mut conn).is_err());
+    assert_eq!(get_schema_version(&conn).unwrap(), 1);
+}
+
+#[test]
+fn check_db_version_on_disk() {
+    let dir = env::temp_dir().join(format!("loa-logs-db-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    setup_db(dir.clone()).unwrap();
+    check_db_version(&dir).unwrap();
+
+    let conn = get_db_connection(&dir).unwrap();
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+        .unwrap();
+    drop(conn);
+    assert!(check_db_version(&dir).is_err());
+    assert!(setup_db(dir.clone()).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn db_connection_cascades_encounter_deletes() {
+    let dir = env::temp_dir().join(format!("loa-logs-db-cascade-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let conn = get_db_connection(&dir).unwrap();
+    conn.execute_batch(
+        "
+        INSERT INTO encounter (id, cleared, duration) VALUES (1, 0, 1000);
+        INSERT INTO entity (name, encounter_id) VALUES ('Alice', 1);
+        INSERT INTO entity_skill (encounter_id, player, skill_id) VALUES (1, 'Alice', 16030);
+        INSERT INTO entity_buff (encounter_id, player, buff_id, debuff, damage) VALUES (1, 'Alice', 999001, 0, 10);
+        INSERT INTO character (id, name, first_seen, last_seen) VALUES (42, 'Alice', 0, 0);
+        INSERT INTO character_gear_score (character_id, encounter_id, fight_start, gear_score)
+        VALUES (42, 1, 0, 1620.0);
+        DELETE FROM encounter WHERE cleared = 0;
+        ",
+    )
+    .unwrap();
+
+    for table in [
+        "entity",
+        "entity_skill",
+        "entity_buff",
+        "character_gear_score",
+    ] {
+        let count: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, 0, "{}", table);
+    }
+
+    drop(conn);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,10 @@
+mod characters;
+mod compare;
+mod csv;
+mod export;
+mod history;
+mod migrations;
+mod records;
+mod report;
+mod search;
+mod summary;
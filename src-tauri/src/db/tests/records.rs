@@ -0,0 +1,161 @@
+use rusqlite::{params, Connection};
+
+use crate::db::migrate;
+use crate::db::records::{get_character_records, rebuild_records, update_records};
+use crate::parser::models::RecordKind;
+
+fn setup() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+    conn
+}
+
+// one player with a single skill, crit rate is crits / hits
+fn insert_clear(
+    conn: &Connection,
+    id: i64,
+    cleared: bool,
+    duration: i64,
+    dps: i64,
+    max_damage: i64,
+    crits: i64,
+) {
+    conn.execute(
+        "INSERT INTO encounter (id, current_boss, difficulty, fight_start, duration, cleared)
+        VALUES (?, 'Valtan', 'Hard', ?, ?, ?)",
+        params![id, id * 1000, duration, cleared],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO entity (name, encounter_id, entity_type, class, dps)
+        VALUES ('Alice', ?, 'PLAYER', 'Bard', ?)",
+        params![id, dps],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO entity_skill (encounter_id, player, skill_id, max_damage, hits, crits)
+        VALUES (?, 'Alice', 21020, ?, 10, ?)",
+        params![id, max_damage, crits],
+    )
+    .unwrap();
+}
+
+fn record(conn: &Connection, kind: RecordKind) -> (f64, i64) {
+    let record = get_character_records(conn, "Alice", None)
+        .unwrap()
+        .into_iter()
+        .find(|r| r.kind == kind)
+        .unwrap();
+    (record.value, record.encounter_id)
+}
+
+#[test]
+fn first_clear_sets_every_record() {
+    let conn = setup();
+    insert_clear(&conn, 1, true, 300_000, 1000, 50_000, 5);
+
+    let records = update_records(&conn, 1).unwrap();
+    assert_eq!(records.len(), 4);
+    assert!(records.iter().all(|r| r.previous.is_none()));
+    assert_eq!(record(&conn, RecordKind::CritRate), (0.5, 1));
+    assert_eq!(record(&conn, RecordKind::MaxHit), (50_000.0, 1));
+}
+
+#[test]
+fn only_better_values_replace_records() {
+    let conn = setup();
+    insert_clear(&conn, 1, true, 300_000, 1000, 50_000, 5);
+    update_records(&conn, 1).unwrap();
+
+    // faster and more dps, but a lower max hit and crit rate
+    insert_clear(&conn, 2, true, 250_000, 1200, 40_000, 2);
+    let records = update_records(&conn, 2).unwrap();
+    let mut kinds: Vec<RecordKind> = records.iter().map(|r| r.kind).collect();
+    kinds.sort_by_key(|k| k.as_str());
+    assert_eq!(kinds, vec![RecordKind::ClearTime, RecordKind::Dps]);
+    assert!(records
+        .iter()
+        .any(|r| r.kind == RecordKind::Dps && r.previous == Some(1000.0)));
+
+    assert_eq!(record(&conn, RecordKind::Dps), (1200.0, 2));
+    assert_eq!(record(&conn, RecordKind::ClearTime), (250_000.0, 2));
+    assert_eq!(record(&conn, RecordKind::MaxHit), (50_000.0, 1));
+}
+
+#[test]
+fn uncleared_encounters_are_ignored() {
+    let conn = setup();
+    insert_clear(&conn, 1, false, 300_000, 1000, 50_000, 5);
+
+    assert!(update_records(&conn, 1).unwrap().is_empty());
+    assert!(get_character_records(&conn, "Alice", None)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn rebuild_matches_incremental_updates() {
+    let conn = setup();
+    insert_clear(&conn, 1, true, 300_000, 1000, 50_000, 5);
+    insert_clear(&conn, 2, true, 250_000, 1200, 40_000, 2);
+    update_records(&conn, 1).unwrap();
+    update_records(&conn, 2).unwrap();
+    let incremental = get_character_records(&conn, "Alice", None).unwrap();
+
+    rebuild_records(&conn).unwrap();
+    let rebuilt = get_character_records(&conn, "Alice", None).unwrap();
+    assert_eq!(format!("{:?}", incremental), format!("{:?}", rebuilt));
+
+    // deleting the record holder falls back to the next best
+    conn.execute("DELETE FROM encounter WHERE id = 2", [])
+        .unwrap();
+    conn.execute("DELETE FROM entity WHERE encounter_id = 2", [])
+        .unwrap();
+    conn.execute("DELETE FROM entity_skill WHERE encounter_id = 2", [])
+        .unwrap();
+    rebuild_records(&conn).unwrap();
+    assert_eq!(record(&conn, RecordKind::Dps), (1000.0, 1));
+}
+
+#[test]
+fn records_are_kept_per_character_id() {
+    let conn = setup();
+    // an old log without a character id, then two characters that have both gone by Alice
+    insert_clear(&conn, 1, true, 250_000, 1000, 50_000, 5);
+    insert_clear(&conn, 2, true, 300_000, 1500, 50_000, 5);
+    insert_clear(&conn, 3, true, 300_000, 2000, 50_000, 5);
+    conn.execute_batch(
+        "
+        UPDATE entity SET character_id = 42 WHERE encounter_id = 2;
+        UPDATE entity SET character_id = 77 WHERE encounter_id = 3;
+        INSERT INTO character (id, name, first_seen, last_seen) VALUES (42, 'Alicia', 2000, 4000);
+        INSERT INTO character (id, name, first_seen, last_seen) VALUES (77, 'Alice', 3000, 3000);
+        INSERT INTO character_name (character_id, name, first_seen, last_seen)
+        VALUES (42, 'Alice', 2000, 2000), (42, 'Alicia', 4000, 4000), (77, 'Alice', 3000, 3000);
+        ",
+    )
+    .unwrap();
+    for id in 1..=3 {
+        update_records(&conn, id).unwrap();
+    }
+
+    // the other Alice's 2000 dps doesn't count, the renamed one keeps her record
+    let records = get_character_records(&conn, "Alicia", None).unwrap();
+    let dps = records.iter().find(|r| r.kind == RecordKind::Dps).unwrap();
+    assert_eq!((dps.value, dps.encounter_id), (1500.0, 2));
+    assert_eq!(record(&conn, RecordKind::Dps), (2000.0, 3));
+
+    // the better of the id and name keyed records wins
+    let records = get_character_records(&conn, "Alice", Some(42)).unwrap();
+    assert_eq!(records.len(), 4);
+    let clear_time = records
+        .iter()
+        .find(|r| r.kind == RecordKind::ClearTime)
+        .unwrap();
+    assert_eq!(clear_time.encounter_id, 1);
+
+    let incremental = get_character_records(&conn, "Alicia", None).unwrap();
+    rebuild_records(&conn).unwrap();
+    let rebuilt = get_character_records(&conn, "Alicia", None).unwrap();
+    assert_eq!(format!("{:?}", incremental), format!("{:?}", rebuilt));
+}
@@ -0,0 +1,155 @@
+use std::{env, fs, process};
+
+use hashbrown::HashMap;
+
+use crate::db::report::{base64, escape, render_report};
+use crate::parser::models::*;
+
+fn player(name: &str, class_id: u32, damage: i64) -> EncounterEntity {
+    EncounterEntity {
+        name: name.to_string(),
+        entity_type: EntityType::PLAYER,
+        class_id,
+        class: "Bard".to_string(),
+        damage_stats: DamageStats {
+            damage_dealt: damage,
+            dps_average: vec![100, 200, 300],
+            dps_rolling_10s_avg: vec![50, 150],
+            ..Default::default()
+        },
+        skills: HashMap::from([(
+            21020,
+            Skill {
+                id: 21020,
+                name: "Sound Shock".to_string(),
+                icon: "bard_skill_01.png".to_string(),
+                total_damage: damage,
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    }
+}
+
+fn encounter() -> Encounter {
+    let mut entities = HashMap::new();
+    entities.insert("<Alice>".to_string(), player("<Alice>", 204, 1000));
+    entities.insert("Bob".to_string(), player("Bob", 204, 3000));
+    Encounter {
+        current_boss_name: "Valtan".to_string(),
+        duration: 125_000,
+        entities,
+        encounter_damage_stats: EncounterDamageStats {
+            total_damage_dealt: 4000,
+            misc: Some(EncounterMisc {
+                boss_hp_log: HashMap::from([(
+                    "Valtan".to_string(),
+                    vec![
+                        BossHpLog {
+                            time: 0,
+                            hp: 100,
+                            p: 1.0,
+                        },
+                        BossHpLog {
+                            time: 60,
+                            hp: 0,
+                            p: 0.0,
+                        },
+                    ],
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn base64_matches_known_vectors() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+}
+
+#[test]
+fn names_are_escaped() {
+    assert_eq!(
+        escape("<a href=\"x\">'&'</a>"),
+        "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+    );
+}
+
+#[test]
+fn report_inlines_each_icon_once() {
+    let images = env::temp_dir().join(format!("loa-logs-report-{}", process::id()));
+    fs::create_dir_all(images.join("classes")).unwrap();
+    fs::create_dir_all(images.join("skills")).unwrap();
+    fs::write(images.join("classes/204.png"), b"class").unwrap();
+    fs::write(images.join("skills/bard_skill_01.png"), b"skill").unwrap();
+
+    let html = render_report(3, &encounter(), &images);
+    let _ = fs::remove_dir_all(&images);
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("#3 Valtan"));
+    assert!(html.contains("2:05"));
+    assert!(html.contains("&lt;Alice&gt;"));
+    assert!(!html.contains("<Alice>"));
+    // both players share the class and skill icon
+    assert_eq!(html.matches(&base64(b"class")).count(), 1);
+    assert_eq!(html.matches(&base64(b"skill")).count(), 1);
+    assert!(!html.contains("src=\"images/"));
+    // dps average, rolling dps and boss hp
+    assert!(html.matches("<svg").count() >= 3);
+    assert!(html.find("Bob").unwrap() < html.find("&lt;Alice&gt;").unwrap());
+}
+
+#[test]
+fn report_shows_buff_and_debuff_uptime() {
+    let effect = |name: &str, target: StatusEffectTarget| StatusEffect {
+        target,
+        source: StatusEffectSource {
+            name: name.to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut encounter = encounter();
+    encounter.encounter_damage_stats.buffs = HashMap::from([(
+        211400,
+        effect("Serenade of Courage", StatusEffectTarget::PARTY),
+    )]);
+    encounter.encounter_damage_stats.debuffs =
+        HashMap::from([(210230, effect("Note Brand", StatusEffectTarget::OTHER))]);
+    encounter
+        .entities
+        .get_mut("Bob")
+        .unwrap()
+        .damage_stats
+        .uptime = HashMap::from([(211400, 0.625)]);
+    encounter.entities.insert(
+        "Valtan".to_string(),
+        EncounterEntity {
+            name: "Valtan".to_string(),
+            entity_type: EntityType::BOSS,
+            damage_stats: DamageStats {
+                uptime: HashMap::from([(210230, 0.25)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+
+    let html = render_report(3, &encounter, &env::temp_dir());
+
+    let buffs = &html[html.find("Party buff uptime").unwrap()..html.find("Debuff uptime").unwrap()];
+    assert!(buffs.contains("title=\"Serenade of Courage\""));
+    assert!(buffs.contains("<td class=\"name\">Bob</td><td>62.5</td>"));
+    assert!(buffs.contains("<td class=\"name\">&lt;Alice&gt;</td><td>0.0</td>"));
+    let debuffs = &html[html.find("Debuff uptime").unwrap()..];
+    assert!(debuffs.contains("title=\"Note Brand\""));
+    assert!(debuffs.contains("<td class=\"name\">Valtan</td><td>25.0</td>"));
+}
@@ -0,0 +1,243 @@
+use rusqlite::{params, Connection};
+
+use crate::db::migrate;
+use crate::db::search::{index_encounter, load_encounters_preview, match_expression};
+use crate::parser::models::{EncounterSort, SearchFilter, SortOrder};
+
+// (id, boss, fight_start, duration ms, raid dps, difficulty, local player)
+const ENCOUNTERS: &[(i32, &str, i64, i64, i64, &str, &str)] = &[
+    (1, "Valtan", 1_000, 300_000, 900, "Normal", "Alice"),
+    (2, "Valtan", 2_000, 200_000, 1200, "Hard", "Alice"),
+    (3, "Vykas", 3_000, 400_000, 600, "Hard", "Bob"),
+    (4, "Kakul-Saydon", 4_000, 30_000, 300, "Normal", "Bob"),
+];
+
+// (name, encounter id, class, dps)
+const ENTITIES: &[(&str, i32, &str, i64)] = &[
+    ("Alice", 1, "Bard", 100),
+    ("Bob", 1, "Berserker", 800),
+    ("Alice", 2, "Bard", 200),
+    ("Carol", 2, "Sorceress", 1000),
+    ("Bob", 3, "Berserker", 500),
+    ("Alice", 3, "Artillerist", 100),
+    ("Bob", 4, "Bard", 300),
+];
+
+fn setup() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+    for (id, boss, fight_start, duration, dps, difficulty, local_player) in ENCOUNTERS {
+        conn.execute(
+            "INSERT INTO encounter (id, current_boss, fight_start, duration, dps, difficulty, local_player, cleared)
+            VALUES (?, ?, ?, ?, ?, ?, ?, 1)",
+            params![id, boss, fight_start, duration, dps, difficulty, local_player],
+        )
+        .unwrap();
+    }
+    for (name, encounter_id, class, dps) in ENTITIES {
+        conn.execute(
+            "INSERT INTO entity (name, encounter_id, entity_type, class_id, class, dps)
+            VALUES (?, ?, 'PLAYER', 102, ?, ?)",
+            params![name, encounter_id, class, dps],
+        )
+        .unwrap();
+    }
+    for encounter in ENCOUNTERS {
+        index_encounter(&conn, encounter.0 as i64).unwrap();
+    }
+    conn
+}
+
+fn search(conn: &Connection, search: &str, filter: SearchFilter) -> Vec<i32> {
+    let overview = load_encounters_preview(conn, 1, 10, search, &filter).unwrap();
+    let ids: Vec<i32> = overview.encounters.iter().map(|e| e.id).collect();
+    assert_eq!(overview.total_encounters as usize, ids.len());
+    ids
+}
+
+#[test]
+fn default_filter_is_newest_first() {
+    let conn = setup();
+    assert_eq!(search(&conn, "", SearchFilter::default()), vec![4, 3, 2, 1]);
+}
+
+#[test]
+fn difficulty_is_bound() {
+    let conn = setup();
+    let filter = SearchFilter {
+        difficulty: "Hard".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(search(&conn, "", filter), vec![3, 2]);
+
+    // used to be formatted into the query
+    let filter = SearchFilter {
+        difficulty: "Hard' OR '1'='1".to_string(),
+        ..Default::default()
+    };
+    assert!(search(&conn, "", filter).is_empty());
+}
+
+#[test]
+fn duration_range() {
+    let conn = setup();
+    let filter = SearchFilter {
+        min_duration: 60,
+        max_duration: 300,
+        ..Default::default()
+    };
+    assert_eq!(search(&conn, "", filter), vec![2, 1]);
+}
+
+#[test]
+fn date_range() {
+    let conn = setup();
+    let filter = SearchFilter {
+        from_date: Some(2_000),
+        to_date: Some(3_000),
+        ..Default::default()
+    };
+    assert_eq!(search(&conn, "", filter), vec![3, 2]);
+}
+
+#[test]
+fn local_player_only() {
+    let conn = setup();
+    let filter = SearchFilter {
+        classes: vec!["Berserker".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(search(&conn, "", filter), vec![3, 1]);
+
+    // bob was only the local player in 3
+    let filter = SearchFilter {
+        classes: vec!["Berserker".to_string()],
+        local_player_only: true,
+        ..Default::default()
+    };
+    assert_eq!(search(&conn, "", filter), vec![3]);
+}
+
+#[test]
+fn party_members() {
+    let conn = setup();
+    let filter = SearchFilter {
+        party_members: vec!["Alice".to_string(), "Bob".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(search(&conn, "", filter), vec![3, 1]);
+}
+
+#[test]
+fn sort_keys() {
+    let conn = setup();
+    let sorted = |sort, order| {
+        search(
+            &conn,
+            "",
+            SearchFilter {
+                sort,
+                order,
+                ..Default::default()
+            },
+        )
+    };
+
+    assert_eq!(
+        sorted(EncounterSort::Date, SortOrder::Asc),
+        vec![1, 2, 3, 4]
+    );
+    assert_eq!(
+        sorted(EncounterSort::Duration, SortOrder::Desc),
+        vec![3, 1, 2, 4]
+    );
+    assert_eq!(
+        sorted(EncounterSort::Dps, SortOrder::Desc),
+        vec![2, 1, 3, 4]
+    );
+    assert_eq!(
+        sorted(EncounterSort::LocalPlayerDps, SortOrder::Desc),
+        vec![3, 4, 2, 1]
+    );
+}
+
+#[test]
+fn paging_counts_all_matches() {
+    let conn = setup();
+    let overview = load_encounters_preview(&conn, 2, 3, "", &SearchFilter::default()).unwrap();
+    assert_eq!(overview.total_encounters, 4);
+    assert_eq!(
+        overview.encounters.iter().map(|e| e.id).collect::<Vec<_>>(),
+        vec![1]
+    );
+}
+
+#[test]
+fn match_expression_syntax() {
+    assert_eq!(match_expression("  "), None);
+    assert_eq!(match_expression("val"), Some("\"val\"*".to_string()));
+    assert_eq!(
+        match_expression("boss:Thaemine class:Bard player:Foo"),
+        Some("boss : \"Thaemine\"* AND classes : \"Bard\"* AND players : \"Foo\"*".to_string())
+    );
+    assert_eq!(
+        match_expression("boss:\"Phantom Legion\""),
+        Some("boss : \"Phantom Legion\"*".to_string())
+    );
+    // unknown keys and fts operators are plain text
+    assert_eq!(
+        match_expression("foo:bar NOT"),
+        Some("\"foo:bar\"* AND \"NOT\"*".to_string())
+    );
+    assert_eq!(match_expression("class: -"), None);
+}
+
+#[test]
+fn full_text_search() {
+    let conn = setup();
+    assert_eq!(search(&conn, "val", SearchFilter::default()), vec![2, 1]);
+    assert_eq!(
+        search(&conn, "boss:kakul", SearchFilter::default()),
+        vec![4]
+    );
+    assert_eq!(
+        search(&conn, "class:bard", SearchFilter::default()),
+        vec![4, 2, 1]
+    );
+    assert_eq!(
+        search(&conn, "class:bard player:carol", SearchFilter::default()),
+        vec![2]
+    );
+    assert_eq!(
+        search(&conn, "difficulty:hard bob", SearchFilter::default()),
+        vec![3]
+    );
+    // boss names aren't player names
+    assert!(search(&conn, "player:valtan", SearchFilter::default()).is_empty());
+    // a lone quote used to be an fts syntax error
+    assert!(search(&conn, "\"", SearchFilter::default()).len() == 4);
+}
+
+#[test]
+fn search_index_follows_updates_and_deletes() {
+    let conn = setup();
+    conn.execute(
+        "UPDATE encounter SET notes = 'first prog clear' WHERE id = 3",
+        [],
+    )
+    .unwrap();
+    assert_eq!(
+        search(&conn, "notes:prog", SearchFilter::default()),
+        vec![3]
+    );
+    assert_eq!(search(&conn, "prog", SearchFilter::default()), vec![3]);
+
+    conn.execute("DELETE FROM encounter WHERE id = 3", [])
+        .unwrap();
+    let rows: i32 = conn
+        .query_row("SELECT COUNT(*) FROM encounter_search", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(rows, 3);
+}
@@ -0,0 +1,145 @@
+use hashbrown::HashMap;
+use rusqlite::Connection;
+
+use crate::db::migrate;
+use crate::db::summary::{abbreviate, encounter_summary, format_summary, markdown};
+use crate::parser::models::*;
+
+fn player(name: &str, class: &str, damage: i64, deaths: i64) -> EncounterEntity {
+    EncounterEntity {
+        name: name.to_string(),
+        entity_type: EntityType::PLAYER,
+        class: class.to_string(),
+        gear_score: 1620.0,
+        damage_stats: DamageStats {
+            damage_dealt: damage,
+            dps: damage / 100,
+            buffed_by_support: damage / 2,
+            deaths,
+            ..Default::default()
+        },
+        skill_stats: SkillStats {
+            hits: 10,
+            crits: 5,
+            counters: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn encounter() -> Encounter {
+    let mut entities = HashMap::new();
+    for entity in [
+        player("Alice", "Bard", 100_000, 0),
+        player("Bob_Smith", "Berserker", 300_000, 1),
+        player("Carol", "Sorceress", 600_000, 2),
+    ] {
+        entities.insert(entity.name.clone(), entity);
+    }
+    entities.insert(
+        "Valtan".to_string(),
+        EncounterEntity {
+            name: "Valtan".to_string(),
+            entity_type: EntityType::BOSS,
+            damage_stats: DamageStats {
+                damage_dealt: 999_999_999,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+    Encounter {
+        current_boss_name: "Valtan".to_string(),
+        difficulty: Some("Hard".to_string()),
+        cleared: true,
+        duration: 303_000,
+        entities,
+        encounter_damage_stats: EncounterDamageStats {
+            total_damage_dealt: 1_000_000,
+            dps: 3300,
+            misc: Some(EncounterMisc {
+                party_info: Some(HashMap::from([
+                    (0, vec!["Bob_Smith".to_string(), "Alice".to_string()]),
+                    (1, vec!["Carol".to_string()]),
+                ])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn compact_summary() {
+    assert_eq!(
+        format_summary(&encounter(), SummaryTemplate::Compact, false),
+        "**[Hard] Valtan** · 5:03 · Cleared
+Total 1.0m damage · 3.3k dps
+
+1. **Carol** (Sorceress) 6.0k dps · 60.0% · sup 50.0% · 2 deaths
+2. **Bob\\_Smith** (Berserker) 3.0k dps · 30.0% · sup 50.0% · 1 death
+3. **Alice** (Bard) 1.0k dps · 10.0% · sup 50.0%
+"
+    );
+}
+
+#[test]
+fn parties_keep_encounter_wide_ranks() {
+    let summary = format_summary(&encounter(), SummaryTemplate::Compact, true);
+    let lines: Vec<&str> = summary.lines().skip(3).collect();
+    assert_eq!(lines[0], "__Party 1__");
+    assert!(lines[1].starts_with("1. **Bob\\_Smith**"));
+    assert!(lines[2].starts_with("2. **Alice**"));
+    assert_eq!(lines[4], "__Party 2__");
+    assert!(lines[5].starts_with("3. **Carol**"));
+
+    // without party info everyone is in one group
+    let mut encounter = encounter();
+    encounter.encounter_damage_stats.misc = None;
+    let summary = format_summary(&encounter, SummaryTemplate::Compact, true);
+    assert!(summary.contains("__Players__\n1. **Carol**"));
+}
+
+#[test]
+fn table_summary_is_aligned() {
+    let summary = format_summary(&encounter(), SummaryTemplate::Table, false);
+    let table: Vec<&str> = summary.split("```\n").nth(1).unwrap().lines().collect();
+    assert_eq!(
+        table,
+        [
+            "#  Name       Class       DPS  Dmg%  Sup%  Deaths",
+            "1  Carol      Sorceress  6.0k  60.0  50.0       2",
+            "2  Bob_Smith  Berserker  3.0k  30.0  50.0       1",
+            "3  Alice      Bard       1.0k  10.0  50.0       0",
+        ]
+    );
+}
+
+#[test]
+fn detailed_summary() {
+    let summary = format_summary(&encounter(), SummaryTemplate::Detailed, false);
+    assert!(summary.contains(
+        "1. **Carol** (Sorceress, 1620.00)
+  - 600.0k damage (60.0%) · 6.0k dps
+  - crit 50.0% · support 50.0% · 2 deaths · 2 counters
+"
+    ));
+    assert!(summary.contains("0 deaths"));
+}
+
+#[test]
+fn helpers() {
+    assert_eq!(abbreviate(999), "999");
+    assert_eq!(abbreviate(12_345), "12.3k");
+    assert_eq!(abbreviate(4_560_000_000), "4.6b");
+    assert_eq!(markdown("*a_b*"), "\\*a\\_b\\*");
+}
+
+#[test]
+fn missing_encounter_is_an_error() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn).unwrap();
+    assert!(encounter_summary(&conn, 1, SummaryTemplate::Compact, false).is_err());
+}
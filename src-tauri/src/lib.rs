@@ -0,0 +1,16 @@
+pub mod db;
+pub mod parser;
+
+use std::{fs::File, io::Read, path::Path};
+
+use parser::models::Settings;
+
+pub fn read_settings(resource_path: &Path) -> Result<Settings, Box<dyn std::error::Error>> {
+    let mut path = resource_path.to_path_buf();
+    path.push("settings.json");
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let settings = serde_json::from_str(&contents)?;
+    Ok(settings)
+}
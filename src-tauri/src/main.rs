@@ -3,27 +3,34 @@
     windows_subsystem = "windows"
 )]
 
-mod parser;
 mod resources;
 use std::{
     fs::{self, File},
-    io::{Read, Write},
-    path::{Path, PathBuf},
-    str::FromStr,
+    io::Write,
+    path::PathBuf,
+    sync::Arc,
 };
 
 use anyhow::Result;
 use flexi_logger::{
     Cleanup, Criterion, DeferredNow, Duplicate, FileSpec, Logger, Naming, WriteMode,
 };
-use hashbrown::HashMap;
 use log::{info, warn, Record};
-use parser::models::*;
+use app::db::records::{self, rebuild_records};
+use app::db::{
+    characters, check_db_version, compare, csv, encounters, export, get_db_connection, history,
+    report, search, setup_db, summary,
+};
+use app::parser::{self, get_capture_source, models::*};
+use app::parser::meter_data::load_meter_data;
+use app::parser::replay::get_replay_source;
+use app::parser::sink::{EventHandler, EventSink};
+use app::read_settings;
 
-use rusqlite::{params, params_from_iter, Connection};
+use rusqlite::{params, params_from_iter};
 use tauri::{
     api::process::Command, CustomMenuItem, LogicalPosition, LogicalSize, Manager, Position, Size,
-    SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem, WindowBuilder,
+    SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem, Window, WindowBuilder, Wry,
 };
 use tauri_plugin_window_state::{AppHandleExt, StateFlags, WindowExt};
 use window_vibrancy::{apply_blur, clear_blur};
@@ -89,6 +96,7 @@ async fn main() -> Result<()> {
             }
 
             let settings = read_settings(&resource_path).ok();
+            load_meter_data(&resource_path);
 
             let meter_window = app.get_window("main").unwrap();
             meter_window
@@ -102,54 +110,11 @@ async fn main() -> Result<()> {
             //     meter_window.open_devtools();
             // }
 
-            let mut raw_socket = false;
-            let mut ip: String;
-            let mut port = 6040;
-
-            if let Some(settings) = settings.clone() {
-                info!("settings loaded");
-                if settings.general.auto_iface {
-                    ip = meter_core::get_most_common_ip().unwrap();
-                    info!("auto_iface enabled, using ip: {}", ip);
-                } else {
-                    ip = settings.general.ip;
-                    let interface = settings.general.if_desc;
-                    info!(
-                        "manual interface set, ip: {} and interface: {}",
-                        ip, interface
-                    );
-                    let os_interfaces = get_network_interfaces();
-                    let right_name: Vec<&(String, String)> = os_interfaces
-                        .iter()
-                        .filter(|iface| iface.0 == interface)
-                        .collect();
-                    if !right_name.is_empty() {
-                        let perfect_match =
-                            right_name.clone().into_iter().find(|iface| iface.1 == ip);
-                        if perfect_match.is_none() {
-                            //in case of multiple interfaces with same name, try the first one
-                            ip = right_name[0].1.clone(); //get the up to date ip
-                            warn!("ip for manual interface was wrong, using ip: {}", ip);
-                        }
-                    } else {
-                        ip = meter_core::get_most_common_ip().unwrap();
-                        warn!("manually set interface not found, using default ip: {}", ip);
-                    }
-                    if settings.general.port > 0 {
-                        port = settings.general.port;
-                        info!("using port: {}", port);
-                    }
-                    raw_socket = settings.general.raw_socket;
-                    if raw_socket {
-                        info!("using raw socket");
-                    } else {
-                        info!("using npcap");
-                    }
-                }
-            } else {
-                ip = meter_core::get_most_common_ip().unwrap();
-                info!("settings not found, auto_iface enabled, using ip: {}", ip);
-            }
+            let args: Vec<String> = std::env::args().collect();
+            let source = match get_replay_source(&args) {
+                Some(source) => source,
+                None => get_capture_source(settings.clone()),
+            };
 
             match setup_db(resource_path) {
                 Ok(_) => (),
@@ -169,7 +134,8 @@ async fn main() -> Result<()> {
             logs_window.set_decorations(true).unwrap();
 
             tokio::task::spawn_blocking(move || {
-                parser::start(meter_window, ip, port, raw_socket, settings).map_err(|e| {
+                let sink = Arc::new(WindowSink(meter_window));
+                parser::start(sink, source, settings).map_err(|e| {
                     warn!("unexpected error occurred in parser: {}", e);
                 })
             });
@@ -279,6 +245,7 @@ async fn main() -> Result<()> {
         .invoke_handler(tauri::generate_handler![
             load_encounters_preview,
             load_encounter,
+            compare_encounters,
             get_encounter_count,
             open_most_recent_encounter,
             delete_encounter,
@@ -288,15 +255,27 @@ async fn main() -> Result<()> {
             open_url,
             save_settings,
             get_settings,
+            reload_meter_data,
+            get_raids,
             open_folder,
             open_db_path,
             delete_encounters_below_min_duration,
             get_db_info,
+            check_db,
             disable_blur,
             enable_blur,
             get_network_interfaces,
             write_log,
             toggle_encounter_favorite,
+            set_encounter_notes,
+            get_character_records,
+            get_character_history,
+            get_characters,
+            export_encounter,
+            import_encounter,
+            export_encounters_csv,
+            write_encounter_report,
+            get_encounter_summary,
             delete_all_encounters,
             delete_all_uncleared_encounters,
             enable_aot,
@@ -309,225 +288,26 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-fn get_db_connection(resource_path: &Path) -> Result<Connection, String> {
-    let mut path = resource_path.to_path_buf();
-    path.push("encounters.db");
-    if !path.exists() {
-        setup_db(path.clone())?;
-    }
-    let conn = match Connection::open(path) {
-        Ok(conn) => conn,
-        Err(e) => {
-            return Err(e.to_string());
-        }
-    };
-    Ok(conn)
-}
-
-fn setup_db(resource_path: PathBuf) -> Result<(), String> {
-    let mut path = resource_path;
-    path.push("encounters.db");
-    let conn = match Connection::open(path) {
-        Ok(conn) => conn,
-        Err(e) => {
-            return Err(e.to_string());
-        }
-    };
-
-    match conn.execute_batch(&format!(
-        "
-    CREATE TABLE IF NOT EXISTS encounter (
-        id INTEGER PRIMARY KEY,
-        last_combat_packet INTEGER,
-        fight_start INTEGER,
-        local_player TEXT,
-        current_boss TEXT,
-        duration INTEGER,
-        total_damage_dealt INTEGER,
-        top_damage_dealt INTEGER,
-        total_damage_taken INTEGER,
-        top_damage_taken INTEGER,
-        dps INTEGER,
-        buffs TEXT,
-        debuffs TEXT,
-        misc TEXT,
-        difficulty TEXT
-        favorite BOOLEAN NOT NULL DEFAULT 0,
-        cleared BOOLEAN,
-        version INTEGER NOT NULL DEFAULT {},
-        boss_only_damage BOOLEAN NOT NULL DEFAULT 0
-    );
-    CREATE INDEX IF NOT EXISTS encounter_fight_start_index
-    ON encounter (fight_start desc);
-    CREATE INDEX IF NOT EXISTS encounter_current_boss_index
-    ON encounter (current_boss);
-    ",
-        DB_VERSION
-    )) {
-        Ok(_) => (),
-        Err(e) => {
-            return Err(e.to_string());
-        }
-    }
-
-    let mut stmt = conn
-        .prepare("SELECT COUNT(*) FROM pragma_table_info('encounter') WHERE name='misc'")
-        .unwrap();
-    let column_count: u32 = stmt.query_row([], |row| row.get(0)).unwrap();
-    if column_count == 0 {
-        conn.execute("ALTER TABLE encounter ADD COLUMN misc TEXT", [])
-            .expect("failed to add column");
-    }
-
-    let mut stmt = conn
-        .prepare("SELECT COUNT(*) FROM pragma_table_info('encounter') WHERE name='difficulty'")
-        .unwrap();
-    let column_count: u32 = stmt.query_row([], |row| row.get(0)).unwrap();
-    if column_count == 0 {
-        conn.execute("ALTER TABLE encounter ADD COLUMN difficulty TEXT", [])
-            .expect("failed to add column");
-    }
-
-    let mut stmt = conn
-        .prepare("SELECT COUNT(*) FROM pragma_table_info('encounter') WHERE name='favorite'")
-        .unwrap();
-    let column_count: u32 = stmt.query_row([], |row| row.get(0)).unwrap();
-    if column_count == 0 {
-        conn.execute(
-            "ALTER TABLE encounter ADD COLUMN favorite BOOLEAN DEFAULT 0",
-            [],
-        )
-        .expect("failed to add columns");
-        conn.execute(
-            &format!(
-                "ALTER TABLE encounter ADD COLUMN version INTEGER DEFAULT {}",
-                DB_VERSION
-            ),
-            [],
-        )
-        .expect("failed to add columns");
-        conn.execute("ALTER TABLE encounter ADD COLUMN cleared BOOLEAN", [])
-            .expect("failed to add columns");
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS encounter_favorite_index ON encounter (favorite);",
-            [],
-        )
-        .expect("failed to add index");
-    }
+// forwards parser events to the meter window
+struct WindowSink(Window<Wry>);
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT COUNT(*) FROM pragma_table_info('encounter') WHERE name='boss_only_damage'",
-        )
-        .unwrap();
-    let column_count: u32 = stmt.query_row([], |row| row.get(0)).unwrap();
-    if column_count == 0 {
-        conn.execute(
-            "ALTER TABLE encounter ADD COLUMN boss_only_damage BOOLEAN NOT NULL DEFAULT 0",
-            [],
-        )
-        .expect("failed to add column");
+impl EventSink for WindowSink {
+    fn emit(&self, event: &str, payload: serde_json::Value) -> Result<()> {
+        self.0.emit(event, payload)?;
+        Ok(())
     }
 
-    match conn.execute_batch(
-        "
-        CREATE TABLE IF NOT EXISTS entity (
-            name TEXT,
-            encounter_id INTEGER NOT NULL,
-            npc_id INTEGER,
-            entity_type TEXT,
-            class_id INTEGER,
-            class TEXT,
-            gear_score REAL,
-            current_hp INTEGER,
-            max_hp INTEGER,
-            is_dead INTEGER,
-            skills TEXT,
-            damage_stats TEXT,
-            dps INTEGER,
-            skill_stats TEXT,
-            last_update INTEGER,
-            PRIMARY KEY (name, encounter_id),
-            FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
-        );
-        CREATE INDEX IF NOT EXISTS entity_encounter_id_index
-        ON entity (encounter_id desc);
-        CREATE INDEX IF NOT EXISTS entity_name_index
-        ON entity (name);
-        CREATE INDEX IF NOT EXISTS entity_class_index
-        ON entity (class);
-        ",
-    ) {
-        Ok(_) => (),
-        Err(e) => {
-            return Err(e.to_string());
-        }
+    fn listen(&self, event: &str, handler: EventHandler) {
+        self.0
+            .listen_global(event, move |event| handler(event.payload()));
     }
 
-    let mut stmt = conn
-        .prepare("SELECT COUNT(*) FROM pragma_table_info('entity') WHERE name='dps'")
-        .unwrap();
-    let column_count: u32 = stmt.query_row([], |row| row.get(0)).unwrap();
-    if column_count == 0 {
-        conn.execute("ALTER TABLE entity ADD COLUMN dps INTEGER", [])
-            .expect("failed to add integer column");
-    }
-
-    update_db(&conn);
-
-    Ok(())
-}
-
-fn update_db(conn: &Connection) {
-    let count: i32 = conn
-        .query_row_and_then(
-            "SElECT COUNT(*) FROM encounter WHERE cleared IS NULL",
-            [],
-            |row| row.get(0),
-        )
-        .expect("could not get encounter count");
-    if count > 0 {
-        match conn.execute(
-            "
-        UPDATE encounter
-        SET cleared = CASE
-                WHEN json_extract(misc, '$.raidClear') IS NULL THEN 0
-                ELSE 1
-                END
-        WHERE cleared IS NULL
-        ",
-            [],
-        ) {
-            Ok(updated) => {
-                info!("updated {} encounters", updated);
-            }
-            Err(e) => {
-                warn!("failed to update cleared status: {}", e);
-            }
-        }
-    }
-
-    let count: i32 = conn
-        .query_row_and_then("SElECT COUNT(*) FROM entity WHERE dps IS NULL", [], |row| {
-            row.get(0)
-        })
-        .expect("could not get entity count");
-    if count > 0 {
-        match conn.execute(
-            "
-        UPDATE entity
-        SET dps = json_extract(damage_stats, '$.dps')
-        WHERE dps IS NULL
-        ",
-            [],
-        ) {
-            Ok(updated) => {
-                info!("updated {} entities", updated);
-            }
-            Err(e) => {
-                warn!("failed to extract dps from entities: {}", e);
-            }
-        }
+    fn resource_dir(&self) -> PathBuf {
+        self.0
+            .app_handle()
+            .path_resolver()
+            .resource_dir()
+            .expect("could not get resource dir")
     }
 }
 
@@ -546,144 +326,8 @@ fn load_encounters_preview(
         .expect("could not get resource dir");
     let conn = get_db_connection(&path).expect("could not get db connection");
 
-    let min_duration = filter.min_duration * 1000;
-
-    let mut params = vec![
-        min_duration.to_string(),
-        search.clone(),
-        search.clone(),
-        search,
-    ];
-
-    let boss_filter = if !filter.bosses.is_empty() {
-        let placeholders: Vec<String> = filter.bosses.iter().map(|_| "?".to_string()).collect();
-        filter.bosses.into_iter().for_each(|boss| params.push(boss));
-        format!("AND (current_boss IN ({}))", placeholders.join(","))
-    } else {
-        "".to_string()
-    };
-
-    let class_filter = if !filter.classes.is_empty() {
-        let placeholders: Vec<String> = filter.classes.iter().map(|_| "?".to_string()).collect();
-        filter
-            .classes
-            .into_iter()
-            .for_each(|class| params.push(class));
-        format!("AND (class IN ({}))", placeholders.join(","))
-    } else {
-        "".to_string()
-    };
-
-    let raid_clear_filter = if filter.cleared {
-        "AND cleared = 1".to_string()
-    } else {
-        "".to_string()
-    };
-
-    let favorite_filter = if filter.favorite {
-        "AND favorite = 1".to_string()
-    } else {
-        "".to_string()
-    };
-
-    let boss_only_damage_filter = if filter.boss_only_damage {
-        "AND boss_only_damage = 1".to_string()
-    } else {
-        "".to_string()
-    };
-
-    let difficulty_filter = if !filter.difficulty.is_empty() {
-        format!("AND difficulty = '{}'", filter.difficulty)
-    } else {
-        "".to_string()
-    };
-
-    let count_params = params.clone();
-
-    let query = format!("SELECT
-    e.id,
-    e.fight_start,
-    e.current_boss,
-    e.duration,
-    e.difficulty,
-    e.favorite,
-    e.cleared,
-    e.local_player,
-    (
-        SELECT GROUP_CONCAT(ordered_classes.class_info, ',')
-        FROM (
-            SELECT en.class_id || ':' || en.name AS class_info
-            FROM entity en
-            WHERE en.encounter_id = e.id AND en.entity_type = 'PLAYER'
-            ORDER BY dps DESC
-        ) AS ordered_classes
-    ) AS classes
-    FROM encounter e
-    JOIN entity ent ON e.id = ent.encounter_id
-    WHERE e.duration > ? AND ((current_boss LIKE '%' || ? || '%') OR (ent.class LIKE '%' || ? || '%') OR (ent.name LIKE '%' || ? || '%'))
-        {} {} {} {} {} {}
-    GROUP BY encounter_id
-    ORDER BY e.fight_start DESC
-    LIMIT ?
-    OFFSET ?", boss_filter, class_filter, raid_clear_filter, favorite_filter, difficulty_filter, boss_only_damage_filter);
-
-    let mut stmt = conn.prepare_cached(&query).unwrap();
-
-    let offset = (page - 1) * page_size;
-
-    params.push(page_size.to_string());
-    params.push(offset.to_string());
-
-    let encounter_iter = stmt
-        .query_map(params_from_iter(params), |row| {
-            let classes = row.get(8).unwrap_or_else(|_| "".to_string());
-
-            let (classes, names) = classes
-                .split(',')
-                .map(|s| {
-                    let info: Vec<&str> = s.split(':').collect();
-                    (info[0].parse::<i32>().unwrap_or(101), info[1].to_string())
-                })
-                .unzip();
-
-            Ok(EncounterPreview {
-                id: row.get(0)?,
-                fight_start: row.get(1)?,
-                boss_name: row.get(2)?,
-                duration: row.get(3)?,
-                classes,
-                names,
-                difficulty: row.get(4)?,
-                favorite: row.get(5)?,
-                cleared: row.get(6)?,
-                local_player: row.get(7)?,
-            })
-        })
-        .expect("could not query encounters");
-
-    let mut encounters: Vec<EncounterPreview> = Vec::new();
-    for encounter in encounter_iter {
-        encounters.push(encounter.unwrap());
-    }
-
-    let query = format!("
-    SElECT COUNT(*)
-    FROM (SELECT encounter_id
-        FROM encounter e
-        JOIN entity ent ON e.id = ent.encounter_id
-        WHERE duration > ? AND ((current_boss LIKE '%' || ? || '%') OR (ent.class LIKE '%' || ? || '%') OR (ent.name LIKE '%' || ? || '%'))
-            {} {} {} {} {} {}
-        GROUP BY encounter_id)
-        ", boss_filter, class_filter, raid_clear_filter, favorite_filter, difficulty_filter, boss_only_damage_filter);
-
-    let count: i32 = conn
-        .query_row_and_then(&query, params_from_iter(count_params), |row| row.get(0))
-        .expect("could not get encounter count");
-
-    EncountersOverview {
-        encounters,
-        total_encounters: count,
-    }
+    search::load_encounters_preview(&conn, page, page_size, &search, &filter)
+        .expect("could not query encounters")
 }
 
 #[tauri::command]
@@ -694,139 +338,25 @@ fn load_encounter(window: tauri::Window, id: String) -> Encounter {
         .resource_dir()
         .expect("could not get resource dir");
     let conn = get_db_connection(&path).expect("could not get db connection");
-    let mut encounter_stmt = conn
-        .prepare_cached(
-            "
-    SELECT last_combat_packet,
-       fight_start,
-       local_player,
-       current_boss,
-       duration,
-       total_damage_dealt,
-       top_damage_dealt,
-       total_damage_taken,
-       top_damage_taken,
-       dps,
-       buffs,
-       debuffs,
-       misc,
-       difficulty,
-       favorite,
-       cleared,
-       boss_only_damage
-    FROM encounter
-    WHERE id = ?
-    ;",
-        )
-        .unwrap();
-
-    let mut encounter = encounter_stmt
-        .query_row(params![id], |row| {
-            let buff_str = row.get(10).unwrap_or_else(|_| "".to_string());
-            let buffs = serde_json::from_str::<HashMap<i32, StatusEffect>>(buff_str.as_str())
-                .unwrap_or_else(|_| HashMap::new());
-
-            let debuff_str = row.get(11).unwrap_or_else(|_| "".to_string());
-            let debuffs = serde_json::from_str::<HashMap<i32, StatusEffect>>(debuff_str.as_str())
-                .unwrap_or_else(|_| HashMap::new());
-
-            let misc_str = row.get(12).unwrap_or_else(|_| "".to_string());
-            let misc = serde_json::from_str::<EncounterMisc>(misc_str.as_str())
-                .map(Some)
-                .unwrap_or_else(|_| None);
-
-            Ok(Encounter {
-                last_combat_packet: row.get(0)?,
-                fight_start: row.get(1)?,
-                local_player: row.get(2)?,
-                current_boss_name: row.get(3)?,
-                duration: row.get(4)?,
-                encounter_damage_stats: EncounterDamageStats {
-                    total_damage_dealt: row.get(5)?,
-                    top_damage_dealt: row.get(6)?,
-                    total_damage_taken: row.get(7)?,
-                    top_damage_taken: row.get(8)?,
-                    dps: row.get(9)?,
-                    buffs,
-                    debuffs,
-                    misc,
-                    ..Default::default()
-                },
-                difficulty: row.get(13)?,
-                favorite: row.get(14)?,
-                cleared: row.get(15)?,
-                boss_only_damage: row.get(16)?,
-                ..Default::default()
-            })
-        })
-        .unwrap_or_else(|_| Encounter::default());
 
-    let mut entity_stmt = conn
-        .prepare_cached(
-            "
-    SELECT name,
-        class_id,
-        class,
-        gear_score,
-        current_hp,
-        max_hp,
-        is_dead,
-        skills,
-        damage_stats,
-        skill_stats,
-        last_update,
-        entity_type,
-        npc_id
-    FROM entity
-    WHERE encounter_id = ?;
-    ",
-        )
-        .unwrap();
-
-    let entity_iter = entity_stmt
-        .query_map(params![id], |row| {
-            let skill_str = row.get(7).unwrap_or_else(|_| "".to_string());
-            let skills = serde_json::from_str::<HashMap<i32, Skill>>(skill_str.as_str())
-                .unwrap_or_else(|_| HashMap::new());
-
-            let damage_stats_str = row.get(8).unwrap_or_else(|_| "".to_string());
-
-            let damage_stats = serde_json::from_str::<DamageStats>(damage_stats_str.as_str())
-                .unwrap_or_else(|_| DamageStats::default());
-
-            let skill_stats_str = row.get(9).unwrap_or_else(|_| "".to_string());
-            let skill_stats = serde_json::from_str::<SkillStats>(skill_stats_str.as_str())
-                .unwrap_or_else(|_| SkillStats::default());
-
-            let entity_type = row.get(11).unwrap_or_else(|_| "".to_string());
-
-            Ok(EncounterEntity {
-                name: row.get(0)?,
-                class_id: row.get(1)?,
-                class: row.get(2)?,
-                gear_score: row.get(3)?,
-                current_hp: row.get(4)?,
-                max_hp: row.get(5)?,
-                is_dead: row.get(6)?,
-                skills,
-                damage_stats,
-                skill_stats,
-                entity_type: EntityType::from_str(entity_type.as_str())
-                    .unwrap_or(EntityType::UNKNOWN),
-                npc_id: row.get(12)?,
-                ..Default::default()
-            })
-        })
-        .unwrap();
-
-    let mut entities: HashMap<String, EncounterEntity> = HashMap::new();
-    for entity in entity_iter.flatten() {
-        entities.insert(entity.name.to_string(), entity);
-    }
+    encounters::load_encounter(&conn, &id)
+}
 
-    encounter.entities = entities;
+#[tauri::command]
+fn compare_encounters(
+    window: tauri::Window,
+    base_id: i32,
+    other_id: i32,
+    player: String,
+) -> Result<EncounterComparison, String> {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&path)?;
 
-    encounter
+    compare::compare_encounters(&conn, base_id, other_id, &player)
 }
 
 #[tauri::command]
@@ -902,15 +432,154 @@ fn toggle_encounter_favorite(window: tauri::Window, id: i32) {
 }
 
 #[tauri::command]
-fn delete_encounter(window: tauri::Window, id: String) {
+fn set_encounter_notes(window: tauri::Window, id: i32, notes: String) {
     let path = window
         .app_handle()
         .path_resolver()
         .resource_dir()
         .expect("could not get resource dir");
+
     let conn = get_db_connection(&path).expect("could not get db connection");
-    conn.execute("PRAGMA foreign_keys = ON;", params![])
+    let mut stmt = conn
+        .prepare_cached(
+            "
+    UPDATE encounter
+    SET notes = ?
+    WHERE id = ?;
+    ",
+        )
         .unwrap();
+
+    stmt.execute(params![notes, id]).unwrap();
+}
+
+#[tauri::command]
+fn get_character_records(
+    window: tauri::Window,
+    character: String,
+    character_id: Option<u64>,
+) -> Vec<CharacterRecord> {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&path).expect("could not get db connection");
+
+    records::get_character_records(&conn, &character, character_id).expect("could not get records")
+}
+
+#[tauri::command]
+fn get_character_history(
+    window: tauri::Window,
+    character: String,
+    filter: HistoryFilter,
+) -> Vec<HistoryPoint> {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&path).expect("could not get db connection");
+
+    history::get_character_history(&conn, &character, &filter).expect("could not get history")
+}
+
+#[tauri::command]
+fn get_characters(window: tauri::Window, name: String) -> Vec<Character> {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&path).expect("could not get db connection");
+
+    characters::get_characters(&conn, &name).expect("could not get characters")
+}
+
+#[tauri::command]
+fn export_encounter(window: tauri::Window, id: i32, path: String) -> Result<(), String> {
+    let app_handle = window.app_handle();
+    let resource_path = app_handle
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&resource_path).expect("could not get db connection");
+
+    let app_version = app_handle.package_info().version.to_string();
+    export::export_encounter(&conn, id, &PathBuf::from(path), &app_version)
+}
+
+#[tauri::command]
+fn import_encounter(window: tauri::Window, path: String) -> Result<i64, String> {
+    let resource_path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let mut conn = get_db_connection(&resource_path).expect("could not get db connection");
+
+    let id = export::import_encounter(&mut conn, &PathBuf::from(&path))?;
+    info!("imported {} as encounter {}", path, id);
+    Ok(id)
+}
+
+#[tauri::command]
+fn export_encounters_csv(window: tauri::Window, ids: Vec<i32>, dir: String) -> Result<(), String> {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&path).expect("could not get db connection");
+
+    let (players, skills) = csv::export_csv(&conn, &ids, &PathBuf::from(dir))?;
+    info!("exported {} encounters to {:?} and {:?}", ids.len(), players, skills);
+    Ok(())
+}
+
+#[tauri::command]
+fn write_encounter_report(window: tauri::Window, id: i32, path: String) -> Result<(), String> {
+    let resource_path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&resource_path).expect("could not get db connection");
+
+    report::write_report(
+        &conn,
+        id,
+        &PathBuf::from(path),
+        &resource_path.join("images"),
+    )
+}
+
+#[tauri::command]
+fn get_encounter_summary(
+    window: tauri::Window,
+    id: i32,
+    template: SummaryTemplate,
+    split_parties: bool,
+) -> Result<String, String> {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&path).expect("could not get db connection");
+
+    summary::encounter_summary(&conn, id, template, split_parties)
+}
+
+#[tauri::command]
+fn delete_encounter(window: tauri::Window, id: String) {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    let conn = get_db_connection(&path).expect("could not get db connection");
     let mut stmt = conn
         .prepare_cached(
             "
@@ -923,6 +592,7 @@ fn delete_encounter(window: tauri::Window, id: String) {
     info!("deleting encounter: {}", id);
 
     stmt.execute(params![id]).unwrap();
+    rebuild_records(&conn).expect("failed to rebuild records");
 }
 
 #[tauri::command]
@@ -933,8 +603,6 @@ fn delete_encounters(window: tauri::Window, ids: Vec<i32>) {
         .resource_dir()
         .expect("could not get resource dir");
     let conn = get_db_connection(&path).expect("could not get db connection");
-    conn.execute("PRAGMA foreign_keys = ON;", params![])
-        .unwrap();
 
     let placeholders: Vec<String> = ids.iter().map(|_| "?".to_string()).collect();
     let placeholders_str = placeholders.join(",");
@@ -945,6 +613,7 @@ fn delete_encounters(window: tauri::Window, ids: Vec<i32>) {
     info!("deleting encounters: {:?}", ids);
 
     stmt.execute(params_from_iter(ids)).unwrap();
+    rebuild_records(&conn).expect("failed to rebuild records");
 }
 
 #[tauri::command]
@@ -990,16 +659,6 @@ fn save_settings(window: tauri::Window, settings: Settings) {
         .expect("could not write to settings file");
 }
 
-fn read_settings(resource_path: &Path) -> Result<Settings, Box<dyn std::error::Error>> {
-    let mut path = resource_path.to_path_buf();
-    path.push("settings.json");
-    let mut file = File::open(path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let settings = serde_json::from_str(&contents)?;
-    Ok(settings)
-}
-
 #[tauri::command]
 fn get_settings(window: tauri::Window) -> Option<Settings> {
     let path = window
@@ -1010,6 +669,32 @@ fn get_settings(window: tauri::Window) -> Option<Settings> {
     read_settings(&path).ok()
 }
 
+#[tauri::command]
+fn reload_meter_data(window: tauri::Window) {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    load_meter_data(&path);
+}
+
+// raid names and gates for the log filters, reloaded along with the rest of meter data
+#[tauri::command]
+fn get_raids() -> Vec<Raid> {
+    RAID_DATA.load().raids.clone()
+}
+
+#[tauri::command]
+fn check_db(window: tauri::Window) -> Result<(), String> {
+    let path = window
+        .app_handle()
+        .path_resolver()
+        .resource_dir()
+        .expect("could not get resource dir");
+    check_db_version(&path)
+}
+
 #[tauri::command]
 fn get_network_interfaces() -> Vec<(String, String)> {
     let interfaces = meter_core::get_network_interfaces();
@@ -1059,6 +744,7 @@ fn delete_encounters_below_min_duration(window: tauri::Window, min_duration: i64
         params![min_duration * 1000],
     )
     .unwrap();
+    rebuild_records(&conn).expect("failed to rebuild records");
     conn.execute("VACUUM;", params![]).unwrap();
 }
 
@@ -1091,6 +777,7 @@ fn delete_all_encounters(window: tauri::Window) {
     let conn = get_db_connection(&path).expect("could not get db connection");
 
     conn.execute("DELETE FROM encounter", []).unwrap();
+    rebuild_records(&conn).expect("failed to rebuild records");
     conn.execute("VACUUM", []).unwrap();
 }
 
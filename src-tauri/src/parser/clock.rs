@@ -0,0 +1,30 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+// time of the packet currently being parsed, as unix ms
+// set from the receive time when capturing and the recorded time when replaying,
+// so the same packets always produce the same encounter
+#[derive(Debug, Clone, Default)]
+pub struct PacketClock {
+    now: Rc<Cell<i64>>,
+}
+
+impl PacketClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, timestamp: i64) {
+        self.now.set(timestamp);
+    }
+
+    pub fn now(&self) -> i64 {
+        self.now.get()
+    }
+
+    pub fn now_utc(&self) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(self.now()).unwrap()
+    }
+}
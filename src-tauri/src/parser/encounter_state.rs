@@ -1,22 +1,32 @@
+use std::cell::RefCell;
 use std::cmp::{max, Ordering};
+use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::db::encounters::insert_encounter;
+use crate::db::open_db;
+use crate::db::records::update_records;
+use crate::parser::clock::PacketClock;
 use crate::parser::entity_tracker::Entity;
+use crate::parser::id_tracker::IdTracker;
 use crate::parser::models::*;
-use chrono::Utc;
+use crate::parser::sink::EventSink;
+use crate::parser::status_tracker::{StatusEffectTargetType, StatusTracker};
 use hashbrown::HashMap;
-use log::info;
+use log::{info, warn};
 use meter_core::packets::definitions::{PKTIdentityGaugeChangeNotify, PKTParalyzationStateNotify};
-use rusqlite::{params, Connection, Transaction};
+use rusqlite::Transaction;
 use serde_json::json;
-use tauri::{Manager, Window, Wry};
 use tokio::task;
 
 const WINDOW_MS: i64 = 5_000;
 const WINDOW_S: i64 = 5;
 
-#[derive(Debug)]
 pub struct EncounterState {
-    pub window: Window<Wry>,
+    pub sink: Arc<dyn EventSink>,
+    clock: PacketClock,
+    status_tracker: Rc<RefCell<StatusTracker>>,
+    id_tracker: Rc<RefCell<IdTracker>>,
     pub encounter: Encounter,
     pub resetting: bool,
     pub boss_dead_update: bool,
@@ -38,12 +48,26 @@ pub struct EncounterState {
     pub party_info: Vec<Vec<String>>,
     pub raid_difficulty: String,
     pub boss_only_damage: bool,
+
+    saves: Vec<task::JoinHandle<()>>,
+
+    // every hit handed to on_damage with the effects it saw, for the golden tests
+    #[cfg(test)]
+    pub hits: Vec<serde_json::Value>,
 }
 
 impl EncounterState {
-    pub fn new(window: Window<Wry>) -> EncounterState {
+    pub fn new(
+        sink: Arc<dyn EventSink>,
+        clock: PacketClock,
+        status_tracker: Rc<RefCell<StatusTracker>>,
+        id_tracker: Rc<RefCell<IdTracker>>,
+    ) -> EncounterState {
         EncounterState {
-            window,
+            sink,
+            clock,
+            status_tracker,
+            id_tracker,
             encounter: Encounter::default(),
             resetting: false,
             raid_clear: false,
@@ -61,6 +85,11 @@ impl EncounterState {
             party_info: Vec::new(),
             raid_difficulty: "".to_string(),
             boss_only_damage: false,
+
+            saves: Vec::new(),
+
+            #[cfg(test)]
+            hits: Vec::new(),
         }
     }
 
@@ -83,6 +112,7 @@ impl EncounterState {
         self.stagger_log = Vec::new();
         self.stagger_intervals = Vec::new();
         self.party_info = Vec::new();
+        self.status_tracker.borrow_mut().clear_uptime();
 
         for (key, entity) in clone.entities.into_iter().filter(|(_, e)| {
             e.entity_type == EntityType::PLAYER
@@ -155,6 +185,7 @@ impl EncounterState {
         if let Some(mut local_player) = self.encounter.entities.remove(&self.encounter.local_player)
         {
             local_player.id = entity.id;
+            local_player.character_id = entity.character_id;
             local_player.name = entity.name.clone();
             local_player.class_id = entity.class_id;
             local_player.class = get_class_from_id(&entity.class_id);
@@ -177,16 +208,16 @@ impl EncounterState {
             e.name == self.encounter.local_player || e.damage_stats.damage_dealt > 0
         });
 
-        self.window
-            .emit("zone-change", "")
+        self.sink
+            .emit("zone-change", json!(""))
             .expect("failed to emit zone-change");
 
         self.soft_reset(false);
     }
 
     pub fn on_phase_transition(&mut self, phase_code: i32) {
-        self.window
-            .emit("phase-transition", phase_code)
+        self.sink
+            .emit("phase-transition", json!(phase_code))
             .expect("failed to emit phase-transition");
 
         match phase_code {
@@ -218,6 +249,7 @@ impl EncounterState {
             .entry(entity.name.clone())
             .and_modify(|player| {
                 player.id = entity.id;
+                player.character_id = entity.character_id;
                 player.gear_score = entity.gear_level;
                 player.current_hp = hp;
                 player.max_hp = max_hp;
@@ -300,7 +332,7 @@ impl EncounterState {
         entity.current_hp = 0;
         entity.is_dead = true;
         entity.damage_stats.deaths += 1;
-        entity.damage_stats.death_time = Utc::now().timestamp_millis();
+        entity.damage_stats.death_time = self.clock.now();
     }
 
     pub fn on_skill_start(
@@ -407,6 +439,22 @@ impl EncounterState {
         se_on_source: Vec<(u32, u64)>,
         se_on_target: Vec<(u32, u64)>,
     ) {
+        #[cfg(test)]
+        {
+            let mut effects_on_source = se_on_source.clone();
+            let mut effects_on_target = se_on_target.clone();
+            effects_on_source.sort();
+            effects_on_target.sort();
+            self.hits.push(json!({
+                "time": self.clock.now(),
+                "source": dmg_src_entity.name,
+                "target": dmg_target_entity.name,
+                "damage": damage,
+                "effectsOnSource": effects_on_source,
+                "effectsOnTarget": effects_on_target,
+            }));
+        }
+
         let hit_flag = match modifier & 0xf {
             0 => HitFlag::NORMAL,
             1 => HitFlag::CRITICAL,
@@ -484,13 +532,13 @@ impl EncounterState {
             return;
         }
 
-        let timestamp = Utc::now().timestamp_millis();
+        let timestamp = self.clock.now();
 
         if self.encounter.fight_start == 0 {
             self.encounter.fight_start = timestamp;
             self.encounter.boss_only_damage = self.boss_only_damage;
-            self.window
-                .emit("raid-start", timestamp)
+            self.sink
+                .emit("raid-start", json!(timestamp))
                 .expect("failed to emit raid-start");
         }
 
@@ -515,6 +563,8 @@ impl EncounterState {
             skill_name = get_skill_name_and_icon(&skill_id, &skill_effect_id, "".to_string()).0;
         }
         let relative_timestamp = (timestamp - self.encounter.fight_start) as i32;
+        // unnamed enemy skills would all be merged into one below
+        let hit_skill_id = skill_id;
 
         if !source_entity.skills.contains_key(&skill_id) {
             if let Some(skill) = source_entity
@@ -589,13 +639,24 @@ impl EncounterState {
                 .or_default()
                 .push((timestamp, damage));
 
-            let mut is_buffed_by_support = false;
-            let mut is_debuffed_by_support = false;
-            let se_on_source = se_on_source
+            // split by target so boss and add damage can be told apart without boss only damage
+            add_target_damage(&mut skill.damage_by_target, dmg_target_entity, damage);
+            add_target_damage(
+                &mut source_entity.damage_stats.damage_by_target,
+                dmg_target_entity,
+                damage,
+            );
+            add_target_damage(
+                &mut self.encounter.encounter_damage_stats.damage_by_target,
+                dmg_target_entity,
+                damage,
+            );
+
+            let buff_ids = se_on_source
                 .iter()
                 .map(|(se, _)| (*se) as i32)
                 .collect::<Vec<_>>();
-            for buff_id in se_on_source.iter() {
+            for buff_id in buff_ids.iter() {
                 if !self
                     .encounter
                     .encounter_damage_stats
@@ -619,21 +680,12 @@ impl EncounterState {
                             .insert(*buff_id);
                     }
                 }
-                if !is_buffed_by_support {
-                    if let Some(buff) = self.encounter.encounter_damage_stats.buffs.get(buff_id) {
-                        if let Some(skill) = buff.source.skill.as_ref() {
-                            is_buffed_by_support = is_support_class_id(skill.class_id)
-                                && buff.buff_type & StatusEffectBuffTypeFlags::DMG.bits() != 0
-                                && buff.target == StatusEffectTarget::PARTY;
-                        }
-                    }
-                }
             }
-            let se_on_target = se_on_target
+            let debuff_ids = se_on_target
                 .iter()
                 .map(|(se, _)| (*se) as i32)
                 .collect::<Vec<_>>();
-            for debuff_id in se_on_target.iter() {
+            for debuff_id in debuff_ids.iter() {
                 if !self
                     .encounter
                     .encounter_damage_stats
@@ -657,29 +709,50 @@ impl EncounterState {
                             .insert(*debuff_id);
                     }
                 }
-                if !is_debuffed_by_support {
-                    if let Some(debuff) =
-                        self.encounter.encounter_damage_stats.debuffs.get(debuff_id)
-                    {
-                        if let Some(skill) = debuff.source.skill.as_ref() {
-                            is_debuffed_by_support = is_support_class_id(skill.class_id)
-                                && debuff.buff_type & StatusEffectBuffTypeFlags::DMG.bits() != 0
-                                && debuff.target == StatusEffectTarget::PARTY;
-                        }
-                    }
-                }
             }
 
-            if is_buffed_by_support {
+            let buffing_supports =
+                support_sources(&se_on_source, &self.encounter.encounter_damage_stats.buffs);
+            let debuffing_supports = support_sources(
+                &se_on_target,
+                &self.encounter.encounter_damage_stats.debuffs,
+            );
+
+            if !buffing_supports.is_empty() {
                 skill.buffed_by_support += damage;
                 source_entity.damage_stats.buffed_by_support += damage;
             }
-            if is_debuffed_by_support {
+            if !debuffing_supports.is_empty() {
                 skill.debuffed_by_support += damage;
                 source_entity.damage_stats.debuffed_by_support += damage;
             }
 
-            for buff_id in se_on_source.into_iter() {
+            // credit each support whose buffs or brands were up, not just whether any were
+            for support_id in buffing_supports {
+                if let Some(support) = self.support_entity(&mut source_entity, support_id) {
+                    support.damage_stats.support_buffed_damage += damage;
+                    let name = support.name.clone();
+                    *source_entity
+                        .damage_stats
+                        .buffed_by_supports
+                        .entry(name)
+                        .or_default() += damage;
+                }
+            }
+            for support_id in debuffing_supports {
+                if let Some(support) = self.support_entity(&mut source_entity, support_id) {
+                    support.damage_stats.support_debuffed_damage += damage;
+                    let name = support.name.clone();
+                    *source_entity
+                        .damage_stats
+                        .debuffed_by_supports
+                        .entry(name)
+                        .or_default() += damage;
+                }
+            }
+
+            let skill = source_entity.skills.get_mut(&skill_id).unwrap();
+            for buff_id in buff_ids.into_iter() {
                 skill
                     .buffed_by
                     .entry(buff_id)
@@ -692,7 +765,7 @@ impl EncounterState {
                     .and_modify(|e| *e += damage)
                     .or_insert(damage);
             }
-            for debuff_id in se_on_target.into_iter() {
+            for debuff_id in debuff_ids.into_iter() {
                 skill
                     .debuffed_by
                     .entry(debuff_id)
@@ -713,6 +786,27 @@ impl EncounterState {
                 self.encounter.encounter_damage_stats.top_damage_taken,
                 target_entity.damage_stats.damage_taken,
             );
+
+            // which mechanics were eaten, by the enemy that used them
+            let taken = target_entity
+                .damage_stats
+                .damage_taken_by_skill
+                .entry(source_entity.name.clone())
+                .or_default()
+                .entry(hit_skill_id)
+                .or_insert_with(|| {
+                    let (name, icon) =
+                        get_skill_name_and_icon(&hit_skill_id, &skill_effect_id, skill_name);
+                    DamageTakenSkill {
+                        id: hit_skill_id,
+                        name,
+                        icon,
+                        ..Default::default()
+                    }
+                });
+            taken.damage += damage;
+            taken.hits += 1;
+            taken.max_hit = max(taken.max_hit, damage);
         }
         // update current_boss
         else if target_entity.entity_type == EntityType::BOSS {
@@ -757,6 +851,31 @@ impl EncounterState {
         self.encounter.last_combat_packet = timestamp;
     }
 
+    // the source is held outside the entity map while a hit is processed, supports can hit too
+    // effects keep the id their source had when applied, a support that respawned since has a new
+    // one, so fall back to the character behind the old id
+    fn support_entity<'a>(
+        &'a mut self,
+        source_entity: &'a mut EncounterEntity,
+        support_id: u64,
+    ) -> Option<&'a mut EncounterEntity> {
+        let character_id = self
+            .id_tracker
+            .borrow()
+            .get_character_id(support_id)
+            .unwrap_or_default();
+        let is_support = |e: &EncounterEntity| {
+            e.id == support_id || (character_id != 0 && e.character_id == character_id)
+        };
+        if is_support(source_entity) {
+            return Some(source_entity);
+        }
+        self.encounter
+            .entities
+            .values_mut()
+            .find(|e| e.entity_type == EntityType::PLAYER && is_support(e))
+    }
+
     pub fn on_counterattack(&mut self, source_entity: &Entity) {
         let entity = self
             .encounter
@@ -773,6 +892,116 @@ impl EncounterState {
         entity.skill_stats.counters += 1;
     }
 
+    pub fn on_shield_applied(
+        &mut self,
+        source_entity: &Entity,
+        target_entity: &Entity,
+        buff_id: i32,
+        shield: i64,
+    ) {
+        if self.encounter.fight_start == 0
+            || source_entity.entity_type != EntityType::PLAYER
+            || target_entity.entity_type != EntityType::PLAYER
+            || shield <= 0
+        {
+            return;
+        }
+
+        self.add_shield_buff(buff_id);
+        self.encounter.encounter_damage_stats.total_shielding += shield;
+
+        if source_entity.id != target_entity.id {
+            let source = self
+                .encounter
+                .entities
+                .entry(source_entity.name.clone())
+                .or_insert_with(|| encounter_entity_from_entity(source_entity));
+            source.damage_stats.shields_given += shield;
+            *source
+                .damage_stats
+                .shields_given_by
+                .entry(buff_id)
+                .or_default() += shield;
+        }
+
+        let target = self
+            .encounter
+            .entities
+            .entry(target_entity.name.clone())
+            .or_insert_with(|| encounter_entity_from_entity(target_entity));
+        target.damage_stats.shields_received += shield;
+        *target
+            .damage_stats
+            .shields_received_by
+            .entry(buff_id)
+            .or_default() += shield;
+    }
+
+    // absorbed is how much the shield's value went down since the last sync
+    pub fn on_shield_absorbed(
+        &mut self,
+        source_entity: &Entity,
+        target_entity: &Entity,
+        buff_id: i32,
+        absorbed: i64,
+    ) {
+        if self.encounter.fight_start == 0
+            || source_entity.entity_type != EntityType::PLAYER
+            || target_entity.entity_type != EntityType::PLAYER
+            || absorbed <= 0
+        {
+            return;
+        }
+
+        self.add_shield_buff(buff_id);
+        self.encounter
+            .encounter_damage_stats
+            .total_effective_shielding += absorbed;
+
+        if source_entity.id != target_entity.id {
+            let source = self
+                .encounter
+                .entities
+                .entry(source_entity.name.clone())
+                .or_insert_with(|| encounter_entity_from_entity(source_entity));
+            source.damage_stats.damage_absorbed_on_others += absorbed;
+            *source
+                .damage_stats
+                .damage_absorbed_on_others_by
+                .entry(buff_id)
+                .or_default() += absorbed;
+        }
+
+        let target = self
+            .encounter
+            .entities
+            .entry(target_entity.name.clone())
+            .or_insert_with(|| encounter_entity_from_entity(target_entity));
+        target.damage_stats.damage_absorbed += absorbed;
+        *target
+            .damage_stats
+            .damage_absorbed_by
+            .entry(buff_id)
+            .or_default() += absorbed;
+    }
+
+    fn add_shield_buff(&mut self, buff_id: i32) {
+        let stats = &mut self.encounter.encounter_damage_stats;
+        if stats.applied_shield_buffs.contains_key(&buff_id)
+            || stats.unknown_buffs.contains(&buff_id)
+        {
+            return;
+        }
+        match get_status_effect_data(buff_id) {
+            Some(status_effect) => {
+                stats.applied_shield_buffs.insert(buff_id, status_effect);
+            }
+            None => {
+                stats.unknown_buffs.insert(buff_id);
+            }
+        }
+    }
+
     pub fn on_identity_gain(&mut self, pkt: &PKTIdentityGaugeChangeNotify) {
         if self.encounter.fight_start == 0 {
             return;
@@ -800,7 +1029,7 @@ impl EncounterState {
                 .entry(entity.name.clone())
                 .or_default()
                 .push((
-                    Utc::now().timestamp_millis(),
+                    self.clock.now(),
                     (
                         pkt.identity_gauge1,
                         pkt.identity_gauge2,
@@ -820,7 +1049,7 @@ impl EncounterState {
             .entities
             .get_mut(&self.encounter.current_boss_name)
         {
-            let timestamp = Utc::now().timestamp_millis();
+            let timestamp = self.clock.now();
             let current_stagger = pkt.paralyzation_point as i32;
             let max_stagger = pkt.paralyzation_max_point as i32;
             if boss.id == pkt.object_id {
@@ -847,7 +1076,7 @@ impl EncounterState {
         }
     }
 
-    pub fn save_to_db(&self, manual: bool) {
+    pub fn save_to_db(&mut self, manual: bool) {
         if !manual {
             if self.encounter.fight_start == 0
                 || self.encounter.current_boss_name.is_empty()
@@ -875,13 +1104,9 @@ impl EncounterState {
             }
         }
 
-        let encounter = self.encounter.clone();
-        let mut path = self
-            .window
-            .app_handle()
-            .path_resolver()
-            .resource_dir()
-            .expect("could not get resource dir");
+        let mut encounter = self.encounter.clone();
+        self.add_uptime(&mut encounter);
+        let mut path = self.sink.resource_dir();
         path.push("encounters.db");
         let prev_stagger = self.prev_stagger;
 
@@ -894,14 +1119,17 @@ impl EncounterState {
         let raid_clear = self.raid_clear;
         let party_info = self.party_info.clone();
         let raid_difficulty = self.raid_difficulty.clone();
+        let sink = self.sink.clone();
 
-        task::spawn(async move {
+        self.saves.retain(|save| !save.is_finished());
+        self.saves.push(task::spawn(async move {
             info!("saving to db - {}", encounter.current_boss_name);
 
-            let mut conn = Connection::open(path).expect("failed to open database");
-            let tx = conn.transaction().expect("failed to create transaction");
+            let mut conn = open_db(&path).expect("failed to open database");
+            let mut tx = conn.transaction().expect("failed to create transaction");
 
-            insert_data(
+            let local_player = encounter.local_player.clone();
+            let encounter_id = insert_data(
                 &tx,
                 encounter,
                 prev_stagger,
@@ -915,16 +1143,100 @@ impl EncounterState {
                 party_info,
                 raid_difficulty,
             );
+            // records are derived and can be rebuilt, failing them must not lose the encounter
+            let records = match tx.savepoint().and_then(|sp| {
+                let records = update_records(&sp, encounter_id)?;
+                sp.commit()?;
+                Ok(records)
+            }) {
+                Ok(records) => records,
+                Err(e) => {
+                    warn!("failed to update records: {}", e);
+                    Vec::new()
+                }
+            };
 
             tx.commit().expect("failed to commit transaction");
             info!("saved to db");
-        });
+
+            // first records are sent too with previous unset, the ui decides which ones to show
+            let personal_bests: Vec<PersonalBest> = records
+                .into_iter()
+                .filter(|record| record.character == local_player)
+                .collect();
+            if !personal_bests.is_empty() {
+                info!("new personal bests: {:?}", personal_bests);
+                if let Err(e) = sink.emit("personal-best", json!(personal_bests)) {
+                    warn!("failed to emit personal-best: {}", e);
+                }
+            }
+        }));
+    }
+
+    // saves the encounter in progress once packets stop coming and waits for every save,
+    // the process may exit as soon as the parser returns
+    pub fn finish_saves(&mut self) {
+        if !self.saved && !self.encounter.current_boss_name.is_empty() {
+            self.save_to_db(false);
+            self.saved = true;
+        }
+
+        let runtime = tokio::runtime::Handle::current();
+        for save in self.saves.drain(..) {
+            if let Err(e) = runtime.block_on(save) {
+                warn!("failed to save encounter: {}", e);
+            }
+        }
+    }
+
+    // buff uptime on players and debuff uptime on bosses over the whole fight
+    fn add_uptime(&self, encounter: &mut Encounter) {
+        let start = encounter.fight_start;
+        let end = encounter.last_combat_packet;
+        let status_tracker = self.status_tracker.borrow();
+        let buffs = &encounter.encounter_damage_stats.buffs;
+        let debuffs = &encounter.encounter_damage_stats.debuffs;
+        for entity in encounter.entities.values_mut() {
+            let mut uptime = HashMap::new();
+            match entity.entity_type {
+                EntityType::PLAYER => {
+                    // party buffs are tracked by character id, everything else by entity id
+                    for (target_id, sett) in [
+                        (entity.character_id, StatusEffectTargetType::Party),
+                        (entity.id, StatusEffectTargetType::Local),
+                    ] {
+                        for (id, fraction) in status_tracker.get_uptime(target_id, sett, start, end)
+                        {
+                            if buffs.contains_key(&(id as i32)) {
+                                let value = uptime.entry(id as i32).or_insert(0.0);
+                                *value = fraction.max(*value);
+                            }
+                        }
+                    }
+                }
+                EntityType::BOSS => {
+                    for (id, fraction) in status_tracker.get_uptime(
+                        entity.id,
+                        StatusEffectTargetType::Local,
+                        start,
+                        end,
+                    ) {
+                        if debuffs.contains_key(&(id as i32)) {
+                            uptime.insert(id as i32, fraction);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            entity.damage_stats.uptime = uptime;
+        }
     }
 }
 
 fn encounter_entity_from_entity(entity: &Entity) -> EncounterEntity {
     EncounterEntity {
         id: entity.id,
+        character_id: entity.character_id,
         name: entity.name.clone(),
         entity_type: entity.entity_type,
         npc_id: entity.npc_id,
@@ -939,8 +1251,41 @@ fn is_support_class_id(class_id: u32) -> bool {
     class_id == 105 || class_id == 204 || class_id == 602
 }
 
+fn is_support_buff(buff: &StatusEffect) -> bool {
+    buff.source.skill.as_ref().is_some_and(|skill| {
+        is_support_class_id(skill.class_id)
+            && buff.buff_type & StatusEffectBuffTypeFlags::DMG.bits() != 0
+            && buff.target == StatusEffectTarget::PARTY
+    })
+}
+
+// distinct sources of the support damage buffs among the effects on an entity
+fn support_sources(effects: &[(u32, u64)], data: &HashMap<i32, StatusEffect>) -> Vec<u64> {
+    let mut sources = Vec::new();
+    for (id, source_id) in effects {
+        if data.get(&(*id as i32)).is_some_and(is_support_buff) && !sources.contains(source_id) {
+            sources.push(*source_id);
+        }
+    }
+    sources
+}
+
+fn add_target_damage(
+    damage_by_target: &mut HashMap<u32, TargetDamage>,
+    target: &Entity,
+    damage: i64,
+) {
+    let entry = damage_by_target
+        .entry(target.npc_id)
+        .or_insert_with(|| TargetDamage {
+            name: target.name.clone(),
+            ..Default::default()
+        });
+    entry.damage += damage;
+}
+
 fn is_battle_item(skill_effect_id: i32, _item_type: &str) -> bool {
-    if let Some(item) = SKILL_EFFECT_DATA.get(&skill_effect_id) {
+    if let Some(item) = SKILL_EFFECT_DATA.load().get(&skill_effect_id) {
         if let Some(category) = item.item_category.as_ref() {
             return category == "useup_battle_item_common_attack";
         }
@@ -949,7 +1294,9 @@ fn is_battle_item(skill_effect_id: i32, _item_type: &str) -> bool {
 }
 
 fn get_status_effect_data(buff_id: i32) -> Option<StatusEffect> {
-    let buff = SKILL_BUFF_DATA.get(&buff_id);
+    let skill_buff_data = SKILL_BUFF_DATA.load();
+    let skill_data = SKILL_DATA.load();
+    let buff = skill_buff_data.get(&buff_id);
     if buff.is_none() || buff.unwrap().icon_show_type == "none" {
         return None;
     }
@@ -989,23 +1336,23 @@ fn get_status_effect_data(buff_id: i32) -> Option<StatusEffect> {
         || (buff_category == "ability" && buff.unique_group != 0)
     {
         if buff.source_skill.is_some() {
-            let buff_source_skill = SKILL_DATA.get(&buff.source_skill.unwrap());
+            let buff_source_skill = skill_data.get(&buff.source_skill.unwrap());
             if buff_source_skill.is_some() {
                 status_effect.source.skill = buff_source_skill.cloned();
             }
-        } else if let Some(buff_source_skill) = SKILL_DATA.get(&(buff_id / 10)) {
+        } else if let Some(buff_source_skill) = skill_data.get(&(buff_id / 10)) {
             status_effect.source.skill = Some(buff_source_skill.clone());
-        } else if let Some(buff_source_skill) = SKILL_DATA.get(&((buff_id / 100) * 10)) {
+        } else if let Some(buff_source_skill) = skill_data.get(&((buff_id / 100) * 10)) {
             status_effect.source.skill = Some(buff_source_skill.clone());
         } else {
             let skill_id = buff.unique_group / 10;
-            let buff_source_skill = SKILL_DATA.get(&skill_id);
+            let buff_source_skill = skill_data.get(&skill_id);
             status_effect.source.skill = buff_source_skill.cloned();
         }
     } else if buff_category == "set" && buff.set_name.is_some() {
         status_effect.source.set_name = buff.set_name.clone();
     } else if buff_category == "battleitem" {
-        if let Some(buff_source_item) = SKILL_EFFECT_DATA.get(&buff_id) {
+        if let Some(buff_source_item) = SKILL_EFFECT_DATA.load().get(&buff_id) {
             if let Some(item_name) = buff_source_item.item_name.as_ref() {
                 status_effect.source.name = item_name.clone();
             }
@@ -1175,7 +1522,7 @@ fn get_status_effect_buff_type_flags(buff: &SkillBuffData) -> u32 {
         } else if ["skill_mana_reduction", "mana_reduction"].contains(&option_type) {
             buff_type |= StatusEffectBuffTypeFlags::RESOURCE;
         } else if option_type == "combat_effect" {
-            if let Some(combat_effect) = COMBAT_EFFECT_DATA.get(&option.key_index) {
+            if let Some(combat_effect) = COMBAT_EFFECT_DATA.load().get(&option.key_index) {
                 for effect in combat_effect.effects.iter() {
                     for action in effect.actions.iter() {
                         if [
@@ -1211,7 +1558,7 @@ fn get_skill_name_and_icon(
     if (*skill_id == 0) && (*skill_effect_id == 0) {
         ("Bleed".to_string(), "buff_168.png".to_string())
     } else if (*skill_effect_id != 0) && (*skill_effect_id == *skill_id) {
-        return if let Some(effect) = SKILL_EFFECT_DATA.get(skill_effect_id) {
+        return if let Some(effect) = SKILL_EFFECT_DATA.load().get(skill_effect_id) {
             if let Some(item_name) = effect.item_name.as_ref() {
                 return (
                     item_name.clone(),
@@ -1219,10 +1566,10 @@ fn get_skill_name_and_icon(
                 );
             }
             if let Some(source_skill) = effect.source_skill {
-                if let Some(skill) = SKILL_DATA.get(&source_skill) {
+                if let Some(skill) = SKILL_DATA.load().get(&source_skill) {
                     return (skill.name.clone(), skill.icon.clone());
                 }
-            } else if let Some(skill) = SKILL_DATA.get(&(skill_effect_id / 10)) {
+            } else if let Some(skill) = SKILL_DATA.load().get(&(skill_effect_id / 10)) {
                 return (skill.name.clone(), skill.icon.clone());
             }
             (effect.comment.clone(), "".to_string())
@@ -1230,15 +1577,15 @@ fn get_skill_name_and_icon(
             (skill_name, "".to_string())
         };
     } else {
-        return if let Some(skill) = SKILL_DATA.get(skill_id) {
+        return if let Some(skill) = SKILL_DATA.load().get(skill_id) {
             if let Some(summon_source_skill) = skill.summon_source_skill {
-                if let Some(skill) = SKILL_DATA.get(&summon_source_skill) {
+                if let Some(skill) = SKILL_DATA.load().get(&summon_source_skill) {
                     (skill.name.clone() + " (Summon)", skill.icon.clone())
                 } else {
                     (skill_name, "".to_string())
                 }
             } else if let Some(source_skill) = skill.source_skill {
-                if let Some(skill) = SKILL_DATA.get(&source_skill) {
+                if let Some(skill) = SKILL_DATA.load().get(&source_skill) {
                     (skill.name.clone(), skill.icon.clone())
                 } else {
                     (skill_name, "".to_string())
@@ -1246,7 +1593,7 @@ fn get_skill_name_and_icon(
             } else {
                 (skill.name.clone(), skill.icon.clone())
             }
-        } else if let Some(skill) = SKILL_DATA.get(&(skill_id - (skill_id % 10))) {
+        } else if let Some(skill) = SKILL_DATA.load().get(&(skill_id - (skill_id % 10))) {
             (skill.name.clone(), skill.icon.clone())
         } else {
             (skill_name, "".to_string())
@@ -1256,6 +1603,7 @@ fn get_skill_name_and_icon(
 
 fn get_skill_name(skill_id: &i32) -> String {
     SKILL_DATA
+        .load()
         .get(skill_id)
         .map_or("".to_string(), |skill| skill.name.clone())
 }
@@ -1274,32 +1622,7 @@ fn insert_data(
     raid_clear: bool,
     party_info: Vec<Vec<String>>,
     raid_difficulty: String,
-) {
-    let mut encounter_stmt = tx
-        .prepare_cached(
-            "
-    INSERT INTO encounter (
-        last_combat_packet,
-        fight_start,
-        local_player,
-        current_boss,
-        duration,
-        total_damage_dealt,
-        top_damage_dealt,
-        total_damage_taken,
-        top_damage_taken,
-        dps,
-        buffs,
-        debuffs,
-        misc,
-        difficulty,
-        cleared,
-        boss_only_damage,
-        version
-    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
-        )
-        .expect("failed to prepare encounter statement");
-
+) -> i64 {
     encounter.duration = encounter.last_combat_packet - encounter.fight_start;
     let duration_seconds = encounter.duration / 1000;
     encounter.encounter_damage_stats.dps =
@@ -1354,62 +1677,23 @@ fn insert_data(
         }
     }
 
-    encounter_stmt
-        .execute(params![
-            encounter.last_combat_packet,
-            encounter.fight_start,
-            encounter.local_player,
-            encounter.current_boss_name,
-            encounter.duration,
-            encounter.encounter_damage_stats.total_damage_dealt,
-            encounter.encounter_damage_stats.top_damage_dealt,
-            encounter.encounter_damage_stats.total_damage_taken,
-            encounter.encounter_damage_stats.top_damage_taken,
-            encounter.encounter_damage_stats.dps,
-            json!(encounter.encounter_damage_stats.buffs),
-            json!(encounter.encounter_damage_stats.debuffs),
-            json!(misc),
-            raid_difficulty,
-            raid_clear,
-            encounter.boss_only_damage,
-            DB_VERSION
-        ])
-        .expect("failed to insert encounter");
-
-    let last_insert_id = tx.last_insert_rowid();
-
-    let mut entity_stmt = tx
-        .prepare_cached(
-            "
-    INSERT INTO entity (
-        name,
-        encounter_id,
-        npc_id,
-        entity_type,
-        class_id,
-        class,
-        gear_score,
-        current_hp,
-        max_hp,
-        is_dead,
-        skills,
-        damage_stats,
-        skill_stats,
-        dps
-    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
-        )
-        .expect("failed to prepare entity statement");
+    encounter.encounter_damage_stats.misc = Some(misc);
+    encounter.difficulty = Some(raid_difficulty);
+    encounter.cleared = raid_clear;
+    encounter.version = DB_VERSION;
 
     let fight_start = encounter.fight_start;
     let fight_end = encounter.last_combat_packet;
 
-    for (_key, entity) in encounter.entities.iter_mut().filter(|(_, e)| {
+    let local_player = encounter.local_player.clone();
+    encounter.entities.retain(|_, e| {
         ((e.entity_type == EntityType::PLAYER && e.class_id != 0 && e.max_hp > 0)
-            || e.name == encounter.local_player
+            || e.name == local_player
             || e.entity_type == EntityType::ESTHER
             || (e.entity_type == EntityType::BOSS && e.max_hp > 0))
             && e.damage_stats.damage_dealt > 0
-    }) {
+    });
+    for entity in encounter.entities.values_mut() {
         if entity.entity_type == EntityType::PLAYER {
             let intervals = generate_intervals(fight_start, fight_end);
             if let Some(damage_log) = damage_log.get(&entity.name) {
@@ -1564,26 +1848,9 @@ fn insert_data(
                 entity.skill_stats.identity_stats = Some(stats);
             }
         }
-
-        entity_stmt
-            .execute(params![
-                entity.name,
-                last_insert_id,
-                entity.npc_id,
-                entity.entity_type.to_string(),
-                entity.class_id,
-                entity.class,
-                entity.gear_score,
-                entity.current_hp,
-                entity.max_hp,
-                entity.is_dead,
-                json!(entity.skills),
-                json!(entity.damage_stats),
-                json!(entity.skill_stats),
-                entity.damage_stats.dps
-            ])
-            .expect("failed to insert entity");
     }
+
+    insert_encounter(tx, &encounter, false).expect("failed to insert encounter")
 }
 
 fn generate_intervals(start: i64, end: i64) -> Vec<i64> {
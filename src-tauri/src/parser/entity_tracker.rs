@@ -1,8 +1,11 @@
+use crate::parser::clock::PacketClock;
 use crate::parser::id_tracker::IdTracker;
 use crate::parser::models::EntityType::*;
 use crate::parser::models::{EntityType, Esther, ESTHER_DATA, NPC_DATA, SKILL_DATA, STAT_TYPE_MAP};
 use crate::parser::party_tracker::PartyTracker;
-use crate::parser::status_tracker::{build_status_effect, StatusEffectTargetType, StatusTracker};
+use crate::parser::status_tracker::{
+    build_status_effect, StatusEffect, StatusEffectTargetType, StatusTracker,
+};
 
 use hashbrown::HashMap;
 use meter_core::packets::common::StatPair;
@@ -12,6 +15,7 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 pub struct EntityTracker {
+    clock: PacketClock,
     id_tracker: Rc<RefCell<IdTracker>>,
     party_tracker: Rc<RefCell<PartyTracker>>,
     status_tracker: Rc<RefCell<StatusTracker>>,
@@ -25,11 +29,13 @@ pub struct EntityTracker {
 
 impl EntityTracker {
     pub fn new(
+        clock: PacketClock,
         status_tracker: Rc<RefCell<StatusTracker>>,
         id_tracker: Rc<RefCell<IdTracker>>,
         party_tracker: Rc<RefCell<PartyTracker>>,
     ) -> Self {
         Self {
+            clock,
             status_tracker,
             id_tracker,
             party_tracker,
@@ -73,6 +79,7 @@ impl EntityTracker {
             },
         };
 
+        let old_local_player_id = self.local_player_id;
         local_player.id = pkt.player_id;
         self.local_player_id = pkt.player_id;
 
@@ -80,6 +87,10 @@ impl EntityTracker {
         self.entities.insert(local_player.id, local_player.clone());
         self.id_tracker.borrow_mut().clear();
         self.status_tracker.borrow_mut().clear();
+        // the encounter being saved already knows the local player by the new id
+        self.status_tracker
+            .borrow_mut()
+            .move_local_uptime(old_local_player_id, pkt.player_id);
         if local_player.character_id > 0 {
             self.id_tracker
                 .borrow_mut()
@@ -233,7 +244,11 @@ impl EntityTracker {
         npc
     }
 
-    pub fn party_status_effect_add(&mut self, pkt: PKTPartyStatusEffectAddNotify) {
+    pub fn party_status_effect_add(
+        &mut self,
+        pkt: PKTPartyStatusEffectAddNotify,
+    ) -> Vec<StatusEffect> {
+        let mut status_effects = Vec::new();
         for sed in pkt.status_effect_datas {
             let source_id = if pkt.player_id_on_refresh != 0 {
                 pkt.player_id_on_refresh
@@ -247,11 +262,14 @@ impl EntityTracker {
                 pkt.character_id,
                 entity.id,
                 StatusEffectTargetType::Party,
+                self.clock.now_utc(),
             );
+            status_effects.push(status_effect.clone());
             self.status_tracker
                 .borrow_mut()
                 .register_status_effect(status_effect);
         }
+        status_effects
     }
 
     pub fn party_status_effect_remove(&mut self, pkt: PKTPartyStatusEffectRemoveNotify) {
@@ -405,17 +423,23 @@ impl EntityTracker {
         entity
     }
 
-    pub fn build_and_register_status_effect(&mut self, sed: &StatusEffectData, target_id: u64) {
+    pub fn build_and_register_status_effect(
+        &mut self,
+        sed: &StatusEffectData,
+        target_id: u64,
+    ) -> StatusEffect {
         let source_entity = self.get_source_entity(sed.source_id);
         let status_effect = build_status_effect(
             sed.clone(),
             target_id,
             source_entity.id,
             StatusEffectTargetType::Local,
+            self.clock.now_utc(),
         );
         self.status_tracker
             .borrow_mut()
-            .register_status_effect(status_effect);
+            .register_status_effect(status_effect.clone());
+        status_effect
     }
 
     fn build_and_register_status_effects(&mut self, seds: Vec<StatusEffectData>, target_id: u64) {
@@ -426,6 +450,7 @@ impl EntityTracker {
                 target_id,
                 source_entity.id,
                 StatusEffectTargetType::Local,
+                self.clock.now_utc(),
             );
             self.status_tracker
                 .borrow_mut()
@@ -433,6 +458,20 @@ impl EntityTracker {
         }
     }
 
+    // party effects are keyed by character id, local ones by entity id
+    pub fn get_status_effect_entities(&self, se: &StatusEffect) -> Option<(&Entity, &Entity)> {
+        let target_id = match se.target_type {
+            StatusEffectTargetType::Party => {
+                self.id_tracker.borrow().get_entity_id(se.target_id)?
+            }
+            StatusEffectTargetType::Local => se.target_id,
+        };
+        Some((
+            self.entities.get(&se.source_id)?,
+            self.entities.get(&target_id)?,
+        ))
+    }
+
     pub fn get_or_create_entity(&mut self, id: u64) -> Entity {
         if let Some(entity) = self.entities.get(&id) {
             return entity.clone();
@@ -472,7 +511,7 @@ fn get_npc_entity_type_and_name(npc: &NpcData, max_hp: i64) -> (EntityType, Stri
         return (ESTHER, esther.name);
     }
 
-    if let Some((_, npc_info)) = NPC_DATA.get_key_value(&npc.type_id) {
+    if let Some((_, npc_info)) = NPC_DATA.load().get_key_value(&npc.type_id) {
         if (npc_info.grade == "boss"
             || npc_info.grade == "raid"
             || npc_info.grade == "epic_raid"
@@ -492,13 +531,14 @@ fn get_npc_entity_type_and_name(npc: &NpcData, max_hp: i64) -> (EntityType, Stri
 
 fn get_esther_from_npc_id(npc_id: u32) -> Option<Esther> {
     ESTHER_DATA
+        .load()
         .iter()
         .find(|esther| esther.npc_ids.contains(&npc_id))
         .cloned()
 }
 
 pub fn get_skill_class_id(skill_id: &i32) -> u32 {
-    if let Some(skill) = SKILL_DATA.get(skill_id) {
+    if let Some(skill) = SKILL_DATA.load().get(skill_id) {
         skill.class_id
     } else {
         0
@@ -0,0 +1,165 @@
+use std::fs;
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Context, Result};
+use hashbrown::HashMap;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+
+use crate::parser::models::*;
+
+// shipped copies live in <resource dir>/meter-data, anything in
+// <resource dir>/meter-data-override is merged on top so new bosses or skills
+// can be added without waiting for an update
+pub const METER_DATA_DIR: &str = "meter-data";
+pub const METER_DATA_OVERRIDE_DIR: &str = "meter-data-override";
+
+pub trait MergeData: DeserializeOwned + Send + Sync {
+    fn merge(&mut self, other: Self);
+
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<K: Eq + Hash + DeserializeOwned + Send + Sync, V: DeserializeOwned + Send + Sync> MergeData
+    for HashMap<K, V>
+{
+    fn merge(&mut self, other: Self) {
+        self.extend(other);
+    }
+}
+
+impl MergeData for Vec<Esther> {
+    // esthers have no id, replace by name
+    fn merge(&mut self, other: Self) {
+        for esther in other {
+            match self.iter_mut().find(|e| e.name == esther.name) {
+                Some(existing) => *existing = esther,
+                None => self.push(esther),
+            }
+        }
+    }
+}
+
+impl MergeData for RaidData {
+    fn merge(&mut self, other: Self) {
+        // raids have no id, replace by name
+        for raid in other.raids {
+            match self.raids.iter_mut().find(|r| r.name == raid.name) {
+                Some(existing) => *existing = raid,
+                None => self.raids.push(raid),
+            }
+        }
+        self.raid_ids.extend(other.raid_ids);
+        self.zone_levels.extend(other.zone_levels);
+        self.signals.extend(other.signals);
+        self.boss_quirks.extend(other.boss_quirks);
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.version > RAID_DATA_VERSION {
+            bail!(
+                "raid data version {} is newer than supported version {}",
+                self.version,
+                RAID_DATA_VERSION
+            );
+        }
+        for (id, info) in &self.raid_ids {
+            if info.gate.is_some() && info.name.is_none() {
+                bail!("raid id {} has a gate but no raid name", id);
+            }
+        }
+        Ok(())
+    }
+}
+
+// a data table that can be swapped while the parser is running
+// lookups hold on to the table they started with, so a reload never changes data mid packet
+pub struct DataTable<T> {
+    file: &'static str,
+    embedded: &'static str,
+    data: RwLock<Option<Arc<T>>>,
+}
+
+impl<T: MergeData> DataTable<T> {
+    pub fn new(file: &'static str, embedded: &'static str) -> Self {
+        Self {
+            file,
+            embedded,
+            data: RwLock::new(None),
+        }
+    }
+
+    pub fn load(&self) -> Arc<T> {
+        if let Some(data) = self.data.read().unwrap().as_ref() {
+            return data.clone();
+        }
+        self.data
+            .write()
+            .unwrap()
+            .get_or_insert_with(|| Arc::new(self.parse_embedded()))
+            .clone()
+    }
+
+    pub fn reload(&self, resource_path: &Path) {
+        let path = resource_path.join(METER_DATA_DIR).join(self.file);
+        let mut data = match read_data::<T>(&path) {
+            Ok(Some(data)) => data,
+            Ok(None) => self.parse_embedded(),
+            Err(e) => {
+                warn!("{:#}, using embedded {}", e, self.file);
+                self.parse_embedded()
+            }
+        };
+
+        let override_path = resource_path.join(METER_DATA_OVERRIDE_DIR).join(self.file);
+        match read_data::<T>(&override_path) {
+            Ok(Some(overrides)) => {
+                info!("merging {}", override_path.display());
+                data.merge(overrides);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("{:#}, ignoring override", e),
+        }
+
+        *self.data.write().unwrap() = Some(Arc::new(data));
+    }
+
+    // drops any reloaded data so the next lookup uses the embedded copy again
+    #[cfg(test)]
+    pub fn reset(&self) {
+        *self.data.write().unwrap() = None;
+    }
+
+    fn parse_embedded(&self) -> T {
+        serde_json::from_str(self.embedded).unwrap()
+    }
+}
+
+fn read_data<T: MergeData>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let data: T = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    data.validate()
+        .with_context(|| format!("invalid {}", path.display()))?;
+    Ok(Some(data))
+}
+
+// called on startup and from the reload command
+pub fn load_meter_data(resource_path: &Path) {
+    info!("loading meter data from {}", resource_path.display());
+    NPC_DATA.reload(resource_path);
+    SKILL_DATA.reload(resource_path);
+    SKILL_EFFECT_DATA.reload(resource_path);
+    SKILL_BUFF_DATA.reload(resource_path);
+    COMBAT_EFFECT_DATA.reload(resource_path);
+    ESTHER_DATA.reload(resource_path);
+    RAID_DATA.reload(resource_path);
+}
@@ -1,19 +1,32 @@
+mod clock;
 pub mod encounter_state;
 mod entity_tracker;
 mod id_tracker;
+pub mod meter_data;
 pub mod models;
 mod party_tracker;
+mod recorder;
+pub mod replay;
+pub mod sink;
 mod status_tracker;
+#[cfg(test)]
+mod tests;
 
 #[macro_use]
 mod maros;
 
+use crate::parser::clock::PacketClock;
 use crate::parser::encounter_state::{EncounterState, get_class_from_id};
 use crate::parser::entity_tracker::{get_current_and_max_hp, EntityTracker};
 use crate::parser::id_tracker::IdTracker;
 use crate::parser::models::{Identity, Stagger, EntityType};
 use crate::parser::party_tracker::PartyTracker;
-use crate::parser::status_tracker::{StatusEffectTargetType, StatusTracker};
+use crate::parser::recorder::CaptureRecorder;
+use crate::parser::replay::{start_replay, ReplaySpeed};
+use crate::parser::sink::EventSink;
+use crate::parser::status_tracker::{
+    get_status_effect_value, StatusEffect, StatusEffectTargetType, StatusEffectType, StatusTracker,
+};
 use anyhow::Result;
 use chrono::Utc;
 use hashbrown::HashMap;
@@ -22,47 +35,141 @@ use meter_core::packets::definitions::*;
 use meter_core::packets::opcodes::Pkt;
 use meter_core::{start_capture, start_raw_capture};
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
-use tauri::{Manager, Window, Wry};
-
-use self::models::{TripodIndex, TripodLevel, Settings};
-
-pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, settings: Option<Settings>) -> Result<()> {
-    let id_tracker = Rc::new(RefCell::new(IdTracker::new()));
-    let party_tracker = Rc::new(RefCell::new(PartyTracker::new(id_tracker.clone())));
-    let status_tracker = Rc::new(RefCell::new(StatusTracker::new(party_tracker.clone())));
-    let mut entity_tracker = EntityTracker::new(
-        status_tracker.clone(),
-        id_tracker.clone(),
-        party_tracker.clone(),
+use serde_json::json;
+
+use self::models::{RaidSignal, Settings, TripodIndex, TripodLevel, RAID_DATA};
+
+pub enum PacketSource {
+    Capture {
+        ip: String,
+        port: u16,
+        raw_socket: bool,
+    },
+    Replay {
+        files: Vec<PathBuf>,
+        speed: ReplaySpeed,
+    },
+}
+
+pub fn get_capture_source(settings: Option<Settings>) -> PacketSource {
+    let mut raw_socket = false;
+    let mut ip: String;
+    let mut port = 6040;
+
+    if let Some(settings) = settings {
+        info!("settings loaded");
+        if settings.general.auto_iface {
+            ip = meter_core::get_most_common_ip().unwrap();
+            info!("auto_iface enabled, using ip: {}", ip);
+        } else {
+            ip = settings.general.ip;
+            let interface = settings.general.if_desc;
+            info!(
+                "manual interface set, ip: {} and interface: {}",
+                ip, interface
+            );
+            let os_interfaces = meter_core::get_network_interfaces();
+            let right_name: Vec<&(String, String)> = os_interfaces
+                .iter()
+                .filter(|iface| iface.0 == interface)
+                .collect();
+            if !right_name.is_empty() {
+                let perfect_match =
+                    right_name.clone().into_iter().find(|iface| iface.1 == ip);
+                if perfect_match.is_none() {
+                    //in case of multiple interfaces with same name, try the first one
+                    ip = right_name[0].1.clone(); //get the up to date ip
+                    warn!("ip for manual interface was wrong, using ip: {}", ip);
+                }
+            } else {
+                ip = meter_core::get_most_common_ip().unwrap();
+                warn!("manually set interface not found, using default ip: {}", ip);
+            }
+            if settings.general.port > 0 {
+                port = settings.general.port;
+                info!("using port: {}", port);
+            }
+            raw_socket = settings.general.raw_socket;
+            if raw_socket {
+                info!("using raw socket");
+            } else {
+                info!("using npcap");
+            }
+        }
+    } else {
+        ip = meter_core::get_most_common_ip().unwrap();
+        info!("settings not found, auto_iface enabled, using ip: {}", ip);
+    }
+
+    PacketSource::Capture {
+        ip,
+        port,
+        raw_socket,
+    }
+}
+
+pub fn start(sink: Arc<dyn EventSink>, source: PacketSource, settings: Option<Settings>) -> Result<()> {
+    let clock = PacketClock::new();
+    let mut trackers = Trackers::new(&clock);
+    let mut state = EncounterState::new(
+        sink.clone(),
+        clock.clone(),
+        trackers.status_tracker.clone(),
+        trackers.id_tracker.clone(),
     );
-    let mut state = EncounterState::new(window.clone());
-    let rx = if raw_socket {
-        if !meter_core::check_is_admin() {
-            warn!("Not running as admin, cannot use raw socket");
-            loop {
-                window.emit("admin", "")?;
-                thread::sleep(Duration::from_millis(5000));
+    let is_replay = matches!(source, PacketSource::Replay { .. });
+
+    // closing the packet source ends the loop below, which saves the encounter in progress
+    let stop = Arc::new(AtomicBool::new(false));
+    sink.listen("stop-request", Box::new({
+        let stop = stop.clone();
+        move |_payload| {
+            info!("stopping parser");
+            stop.store(true, Ordering::Relaxed);
+        }
+    }));
+
+    let packets: Box<dyn Iterator<Item = (Pkt, Vec<u8>, i64)>> = match source {
+        PacketSource::Capture { ip, port, raw_socket: true } => {
+            if !meter_core::check_is_admin() {
+                warn!("Not running as admin, cannot use raw socket");
+                loop {
+                    sink.emit("admin", json!(""))?;
+                    thread::sleep(Duration::from_millis(5000));
+                }
+            }
+            meter_core::add_firewall()?;
+            match start_raw_capture(ip, port) {
+                Ok(rx) => Box::new(with_receive_time(rx, stop.clone()).into_iter()),
+                Err(e) => {
+                    warn!("Error starting capture: {}", e);
+                    return Ok(());
+                }
             }
         }
-        meter_core::add_firewall()?;
-        match start_raw_capture(ip, port) {
-            Ok(rx) => rx,
-            Err(e) => {
-                warn!("Error starting capture: {}", e);
-                return Ok(());
+        PacketSource::Capture { ip, port, raw_socket: false } => {
+            match start_capture(ip, port) {
+                Ok(rx) => Box::new(with_receive_time(rx, stop.clone()).into_iter()),
+                Err(e) => {
+                    warn!("Error starting capture: {}", e);
+                    return Ok(());
+                }
             }
         }
-    } else {
-        match start_capture(ip, port) {
-            Ok(rx) => rx,
-            Err(e) => {
-                warn!("Error starting capture: {}", e);
-                return Ok(());
+        PacketSource::Replay { files, speed } => {
+            match start_replay(files, speed) {
+                Ok(rx) => Box::new(forward_packets(rx, stop.clone(), |packet| packet).into_iter()),
+                Err(e) => {
+                    warn!("Error starting replay: {}", e);
+                    return Ok(());
+                }
             }
         }
     };
@@ -71,60 +178,67 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
     let duration = Duration::from_millis(100);
     let mut last_party_update = Instant::now();
     let party_duration = Duration::from_millis(1000);
-    let mut raid_end_cd: Instant = Instant::now();
 
     let reset = Arc::new(AtomicBool::new(false));
     let pause = Arc::new(AtomicBool::new(false));
     let save = Arc::new(AtomicBool::new(false));
     let boss_only_damage = Arc::new(AtomicBool::new(false));
+    let mut recorder: Option<CaptureRecorder> = None;
     if let Some(settings) = settings {
         if settings.general.boss_only_damage {
             boss_only_damage.store(true, Ordering::Relaxed);
             info!("boss only damage enabled")
         }
+        if settings.general.record_packets && !is_replay {
+            let mut path = sink.resource_dir();
+            path.push("captures");
+            match CaptureRecorder::new(path) {
+                Ok(r) => recorder = Some(r),
+                Err(e) => warn!("error starting packet recorder: {}", e),
+            }
+        }
     }
 
     let emit_details = Arc::new(AtomicBool::new(false));
 
-    let meter_window_clone = window.clone();
-    window.listen_global("reset-request", {
+    sink.listen("reset-request", Box::new({
         let reset_clone = reset.clone();
-        let meter_window_clone = meter_window_clone.clone();
-        move |_event| {
+        let sink_clone = sink.clone();
+        move |_payload| {
             reset_clone.store(true, Ordering::Relaxed);
             info!("resetting meter");
-            meter_window_clone.emit("reset-encounter", "").ok();
+            sink_clone.emit("reset-encounter", json!("")).ok();
         }
-    });
+    }));
 
-    window.listen_global("save-request", {
+    sink.listen("save-request", Box::new({
         let save_clone = save.clone();
-        let meter_window_clone = meter_window_clone.clone();
-        move |_event| {
+        let sink_clone = sink.clone();
+        move |_payload| {
             save_clone.store(true, Ordering::Relaxed);
             info!("manual saving encounter");
-            meter_window_clone.emit("save-encounter", "").ok();
+            sink_clone.emit("save-encounter", json!("")).ok();
         }
-    });
+    }));
     
-    window.listen_global("pause-request", {
+    sink.listen("pause-request", Box::new({
         let pause_clone = pause.clone();
-        let meter_window_clone = meter_window_clone.clone();
-        move |_event| {
+        let sink_clone = sink.clone();
+        move |_payload| {
             let prev = pause_clone.fetch_xor(true, Ordering::Relaxed);
             if prev {
                 info!("unpausing meter");
             } else {
                 info!("pausing meter");
             }
-            meter_window_clone.emit("pause-encounter", "").ok();
+            sink_clone.emit("pause-encounter", json!("")).ok();
         }
-    });
+    }));
 
-    window.listen_global("boss-only-damage-request", {
+    sink.listen("boss-only-damage-request", Box::new({
         let boss_only_damage = boss_only_damage.clone();
-        move |event| {
-            if let Some(bod) = event.payload() {
+        move |payload| {
+            if let Some(bod) = payload {
                 if bod == "true" {
                     boss_only_damage.store(true, Ordering::Relaxed);
                     info!("boss only damage enabled")
@@ -134,11 +248,11 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
                 }
             }
         }
-    });
+    }));
 
-    window.listen_global("emit-details-request", {
+    sink.listen("emit-details-request", Box::new({
         let emit_clone = emit_details.clone();
-        move |_event| {
+        move |_payload| {
             let prev = emit_clone.fetch_xor(true, Ordering::Relaxed);
             if prev {
                 info!("stopped sending details");
@@ -146,11 +260,23 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
                 info!("sending details");
             }
         }
-    });
+    }));
 
-    let mut party_freeze = false;
+    for (op, data, timestamp) in packets {
+        clock.set(timestamp);
+
+        if let Some(r) = recorder.as_mut() {
+            let result = if matches!(op, Pkt::InitEnv) {
+                r.rotate(timestamp).and_then(|_| r.record(timestamp, op, &data))
+            } else {
+                r.record(timestamp, op, &data)
+            };
+            if let Err(e) = result {
+                warn!("error recording packet, disabling recorder: {}", e);
+                recorder = None;
+            }
+        }
 
-    while let Ok((op, data)) = rx.recv() {
         if reset.load(Ordering::Relaxed) {
             state.soft_reset(true);
             reset.store(false, Ordering::Relaxed);
@@ -160,7 +286,7 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
         }
         if save.load(Ordering::Relaxed) {
             save.store(false, Ordering::Relaxed);
-            state.party_info = update_party(&party_tracker, &entity_tracker);
+            state.party_info = update_party(&trackers.party_tracker, &trackers.entity_tracker);
             state.save_to_db(true);
             state.saved = true;
             state.resetting = true;
@@ -173,378 +299,13 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
             state.encounter.boss_only_damage = false;
         }
 
-        match op {
-            Pkt::CounterAttackNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTCounterAttackNotify::new, "PKTCounterAttackNotify") {
-                    if let Some(entity) = entity_tracker.entities.get(&pkt.source_id) {
-                        state.on_counterattack(entity);
-                    }
-                }
-            }
-            Pkt::DeathNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTDeathNotify::new, "PKTDeathNotify") {
-                    if let Some(entity) = entity_tracker.entities.get(&pkt.target_id) {
-                        debug_print!("death", &(&entity.name, entity.entity_type, entity.id));
-                        state.on_death(entity);
-                    }
-                }
-            }
-            Pkt::IdentityGaugeChangeNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTIdentityGaugeChangeNotify::new, "PKTIdentityGaugeChangeNotify") {
-                    state.on_identity_gain(&pkt);
-                    if emit_details.load(Ordering::Relaxed) {
-                        window
-                            .emit("identity-update", Identity {
-                                gauge1: pkt.identity_gauge1,
-                                gauge2: pkt.identity_gauge2,
-                                gauge3: pkt.identity_gauge3,
-                            })
-                            .expect("failed to emit identity-update");
-                    }
-                }
-            }
-            Pkt::InitEnv => {
-                if let Some(pkt) = parse_pkt(&data, PKTInitEnv::new, "PKTInitEnv") {
-                    party_tracker.borrow_mut().reset_party_mappings();
-                    state.raid_difficulty = "".to_string();
-                    let entity = entity_tracker.init_env(pkt);
-                    debug_print!("init env", &entity);
-                    state.on_init_env(entity);
-                }
-            }
-            Pkt::InitPC => {
-                if let Some(pkt) = parse_pkt(&data, PKTInitPC::new, "PKTInitPC") {
-                    let (hp, max_hp) = get_current_and_max_hp(&pkt.stat_pair);
-                    let entity = entity_tracker.init_pc(pkt);
-                    info!("local player: {:?}, class: {:?}, ilvl: {:?}, id: {:?}", entity.name, get_class_from_id(&entity.class_id), entity.gear_level, entity.character_id);
-                    // debug_print!("init pc", &entity);
-
-                    state.on_init_pc(entity, hp, max_hp)
-                }
-            }
-            Pkt::MigrationExecute => {
-                if let Some(pkt) = parse_pkt(&data, PKTMigrationExecute::new, "PKTMigrationExecute") {
-                    entity_tracker.migration_execute(pkt);
-                }
-            }
-            Pkt::NewPC => {
-                if let Some(pkt) = parse_pkt(&data, PKTNewPC::new, "PKTNewPC") {
-                    let (hp, max_hp) = get_current_and_max_hp(&pkt.pc_struct.stat_pair);
-                    let entity = entity_tracker.new_pc(pkt);
-                    debug_print!("new pc", &(&entity.name, get_class_from_id(&entity.class_id), entity.id, entity.character_id, entity.gear_level));
-                    state.on_new_pc(entity, hp, max_hp);
-                }
-            }
-            Pkt::NewNpc => {
-                if let Some(pkt) = parse_pkt(&data, PKTNewNpc::new, "PKTNewNpc") {
-                    let (hp, max_hp) = get_current_and_max_hp(&pkt.npc_struct.stat_pair);
-                    let entity = entity_tracker.new_npc(pkt, max_hp);
-                    debug_print!("new npc", &(&entity.name, entity.entity_type, entity.id, entity.npc_id, hp, max_hp));
-                    state.on_new_npc(entity, hp, max_hp);
-                }
-            }
-            Pkt::NewNpcSummon => {
-                if let Some(pkt) = parse_pkt(&data, PKTNewNpcSummon::new, "PKTNewNpcSummon") {
-                    let (hp, max_hp) = get_current_and_max_hp(&pkt.npc_data.stat_pair);
-                    let entity = entity_tracker.new_npc_summon(pkt, max_hp);
-                    debug_print!("new summon", &(&entity.name, entity.entity_type, entity.id, entity.npc_id, entity.owner_id, hp, max_hp));
-                    state.on_new_npc(entity, hp, max_hp);
-                }
-            }
-            Pkt::NewProjectile => {
-                if let Some(pkt) = parse_pkt(&data, PKTNewProjectile::new, "PKTNewProjectile") {
-                    entity_tracker.new_projectile(pkt);
-                }
-            }
-            Pkt::NewTrap => {
-                if let Some(pkt) = parse_pkt(&data, PKTNewTrap::new, "PKTNewTrap") {
-                    entity_tracker.new_trap(pkt);
-                }
-            }
-            Pkt::ParalyzationStateNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTParalyzationStateNotify::new, "PKTParalyzationStateNotify") {
-                    state.on_stagger_change(&pkt);
-                    if emit_details.load(Ordering::Relaxed) {
-                        window
-                            .emit("stagger-update", Stagger {
-                                current: pkt.paralyzation_point,
-                                max: pkt.paralyzation_max_point,
-                            })
-                            .expect("failed to emit stagger-update");
-                    }
-                }
-            }
-            Pkt::PartyInfo => {
-                if let Some(pkt) = parse_pkt(&data, PKTPartyInfo::new, "PKTPartyInfo") {
-                    entity_tracker.party_info(pkt);
-                    let local_player_id = entity_tracker.local_player_id;
-                    if let Some(entity) = entity_tracker.entities.get(&local_player_id) {
-                        state.update_local_player(entity);
-                    }
-                }
-            }
-            Pkt::PartyLeaveResult => {
-                if let Some(pkt) = parse_pkt(&data, PKTPartyLeaveResult::new, "PKTPartyLeaveResult") {
-                    party_tracker
-                        .borrow_mut()
-                        .remove(pkt.party_instance_id, pkt.name);
-                }
-            }
-            Pkt::PartyStatusEffectAddNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTPartyStatusEffectAddNotify::new, "PKTPartyStatusEffectAddNotify") {
-                    entity_tracker.party_status_effect_add(pkt);
-                }
-            }
-            Pkt::PartyStatusEffectRemoveNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTPartyStatusEffectRemoveNotify::new, "PKTPartyStatusEffectRemoveNotify") {
-                    entity_tracker.party_status_effect_remove(pkt);
-                }
-            }
-            Pkt::PartyStatusEffectResultNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTPartyStatusEffectResultNotify::new, "PKTPartyStatusEffectResultNotify") {
-                    party_tracker.borrow_mut().add(
-                        pkt.raid_instance_id,
-                        pkt.party_instance_id,
-                        pkt.character_id,
-                        0,
-                        None,
-                    );
-                }
-            }
-            Pkt::RaidBegin => {
-                if let Some(pkt) = parse_pkt(&data, PKTRaidBegin::new, "PKTRaidBegin") {
-                    debug_print!("raid begin", pkt.raid_id);
-                    match pkt.raid_id {
-                        308226 | 308227 => {
-                            state.raid_difficulty = "Trial".to_string();
-                        }
-                        308428 | 308429 | 308420 | 308410 | 308411 | 308414 | 308422 | 308424
-                        | 308421 | 308412 | 308423 | 308426 | 308416 | 308419 | 308415 | 308437
-                        | 308417 | 308418 | 308425 | 308430 => {
-                            state.raid_difficulty = "Challenge".to_string();
-                        }
-                        _ => {
-                            state.raid_difficulty = "".to_string();
-                        }
-                    }
-                }
-            }
-            Pkt::RaidBossKillNotify => {
-                state.on_phase_transition(1);
-                state.raid_clear = true;
-                debug_print!("phase", &1);
-            }
-            Pkt::RaidResult => {
-                party_freeze = true;
-                state.party_info = update_party(&party_tracker, &entity_tracker);
-                state.on_phase_transition(0);
-                raid_end_cd = Instant::now();
-                debug_print!("phase", &0);
-            }
-            Pkt::RemoveObject => {
-                if let Some(pkt) = parse_pkt(&data, PKTRemoveObject::new, "PKTRemoveObject") {
-                    for upo in pkt.unpublished_objects {
-                        entity_tracker.entities.remove(&upo.object_id);
-                        status_tracker
-                            .borrow_mut()
-                            .remove_local_object(upo.object_id);
-                    }
-                }
-            }
-            Pkt::SkillCastNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTSkillCastNotify::new, "PKTSkillCastNotify") {
-                    let mut entity = entity_tracker.get_source_entity(pkt.caster);
-                    if entity.class_id == 202 {
-                        entity = entity_tracker.guess_is_player(entity, pkt.skill_id);
-                        state.on_skill_start(entity, pkt.skill_id as i32, None, None, Utc::now().timestamp_millis());
-                    }
-                }
-            }
-            Pkt::SkillStartNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTSkillStartNotify::new, "PKTSkillStartNotify") {
-                    let mut entity = entity_tracker.get_source_entity(pkt.source_id);
-                    entity = entity_tracker.guess_is_player(entity, pkt.skill_id);
-                    let tripod_index = pkt.skill_option_data.tripod_index.map(|tripod_index| TripodIndex {
-                            first: tripod_index.first,
-                            second: tripod_index.second,
-                            third: tripod_index.third,
-                        });
-                    let tripod_level = pkt.skill_option_data.tripod_level.map(|tripod_level| TripodLevel {
-                            first: tripod_level.first,
-                            second: tripod_level.second,
-                            third: tripod_level.third,
-                        });
-                    state.on_skill_start(entity, pkt.skill_id as i32, tripod_index, tripod_level, Utc::now().timestamp_millis());
-                }
-            }
-            Pkt::SkillStageNotify => {
-                // let pkt = PKTSkillStageNotify::new(&data);
-            }
-            Pkt::SkillDamageAbnormalMoveNotify => {
-                if Instant::now() - raid_end_cd < Duration::from_secs(5) {
-                    debug_print!("ignoring damage", "");
-                    continue;
-                }
-                if let Some(pkt) = parse_pkt(&data, PKTSkillDamageAbnormalMoveNotify::new, "PKTSkillDamageAbnormalMoveNotify") {
-                    let owner = entity_tracker.get_source_entity(pkt.source_id);
-                    let local_character_id = id_tracker
-                        .borrow()
-                        .get_local_character_id(entity_tracker.local_player_id);
-                    for event in pkt.skill_damage_abnormal_move_events.iter() {
-                        let target_entity =
-                            entity_tracker.get_or_create_entity(event.skill_damage_event.target_id);
-                        let source_entity = entity_tracker.get_or_create_entity(pkt.source_id);
-                        let (se_on_source, se_on_target) = status_tracker
-                            .borrow_mut()
-                            .get_status_effects(&owner, &target_entity, local_character_id);
-                        state.on_damage(
-                            &owner,
-                            &source_entity,
-                            &target_entity,
-                            event.skill_damage_event.damage,
-                            pkt.skill_id as i32,
-                            pkt.skill_effect_id as i32,
-                            event.skill_damage_event.modifier as i32,
-                            event.skill_damage_event.cur_hp,
-                            event.skill_damage_event.max_hp,
-                            se_on_source,
-                            se_on_target,
-                        );
-                    }
-                }
-            }
-            Pkt::SkillDamageNotify => {
-                // use this to make sure damage packets are not tracked after a raid just wiped
-                if Instant::now() - raid_end_cd < Duration::from_secs(5) {
-                    debug_print!("ignoring damage", "");
-                    continue;
-                }
-                if let Some(pkt) = parse_pkt(&data, PKTSkillDamageNotify::new, "PktSkillDamageNotify") {
-                    let owner = entity_tracker.get_source_entity(pkt.source_id);
-                    let local_character_id = id_tracker
-                        .borrow()
-                        .get_local_character_id(entity_tracker.local_player_id);
-                    for event in pkt.skill_damage_events.iter() {
-                        let target_entity = entity_tracker.get_or_create_entity(event.target_id);
-                        // source_entity is to determine battle item
-                        let source_entity = entity_tracker.get_or_create_entity(pkt.source_id);
-                        let (se_on_source, se_on_target) = status_tracker
-                            .borrow_mut()
-                            .get_status_effects(&owner, &target_entity, local_character_id);
-                        state.on_damage(
-                            &owner,
-                            &source_entity,
-                            &target_entity,
-                            event.damage,
-                            pkt.skill_id as i32,
-                            pkt.skill_effect_id as i32,
-                            event.modifier as i32,
-                            event.cur_hp,
-                            event.max_hp,
-                            se_on_source,
-                            se_on_target,
-                        );
-                    }
-                }
-            }
-            Pkt::StatusEffectAddNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTStatusEffectAddNotify::new, "PKTStatusEffectAddNotify") {
-                    entity_tracker
-                        .build_and_register_status_effect(&pkt.status_effect_data, pkt.object_id)
-                }
-            }
-            Pkt::StatusEffectDurationNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTStatusEffectDurationNotify::new, "PKTStatusEffectDurationNotify") {
-                    status_tracker.borrow_mut().update_status_duration(
-                        pkt.effect_instance_id,
-                        pkt.target_id,
-                        pkt.expiration_tick,
-                        StatusEffectTargetType::Local,
-                    );
-                }
-            }
-            Pkt::StatusEffectRemoveNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTStatusEffectRemoveNotify::new, "PKTStatusEffectRemoveNotify") {
-                    status_tracker.borrow_mut().remove_status_effects(
-                        pkt.object_id,
-                        pkt.status_effect_ids,
-                        StatusEffectTargetType::Local,
-                    );
-                }
-            }
-            Pkt::TriggerBossBattleStatus => {
-                // need to hard code clown because it spawns before the trigger is sent???
-                if state.encounter.current_boss_name.is_empty() || state.encounter.fight_start == 0
-                    || state.encounter.current_boss_name == "Saydon" {
-                    state.on_phase_transition(3);
-                    debug_print!("resetting encounter", "");
-                }
-            }
-            Pkt::TriggerStartNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTTriggerStartNotify::new, "PKTTriggerStartNotify") {
-                    match pkt.trigger_signal_type {
-                        57 | 59 | 61 | 63 | 74 | 76 => {
-                            party_freeze = true;
-                            state.party_info = update_party(&party_tracker, &entity_tracker);
-                            state.raid_clear = true;
-                            state.on_phase_transition(2);
-                            raid_end_cd = Instant::now();
-                        }
-                        58 | 60 | 62 | 64 | 75 | 77 => {
-                            party_freeze = true;
-                            state.party_info = update_party(&party_tracker, &entity_tracker);
-                            state.raid_clear = false;
-                            state.on_phase_transition(4);
-                            raid_end_cd = Instant::now();
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            Pkt::ZoneMemberLoadStatusNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTZoneMemberLoadStatusNotify::new, "PKTZoneMemberLoadStatusNotify") {
-                    if !state.raid_difficulty.is_empty() {
-                        continue;
-                    }
-
-                    match pkt.zone_level {
-                        0 => {
-                            state.raid_difficulty = "Normal".to_string();
-                        }
-                        1 => {
-                            state.raid_difficulty = "Hard".to_string();
-                        }
-                        2 => {
-                            state.raid_difficulty = "Inferno".to_string();
-                        }
-                        3 => {
-                            state.raid_difficulty = "Challenge".to_string();
-                        }
-                        4 => {
-                            state.raid_difficulty = "Special".to_string();
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            Pkt::ZoneObjectUnpublishNotify => {
-                if let Some(pkt) = parse_pkt(&data, PKTZoneObjectUnpublishNotify::new, "PKTZoneObjectUnpublishNotify") {
-                    status_tracker
-                        .borrow_mut()
-                        .remove_local_object(pkt.object_id);
-                }
-            }
-            Pkt::StatusEffectSyncDataNotify => {
-                // let pkt = PKTStatusEffectSyncDataNotify::new(&data);
-                // shields
-            }
-            Pkt::TroopMemberUpdateMinNotify => {
-                // let pkt = PKTTroopMemberUpdateMinNotify::new(&data);
-                // shields
-            }
-            _ => {
-                continue;
-            }
+        let packet = match parse_packet(op, &data) {
+            Some(packet) => packet,
+            None => continue,
+        };
+        trackers.emit_details = emit_details.load(Ordering::Relaxed);
+        if !handle_packet(&mut state, &mut trackers, packet, timestamp) {
+            continue;
         }
 
         if last_update.elapsed() >= duration || state.resetting || state.boss_dead_update {
@@ -554,11 +315,11 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
                 state.boss_dead_update = false;
             }
             let mut clone = state.encounter.clone();
-            let window = window.clone();
+            let sink = sink.clone();
 
-            let party_info: Option<HashMap<i32, Vec<String>>>  = if last_party_update.elapsed() >= party_duration && !party_freeze {
+            let party_info: Option<HashMap<i32, Vec<String>>>  = if last_party_update.elapsed() >= party_duration && !trackers.party_freeze {
                 last_party_update = Instant::now();
-                let party = update_party(&party_tracker, &entity_tracker);
+                let party = update_party(&trackers.party_tracker, &trackers.entity_tracker);
                 if party.len() > 1 {
                     Some(party
                         .into_iter()
@@ -591,13 +352,13 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
                 });
 
                 if !clone.entities.is_empty() {
-                    window
-                        .emit("encounter-update", Some(clone))
+                    sink
+                        .emit("encounter-update", json!(Some(clone)))
                         .expect("failed to emit encounter-update");
 
                     if party_info.is_some() {
-                        window
-                            .emit("party-update", party_info)
+                        sink
+                            .emit("party-update", json!(party_info))
                             .expect("failed to emit party-update");
                     }
                 }
@@ -606,17 +367,537 @@ pub fn start(window: Window<Wry>, ip: String, port: u16, raw_socket: bool, setti
             last_update = Instant::now();
         }
 
-        if state.resetting {
-            state.soft_reset(true);
-            state.resetting = false;
-            state.saved = false;
-            party_freeze = false;
-        }
+        finish_reset(&mut state, &mut trackers);
     }
 
+    // the replay ran out or the capture stopped
+    state.finish_saves();
+
     Ok(())
 }
 
+// the trackers packets are dispatched to, shared by the capture loop and the golden tests
+struct Trackers {
+    id_tracker: Rc<RefCell<IdTracker>>,
+    party_tracker: Rc<RefCell<PartyTracker>>,
+    status_tracker: Rc<RefCell<StatusTracker>>,
+    entity_tracker: EntityTracker,
+    raid_end_cd: i64,
+    party_freeze: bool,
+    emit_details: bool,
+}
+
+impl Trackers {
+    fn new(clock: &PacketClock) -> Self {
+        let id_tracker = Rc::new(RefCell::new(IdTracker::new()));
+        let party_tracker = Rc::new(RefCell::new(PartyTracker::new(id_tracker.clone())));
+        let status_tracker = Rc::new(RefCell::new(StatusTracker::new(clock.clone(), party_tracker.clone())));
+        let entity_tracker = EntityTracker::new(
+            clock.clone(),
+            status_tracker.clone(),
+            id_tracker.clone(),
+            party_tracker.clone(),
+        );
+        Self {
+            id_tracker,
+            party_tracker,
+            status_tracker,
+            entity_tracker,
+            raid_end_cd: 0,
+            party_freeze: false,
+            emit_details: false,
+        }
+    }
+}
+
+// the packets the parser handles, decoded from their opcode
+#[allow(clippy::large_enum_variant)]
+enum Packet {
+    CounterAttack(PKTCounterAttackNotify),
+    Death(PKTDeathNotify),
+    IdentityGaugeChange(PKTIdentityGaugeChangeNotify),
+    InitEnv(PKTInitEnv),
+    InitPC(PKTInitPC),
+    MigrationExecute(PKTMigrationExecute),
+    NewPC(PKTNewPC),
+    NewNpc(PKTNewNpc),
+    NewNpcSummon(PKTNewNpcSummon),
+    NewProjectile(PKTNewProjectile),
+    NewTrap(PKTNewTrap),
+    ParalyzationState(PKTParalyzationStateNotify),
+    PartyInfo(PKTPartyInfo),
+    PartyLeaveResult(PKTPartyLeaveResult),
+    PartyStatusEffectAdd(PKTPartyStatusEffectAddNotify),
+    PartyStatusEffectRemove(PKTPartyStatusEffectRemoveNotify),
+    PartyStatusEffectResult(PKTPartyStatusEffectResultNotify),
+    RaidBegin(PKTRaidBegin),
+    RaidBossKill,
+    RaidResult,
+    RemoveObject(PKTRemoveObject),
+    SkillCast(PKTSkillCastNotify),
+    SkillStart(PKTSkillStartNotify),
+    SkillStage,
+    SkillDamageAbnormalMove(PKTSkillDamageAbnormalMoveNotify),
+    SkillDamage(PKTSkillDamageNotify),
+    StatusEffectAdd(PKTStatusEffectAddNotify),
+    StatusEffectDuration(PKTStatusEffectDurationNotify),
+    StatusEffectRemove(PKTStatusEffectRemoveNotify),
+    TriggerBossBattleStatus,
+    TriggerStart(PKTTriggerStartNotify),
+    ZoneMemberLoadStatus(PKTZoneMemberLoadStatusNotify),
+    ZoneObjectUnpublish(PKTZoneObjectUnpublishNotify),
+    StatusEffectSync(PKTStatusEffectSyncDataNotify),
+    TroopMemberUpdateMin(PKTTroopMemberUpdateMinNotify),
+}
+
+// None for opcodes the parser ignores and packets that fail to parse
+fn parse_packet(op: Pkt, data: &[u8]) -> Option<Packet> {
+    match op {
+        Pkt::CounterAttackNotify => parse_pkt(data, PKTCounterAttackNotify::new, "PKTCounterAttackNotify").map(Packet::CounterAttack),
+        Pkt::DeathNotify => parse_pkt(data, PKTDeathNotify::new, "PKTDeathNotify").map(Packet::Death),
+        Pkt::IdentityGaugeChangeNotify => parse_pkt(data, PKTIdentityGaugeChangeNotify::new, "PKTIdentityGaugeChangeNotify").map(Packet::IdentityGaugeChange),
+        Pkt::InitEnv => parse_pkt(data, PKTInitEnv::new, "PKTInitEnv").map(Packet::InitEnv),
+        Pkt::InitPC => parse_pkt(data, PKTInitPC::new, "PKTInitPC").map(Packet::InitPC),
+        Pkt::MigrationExecute => parse_pkt(data, PKTMigrationExecute::new, "PKTMigrationExecute").map(Packet::MigrationExecute),
+        Pkt::NewPC => parse_pkt(data, PKTNewPC::new, "PKTNewPC").map(Packet::NewPC),
+        Pkt::NewNpc => parse_pkt(data, PKTNewNpc::new, "PKTNewNpc").map(Packet::NewNpc),
+        Pkt::NewNpcSummon => parse_pkt(data, PKTNewNpcSummon::new, "PKTNewNpcSummon").map(Packet::NewNpcSummon),
+        Pkt::NewProjectile => parse_pkt(data, PKTNewProjectile::new, "PKTNewProjectile").map(Packet::NewProjectile),
+        Pkt::NewTrap => parse_pkt(data, PKTNewTrap::new, "PKTNewTrap").map(Packet::NewTrap),
+        Pkt::ParalyzationStateNotify => parse_pkt(data, PKTParalyzationStateNotify::new, "PKTParalyzationStateNotify").map(Packet::ParalyzationState),
+        Pkt::PartyInfo => parse_pkt(data, PKTPartyInfo::new, "PKTPartyInfo").map(Packet::PartyInfo),
+        Pkt::PartyLeaveResult => parse_pkt(data, PKTPartyLeaveResult::new, "PKTPartyLeaveResult").map(Packet::PartyLeaveResult),
+        Pkt::PartyStatusEffectAddNotify => parse_pkt(data, PKTPartyStatusEffectAddNotify::new, "PKTPartyStatusEffectAddNotify").map(Packet::PartyStatusEffectAdd),
+        Pkt::PartyStatusEffectRemoveNotify => parse_pkt(data, PKTPartyStatusEffectRemoveNotify::new, "PKTPartyStatusEffectRemoveNotify").map(Packet::PartyStatusEffectRemove),
+        Pkt::PartyStatusEffectResultNotify => parse_pkt(data, PKTPartyStatusEffectResultNotify::new, "PKTPartyStatusEffectResultNotify").map(Packet::PartyStatusEffectResult),
+        Pkt::RaidBegin => parse_pkt(data, PKTRaidBegin::new, "PKTRaidBegin").map(Packet::RaidBegin),
+        Pkt::RaidBossKillNotify => Some(Packet::RaidBossKill),
+        Pkt::RaidResult => Some(Packet::RaidResult),
+        Pkt::RemoveObject => parse_pkt(data, PKTRemoveObject::new, "PKTRemoveObject").map(Packet::RemoveObject),
+        Pkt::SkillCastNotify => parse_pkt(data, PKTSkillCastNotify::new, "PKTSkillCastNotify").map(Packet::SkillCast),
+        Pkt::SkillStartNotify => parse_pkt(data, PKTSkillStartNotify::new, "PKTSkillStartNotify").map(Packet::SkillStart),
+        Pkt::SkillStageNotify => Some(Packet::SkillStage),
+        Pkt::SkillDamageAbnormalMoveNotify => parse_pkt(data, PKTSkillDamageAbnormalMoveNotify::new, "PKTSkillDamageAbnormalMoveNotify").map(Packet::SkillDamageAbnormalMove),
+        Pkt::SkillDamageNotify => parse_pkt(data, PKTSkillDamageNotify::new, "PktSkillDamageNotify").map(Packet::SkillDamage),
+        Pkt::StatusEffectAddNotify => parse_pkt(data, PKTStatusEffectAddNotify::new, "PKTStatusEffectAddNotify").map(Packet::StatusEffectAdd),
+        Pkt::StatusEffectDurationNotify => parse_pkt(data, PKTStatusEffectDurationNotify::new, "PKTStatusEffectDurationNotify").map(Packet::StatusEffectDuration),
+        Pkt::StatusEffectRemoveNotify => parse_pkt(data, PKTStatusEffectRemoveNotify::new, "PKTStatusEffectRemoveNotify").map(Packet::StatusEffectRemove),
+        Pkt::TriggerBossBattleStatus => Some(Packet::TriggerBossBattleStatus),
+        Pkt::TriggerStartNotify => parse_pkt(data, PKTTriggerStartNotify::new, "PKTTriggerStartNotify").map(Packet::TriggerStart),
+        Pkt::ZoneMemberLoadStatusNotify => parse_pkt(data, PKTZoneMemberLoadStatusNotify::new, "PKTZoneMemberLoadStatusNotify").map(Packet::ZoneMemberLoadStatus),
+        Pkt::ZoneObjectUnpublishNotify => parse_pkt(data, PKTZoneObjectUnpublishNotify::new, "PKTZoneObjectUnpublishNotify").map(Packet::ZoneObjectUnpublish),
+        Pkt::StatusEffectSyncDataNotify => parse_pkt(data, PKTStatusEffectSyncDataNotify::new, "PKTStatusEffectSyncDataNotify").map(Packet::StatusEffectSync),
+        Pkt::TroopMemberUpdateMinNotify => parse_pkt(data, PKTTroopMemberUpdateMinNotify::new, "PKTTroopMemberUpdateMinNotify").map(Packet::TroopMemberUpdateMin),
+        _ => None,
+    }
+}
+
+// applies a packet to the trackers and the encounter, false when it was ignored and nothing changed
+fn handle_packet(state: &mut EncounterState, trackers: &mut Trackers, packet: Packet, timestamp: i64) -> bool {
+    let Trackers {
+        id_tracker,
+        party_tracker,
+        status_tracker,
+        entity_tracker,
+        raid_end_cd,
+        party_freeze,
+        emit_details,
+    } = trackers;
+
+    match packet {
+        Packet::CounterAttack(pkt) => {
+            if let Some(entity) = entity_tracker.entities.get(&pkt.source_id) {
+                state.on_counterattack(entity);
+            }
+        }
+        Packet::Death(pkt) => {
+            if let Some(entity) = entity_tracker.entities.get(&pkt.target_id) {
+                debug_print!("death", &(&entity.name, entity.entity_type, entity.id));
+                state.on_death(entity);
+            }
+        }
+        Packet::IdentityGaugeChange(pkt) => {
+            state.on_identity_gain(&pkt);
+            if *emit_details {
+                state.sink
+                    .emit("identity-update", json!(Identity {
+                        gauge1: pkt.identity_gauge1,
+                        gauge2: pkt.identity_gauge2,
+                        gauge3: pkt.identity_gauge3,
+                    }))
+                    .expect("failed to emit identity-update");
+            }
+        }
+        Packet::InitEnv(pkt) => {
+            party_tracker.borrow_mut().reset_party_mappings();
+            state.raid_difficulty = "".to_string();
+            let entity = entity_tracker.init_env(pkt);
+            debug_print!("init env", &entity);
+            state.on_init_env(entity);
+        }
+        Packet::InitPC(pkt) => {
+            let (hp, max_hp) = get_current_and_max_hp(&pkt.stat_pair);
+            let entity = entity_tracker.init_pc(pkt);
+            info!("local player: {:?}, class: {:?}, ilvl: {:?}, id: {:?}", entity.name, get_class_from_id(&entity.class_id), entity.gear_level, entity.character_id);
+            // debug_print!("init pc", &entity);
+
+            state.on_init_pc(entity, hp, max_hp)
+        }
+        Packet::MigrationExecute(pkt) => {
+            entity_tracker.migration_execute(pkt);
+        }
+        Packet::NewPC(pkt) => {
+            let (hp, max_hp) = get_current_and_max_hp(&pkt.pc_struct.stat_pair);
+            let entity = entity_tracker.new_pc(pkt);
+            debug_print!("new pc", &(&entity.name, get_class_from_id(&entity.class_id), entity.id, entity.character_id, entity.gear_level));
+            state.on_new_pc(entity, hp, max_hp);
+        }
+        Packet::NewNpc(pkt) => {
+            let (hp, max_hp) = get_current_and_max_hp(&pkt.npc_struct.stat_pair);
+            let entity = entity_tracker.new_npc(pkt, max_hp);
+            debug_print!("new npc", &(&entity.name, entity.entity_type, entity.id, entity.npc_id, hp, max_hp));
+            state.on_new_npc(entity, hp, max_hp);
+        }
+        Packet::NewNpcSummon(pkt) => {
+            let (hp, max_hp) = get_current_and_max_hp(&pkt.npc_data.stat_pair);
+            let entity = entity_tracker.new_npc_summon(pkt, max_hp);
+            debug_print!("new summon", &(&entity.name, entity.entity_type, entity.id, entity.npc_id, entity.owner_id, hp, max_hp));
+            state.on_new_npc(entity, hp, max_hp);
+        }
+        Packet::NewProjectile(pkt) => {
+            entity_tracker.new_projectile(pkt);
+        }
+        Packet::NewTrap(pkt) => {
+            entity_tracker.new_trap(pkt);
+        }
+        Packet::ParalyzationState(pkt) => {
+            state.on_stagger_change(&pkt);
+            if *emit_details {
+                state.sink
+                    .emit("stagger-update", json!(Stagger {
+                        current: pkt.paralyzation_point,
+                        max: pkt.paralyzation_max_point,
+                    }))
+                    .expect("failed to emit stagger-update");
+            }
+        }
+        Packet::PartyInfo(pkt) => {
+            entity_tracker.party_info(pkt);
+            let local_player_id = entity_tracker.local_player_id;
+            if let Some(entity) = entity_tracker.entities.get(&local_player_id) {
+                state.update_local_player(entity);
+            }
+        }
+        Packet::PartyLeaveResult(pkt) => {
+            party_tracker
+                .borrow_mut()
+                .remove(pkt.party_instance_id, pkt.name);
+        }
+        Packet::PartyStatusEffectAdd(pkt) => {
+            let status_effects = entity_tracker.party_status_effect_add(pkt);
+            on_shields_applied(entity_tracker, state, &status_effects);
+        }
+        Packet::PartyStatusEffectRemove(pkt) => {
+            entity_tracker.party_status_effect_remove(pkt);
+        }
+        Packet::PartyStatusEffectResult(pkt) => {
+            party_tracker.borrow_mut().add(
+                pkt.raid_instance_id,
+                pkt.party_instance_id,
+                pkt.character_id,
+                0,
+                None,
+            );
+        }
+        Packet::RaidBegin(pkt) => {
+            debug_print!("raid begin", pkt.raid_id);
+            state.raid_difficulty = RAID_DATA
+                .load()
+                .raid_ids
+                .get(&pkt.raid_id)
+                .map(|raid| raid.difficulty.clone())
+                .unwrap_or_default();
+        }
+        Packet::RaidBossKill => {
+            state.on_phase_transition(1);
+            state.raid_clear = true;
+            debug_print!("phase", &1);
+        }
+        Packet::RaidResult => {
+            *party_freeze = true;
+            state.party_info = update_party(party_tracker, entity_tracker);
+            state.on_phase_transition(0);
+            *raid_end_cd = timestamp;
+            debug_print!("phase", &0);
+        }
+        Packet::RemoveObject(pkt) => {
+            for upo in pkt.unpublished_objects {
+                entity_tracker.entities.remove(&upo.object_id);
+                status_tracker
+                    .borrow_mut()
+                    .remove_local_object(upo.object_id);
+            }
+        }
+        Packet::SkillCast(pkt) => {
+            let mut entity = entity_tracker.get_source_entity(pkt.caster);
+            if entity.class_id == 202 {
+                entity = entity_tracker.guess_is_player(entity, pkt.skill_id);
+                state.on_skill_start(entity, pkt.skill_id as i32, None, None, timestamp);
+            }
+        }
+        Packet::SkillStart(pkt) => {
+            let mut entity = entity_tracker.get_source_entity(pkt.source_id);
+            entity = entity_tracker.guess_is_player(entity, pkt.skill_id);
+            let tripod_index = pkt.skill_option_data.tripod_index.map(|tripod_index| TripodIndex {
+                    first: tripod_index.first,
+                    second: tripod_index.second,
+                    third: tripod_index.third,
+                });
+            let tripod_level = pkt.skill_option_data.tripod_level.map(|tripod_level| TripodLevel {
+                    first: tripod_level.first,
+                    second: tripod_level.second,
+                    third: tripod_level.third,
+                });
+            state.on_skill_start(entity, pkt.skill_id as i32, tripod_index, tripod_level, timestamp);
+        }
+        Packet::SkillStage => {
+            // let pkt = PKTSkillStageNotify::new(&data);
+        }
+        Packet::SkillDamageAbnormalMove(pkt) => {
+            if timestamp - *raid_end_cd < 5000 {
+                debug_print!("ignoring damage", "");
+                return false;
+            }
+            let owner = entity_tracker.get_source_entity(pkt.source_id);
+            let local_character_id = id_tracker
+                .borrow()
+                .get_local_character_id(entity_tracker.local_player_id);
+            for event in pkt.skill_damage_abnormal_move_events.iter() {
+                let target_entity =
+                    entity_tracker.get_or_create_entity(event.skill_damage_event.target_id);
+                let source_entity = entity_tracker.get_or_create_entity(pkt.source_id);
+                let (se_on_source, se_on_target) = status_tracker
+                    .borrow_mut()
+                    .get_status_effects(&owner, &target_entity, local_character_id);
+                state.on_damage(
+                    &owner,
+                    &source_entity,
+                    &target_entity,
+                    event.skill_damage_event.damage,
+                    pkt.skill_id as i32,
+                    pkt.skill_effect_id as i32,
+                    event.skill_damage_event.modifier as i32,
+                    event.skill_damage_event.cur_hp,
+                    event.skill_damage_event.max_hp,
+                    se_on_source,
+                    se_on_target,
+                );
+            }
+        }
+        Packet::SkillDamage(pkt) => {
+            // use this to make sure damage packets are not tracked after a raid just wiped
+            if timestamp - *raid_end_cd < 5000 {
+                debug_print!("ignoring damage", "");
+                return false;
+            }
+            let owner = entity_tracker.get_source_entity(pkt.source_id);
+            let local_character_id = id_tracker
+                .borrow()
+                .get_local_character_id(entity_tracker.local_player_id);
+            for event in pkt.skill_damage_events.iter() {
+                let target_entity = entity_tracker.get_or_create_entity(event.target_id);
+                // source_entity is to determine battle item
+                let source_entity = entity_tracker.get_or_create_entity(pkt.source_id);
+                let (se_on_source, se_on_target) = status_tracker
+                    .borrow_mut()
+                    .get_status_effects(&owner, &target_entity, local_character_id);
+                state.on_damage(
+                    &owner,
+                    &source_entity,
+                    &target_entity,
+                    event.damage,
+                    pkt.skill_id as i32,
+                    pkt.skill_effect_id as i32,
+                    event.modifier as i32,
+                    event.cur_hp,
+                    event.max_hp,
+                    se_on_source,
+                    se_on_target,
+                );
+            }
+        }
+        Packet::StatusEffectAdd(pkt) => {
+            let status_effect = entity_tracker
+                .build_and_register_status_effect(&pkt.status_effect_data, pkt.object_id);
+            on_shields_applied(entity_tracker, state, &[status_effect]);
+        }
+        Packet::StatusEffectDuration(pkt) => {
+            status_tracker.borrow_mut().update_status_duration(
+                pkt.effect_instance_id,
+                pkt.target_id,
+                pkt.expiration_tick,
+                StatusEffectTargetType::Local,
+            );
+        }
+        Packet::StatusEffectRemove(pkt) => {
+            status_tracker.borrow_mut().remove_status_effects(
+                pkt.object_id,
+                pkt.status_effect_ids,
+                StatusEffectTargetType::Local,
+            );
+        }
+        Packet::TriggerBossBattleStatus => {
+            // some bosses (clown) spawn before the trigger is sent, see bossQuirks in Raid.json
+            let always_reset = RAID_DATA
+                .load()
+                .boss_quirks
+                .get(&state.encounter.current_boss_name)
+                .is_some_and(|quirks| quirks.reset_on_battle_status);
+            if state.encounter.current_boss_name.is_empty() || state.encounter.fight_start == 0
+                || always_reset {
+                state.on_phase_transition(3);
+                debug_print!("resetting encounter", "");
+            }
+        }
+        Packet::TriggerStart(pkt) => {
+            if let Some(cleared) = raid_end_signal(pkt.trigger_signal_type) {
+                *party_freeze = true;
+                state.party_info = update_party(party_tracker, entity_tracker);
+                state.raid_clear = cleared;
+                state.on_phase_transition(if cleared { 2 } else { 4 });
+                *raid_end_cd = timestamp;
+            }
+        }
+        Packet::ZoneMemberLoadStatus(pkt) => {
+            if !state.raid_difficulty.is_empty() {
+                return false;
+            }
+
+            if let Some(difficulty) = RAID_DATA.load().zone_levels.get(&pkt.zone_level) {
+                state.raid_difficulty = difficulty.clone();
+            }
+        }
+        Packet::ZoneObjectUnpublish(pkt) => {
+            status_tracker
+                .borrow_mut()
+                .remove_local_object(pkt.object_id);
+        }
+        Packet::StatusEffectSync(pkt) => {
+            let local_character_id = id_tracker
+                .borrow()
+                .get_local_character_id(entity_tracker.local_player_id);
+            let synced = status_tracker.borrow_mut().sync_status_effect(
+                pkt.effect_instance_id,
+                pkt.character_id,
+                pkt.object_id,
+                pkt.value,
+                local_character_id,
+            );
+            on_shield_synced(entity_tracker, state, synced);
+        }
+        Packet::TroopMemberUpdateMin(pkt) => {
+            let local_character_id = id_tracker
+                .borrow()
+                .get_local_character_id(entity_tracker.local_player_id);
+            let object_id = id_tracker
+                .borrow()
+                .get_entity_id(pkt.character_id)
+                .unwrap_or_default();
+            for sed in pkt.status_effect_datas.iter() {
+                let synced = status_tracker.borrow_mut().sync_status_effect(
+                    sed.effect_instance_id,
+                    pkt.character_id,
+                    object_id,
+                    get_status_effect_value(&sed.value),
+                    local_character_id,
+                );
+                on_shield_synced(entity_tracker, state, synced);
+            }
+        }
+    }
+
+    true
+}
+
+// starts the next encounter once the current one has been saved or reset
+fn finish_reset(state: &mut EncounterState, trackers: &mut Trackers) {
+    if state.resetting {
+        state.soft_reset(true);
+        state.resetting = false;
+        state.saved = false;
+        trackers.party_freeze = false;
+    }
+}
+
+// trigger signals that end a raid, Some(true) for a clear and Some(false) for a wipe
+fn raid_end_signal(trigger_signal_type: u32) -> Option<bool> {
+    RAID_DATA
+        .load()
+        .signals
+        .get(&trigger_signal_type)
+        .map(|signal| *signal == RaidSignal::Clear)
+}
+
+// the value of a newly added shield is how much it can absorb
+fn on_shields_applied(entity_tracker: &EntityTracker, state: &mut EncounterState, status_effects: &[StatusEffect]) {
+    for se in status_effects.iter().filter(|se| se.status_effect_type == StatusEffectType::Shield) {
+        if let Some((source, target)) = entity_tracker.get_status_effect_entities(se) {
+            state.on_shield_applied(source, target, se.status_effect_id as i32, se.value as i64);
+        }
+    }
+}
+
+// syncs lower a shield's value by what it absorbed, a higher value is a refresh and isn't counted
+fn on_shield_synced(entity_tracker: &EntityTracker, state: &mut EncounterState, synced: Option<(StatusEffect, u32)>) {
+    if let Some((se, old_value)) = synced {
+        if se.status_effect_type != StatusEffectType::Shield || se.value >= old_value {
+            return;
+        }
+        if let Some((source, target)) = entity_tracker.get_status_effect_entities(&se) {
+            state.on_shield_absorbed(source, target, se.status_effect_id as i32, (old_value - se.value) as i64);
+        }
+    }
+}
+
+// live packets are stamped on their own thread as soon as meter_core hands them over,
+// so a parser that falls behind still sees the time each packet was received
+fn with_receive_time(
+    rx: Receiver<(Pkt, Vec<u8>)>,
+    stop: Arc<AtomicBool>,
+) -> Receiver<(Pkt, Vec<u8>, i64)> {
+    forward_packets(rx, stop, |(op, data)| {
+        (op, data, Utc::now().timestamp_millis())
+    })
+}
+
+// passes packets on from a source thread until it runs out or a stop is requested,
+// either way the returned channel is closed so the parser loop can finish up
+fn forward_packets<T, U>(
+    rx: Receiver<T>,
+    stop: Arc<AtomicBool>,
+    map: impl Fn(T) -> U + Send + 'static,
+) -> Receiver<U>
+where
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    let (tx, forwarded) = mpsc::channel();
+    thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            match rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(packet) => {
+                    if tx.send(map(packet)).is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    });
+    forwarded
+}
+
 fn update_party(party_tracker: &Rc<RefCell<PartyTracker>>, entity_tracker: &EntityTracker) -> Vec<Vec<String>> {
     let mut party_info: HashMap<u32, Vec<String>> = HashMap::new();
 
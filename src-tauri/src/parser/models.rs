@@ -7,7 +7,13 @@ use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use serde_with::DefaultOnError;
 
+use crate::parser::meter_data::DataTable;
+
 pub const DB_VERSION: i32 = 3;
+// bump when Raid.json changes shape, newer files are ignored in favor of the embedded copy
+pub const RAID_DATA_VERSION: u32 = 2;
+// bump when the exported encounter document changes shape, newer exports are rejected on import
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq, Copy, Clone)]
 #[allow(non_camel_case_types)]
@@ -57,8 +63,8 @@ impl FromStr for EntityType {
     }
 }
 
-#[derive(Debug, Serialize, Clone, Default)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Encounter {
     pub last_combat_packet: i64,
     pub fight_start: i64,
@@ -72,10 +78,14 @@ pub struct Encounter {
     pub favorite: bool,
     pub cleared: bool,
     pub boss_only_damage: bool,
+    pub notes: Option<String>,
+    pub imported: bool,
+    // db version the encounter was first saved with, kept across export and import
+    pub version: i32,
 }
 
-#[derive(Debug, Serialize, Clone, Default)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct EncounterDamageStats {
     pub total_damage_dealt: i64,
     pub top_damage_dealt: i64,
@@ -85,6 +95,11 @@ pub struct EncounterDamageStats {
     pub most_damage_taken_entity: MostDamageTakenEntity,
     pub buffs: HashMap<i32, StatusEffect>,
     pub debuffs: HashMap<i32, StatusEffect>,
+    pub total_shielding: i64,
+    pub total_effective_shielding: i64,
+    pub applied_shield_buffs: HashMap<i32, StatusEffect>,
+    // raid damage to each target, keyed by npc id
+    pub damage_by_target: HashMap<u32, TargetDamage>,
     #[serde(skip)]
     pub unknown_buffs: HashSet<i32>,
     #[serde(skip)]
@@ -95,17 +110,18 @@ pub struct EncounterDamageStats {
     pub misc: Option<EncounterMisc>,
 }
 
-#[derive(Debug, Serialize, Clone, Default)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct MostDamageTakenEntity {
     pub name: String,
     pub damage_taken: i64,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EncounterEntity {
     pub id: u64,
+    pub character_id: u64,
     pub npc_id: u32,
     pub name: String,
     pub entity_type: EntityType,
@@ -132,6 +148,7 @@ pub struct Skill {
     pub debuffed_by: HashMap<i32, i64>,
     pub buffed_by_support: i64,
     pub debuffed_by_support: i64,
+    pub damage_by_target: HashMap<u32, TargetDamage>,
     pub casts: i64,
     pub hits: i64,
     pub crits: i64,
@@ -172,18 +189,58 @@ pub struct DamageStats {
     pub debuffed_by: HashMap<i32, i64>,
     pub buffed_by_support: i64,
     pub debuffed_by_support: i64,
+    // damage dealt under each support's buffs and brands, keyed by the support's name
+    pub buffed_by_supports: HashMap<String, i64>,
+    pub debuffed_by_supports: HashMap<String, i64>,
+    // for supports, damage the party dealt under their buffs and brands
+    pub support_buffed_damage: i64,
+    pub support_debuffed_damage: i64,
     pub crit_damage: i64,
     pub back_attack_damage: i64,
     pub front_attack_damage: i64,
     pub deaths: i64,
     pub death_time: i64,
     pub dps: i64,
+    pub damage_by_target: HashMap<u32, TargetDamage>,
+    // damage taken keyed by the enemy that dealt it, then by its skill
+    pub damage_taken_by_skill: HashMap<String, HashMap<i32, DamageTakenSkill>>,
+    // shields given to and absorbed on other players, keyed by shield buff id
+    pub shields_given: i64,
+    pub shields_given_by: HashMap<i32, i64>,
+    pub damage_absorbed_on_others: i64,
+    pub damage_absorbed_on_others_by: HashMap<i32, i64>,
+    // shields on this player from anyone, including their own
+    pub shields_received: i64,
+    pub shields_received_by: HashMap<i32, i64>,
+    pub damage_absorbed: i64,
+    pub damage_absorbed_by: HashMap<i32, i64>,
+    // fraction of the fight each buff (players) or debuff (bosses) was on this entity
+    pub uptime: HashMap<i32, f64>,
     #[serde_as(deserialize_as = "DefaultOnError")]
     pub dps_average: Vec<i64>,
     #[serde_as(deserialize_as = "DefaultOnError")]
     pub dps_rolling_10s_avg: Vec<i64>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DamageTakenSkill {
+    pub id: i32,
+    pub name: String,
+    pub icon: String,
+    pub damage: i64,
+    pub hits: i64,
+    pub max_hit: i64,
+}
+
+// adds that share a name are kept apart by npc id, the name is only for display
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TargetDamage {
+    pub name: String,
+    pub damage: i64,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SkillStats {
@@ -286,6 +343,77 @@ pub struct Npc {
     pub npc_type: String,
 }
 
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RaidData {
+    #[serde(default)]
+    pub version: u32,
+    // raids and the bosses in each gate, oldest first
+    #[serde(default)]
+    pub raids: Vec<Raid>,
+    // raid id from RaidBegin to difficulty
+    #[serde(default)]
+    pub raid_ids: HashMap<u32, RaidInfo>,
+    // zone level from ZoneMemberLoadStatusNotify to difficulty
+    #[serde(default)]
+    pub zone_levels: HashMap<u32, String>,
+    // trigger signal types that end a raid
+    #[serde(default)]
+    pub signals: HashMap<u32, RaidSignal>,
+    #[serde(default)]
+    pub boss_quirks: HashMap<String, BossQuirks>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct RaidInfo {
+    pub difficulty: String,
+    // raid and gate from `raids`, left null until the id is confirmed in game
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub gate: Option<u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Raid {
+    pub name: String,
+    pub gates: Vec<RaidGate>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct RaidGate {
+    pub gate: u32,
+    pub bosses: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RaidSignal {
+    Clear,
+    Wipe,
+}
+
+// layouts for the chat summary, discord renders all of them
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryTemplate {
+    // one line per player
+    #[default]
+    Compact,
+    // aligned columns in a code block
+    Table,
+    // a bullet list with damage, crit and counters as well
+    Detailed,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BossQuirks {
+    // the boss spawns before TriggerBossBattleStatus, so always reset on it
+    #[serde(default)]
+    pub reset_on_battle_status: bool,
+}
+
 #[derive(Debug, Default, Deserialize, Clone)]
 pub struct Esther {
     pub name: String,
@@ -464,6 +592,178 @@ pub struct EncounterPreview {
     pub cleared: bool,
 }
 
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EncounterComparison {
+    pub player: String,
+    pub base: PlayerSummary,
+    pub other: PlayerSummary,
+    // other minus base
+    pub dps_delta: i64,
+    pub skills: Vec<SkillComparison>,
+    pub buffs: Vec<BuffComparison>,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSummary {
+    pub encounter_id: i32,
+    pub boss_name: String,
+    pub fight_start: i64,
+    pub duration: i64,
+    pub class: String,
+    pub damage_dealt: i64,
+    pub dps: i64,
+    pub casts_per_minute: f64,
+    pub crit_rate: f64,
+    pub back_attack_rate: f64,
+    pub front_attack_rate: f64,
+    pub identity_average: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SkillComparison {
+    pub skill_id: i32,
+    pub name: String,
+    pub icon: String,
+    // none when the skill wasn't used in that encounter
+    pub base: Option<SkillSummary>,
+    pub other: Option<SkillSummary>,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SkillSummary {
+    pub damage: i64,
+    pub damage_share: f64,
+    pub dps: i64,
+    pub casts: i64,
+    pub casts_per_minute: f64,
+    pub crit_rate: f64,
+    pub back_attack_rate: f64,
+    pub front_attack_rate: f64,
+}
+
+// share of the player's damage dealt while the buff or debuff was up
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BuffComparison {
+    pub buff_id: i32,
+    pub name: String,
+    pub debuff: bool,
+    pub base: f64,
+    pub other: f64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryFilter {
+    // narrows the history to one character when several have had the same name
+    pub character_id: Option<u64>,
+    pub bosses: Vec<String>,
+    pub difficulty: String,
+    pub cleared: bool,
+    // number of encounters in each rolling average, 0 uses the default
+    pub rolling_window: usize,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPoint {
+    pub encounter_id: i32,
+    pub fight_start: i64,
+    pub boss_name: String,
+    pub difficulty: Option<String>,
+    pub cleared: bool,
+    pub class: String,
+    pub gear_score: f64,
+    pub dps: i64,
+    pub damage_percent: f64,
+    pub deaths: i64,
+    pub support_buffed_percent: f64,
+    pub rolling_dps: f64,
+    pub rolling_damage_percent: f64,
+    pub rolling_support_buffed_percent: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordKind {
+    Dps,
+    MaxHit,
+    CritRate,
+    ClearTime,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterRecord {
+    pub boss: String,
+    pub difficulty: String,
+    pub kind: RecordKind,
+    pub value: f64,
+    pub encounter_id: i64,
+    pub skill_id: Option<i64>,
+    pub fight_start: i64,
+}
+
+// sent with the personal-best event when a saved encounter sets or beats a record
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalBest {
+    pub character: String,
+    pub boss: String,
+    pub difficulty: String,
+    pub kind: RecordKind,
+    pub value: f64,
+    pub previous: Option<f64>,
+    pub encounter_id: i64,
+    pub skill_id: Option<i64>,
+}
+
+// the document written by export_encounter, everything needed to open the log in another meter
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EncounterExport {
+    pub format_version: u32,
+    pub app_version: String,
+    pub db_version: i32,
+    pub raid_data_version: u32,
+    pub exported_at: i64,
+    pub encounter: Encounter,
+}
+
+// a player keyed by their character id, so renames and same-named players on other servers stay apart
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Character {
+    pub character_id: u64,
+    pub name: String,
+    pub class_id: u32,
+    pub class: String,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    pub names: Vec<CharacterName>,
+    pub gear_scores: Vec<GearScorePoint>,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterName {
+    pub name: String,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GearScorePoint {
+    pub encounter_id: i64,
+    pub fight_start: i64,
+    pub gear_score: f64,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct EncountersOverview {
@@ -482,6 +782,33 @@ pub struct SearchFilter {
     pub favorite: bool,
     pub difficulty: String,
     pub boss_only_damage: bool,
+    // fight_start bounds in unix ms
+    pub from_date: Option<i64>,
+    pub to_date: Option<i64>,
+    // class filter only looks at the local player
+    pub local_player_only: bool,
+    // encounters that have all of these players
+    pub party_members: Vec<String>,
+    pub sort: EncounterSort,
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EncounterSort {
+    #[default]
+    Date,
+    Duration,
+    Dps,
+    LocalPlayerDps,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -529,6 +856,7 @@ pub struct GeneralSettings {
     #[serde(default = "default_true")]
     pub always_on_top: bool,
     pub boss_only_damage: bool,
+    pub record_packets: bool,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -668,30 +996,20 @@ pub enum HitFlag {
 }
 
 lazy_static! {
-    pub static ref NPC_DATA: HashMap<u32, Npc> = {
-        let json_str = include_str!("../../meter-data/Npc.json");
-        serde_json::from_str(json_str).unwrap()
-    };
-    pub static ref SKILL_DATA: HashMap<i32, SkillData> = {
-        let json_str = include_str!("../../meter-data/Skill.json");
-        serde_json::from_str(json_str).unwrap()
-    };
-    pub static ref SKILL_EFFECT_DATA: HashMap<i32, SkillEffectData> = {
-        let json_str = include_str!("../../meter-data/SkillEffect.json");
-        serde_json::from_str(json_str).unwrap()
-    };
-    pub static ref SKILL_BUFF_DATA: HashMap<i32, SkillBuffData> = {
-        let json_str = include_str!("../../meter-data/SkillBuff.json");
-        serde_json::from_str(json_str).unwrap()
-    };
-    pub static ref COMBAT_EFFECT_DATA: HashMap<i32, CombatEffectData> = {
-        let json_str = include_str!("../../meter-data/CombatEffect.json");
-        serde_json::from_str(json_str).unwrap()
-    };
-    pub static ref ESTHER_DATA: Vec<Esther> = {
-        let json_str = include_str!("../../meter-data/Esther.json");
-        serde_json::from_str(json_str).unwrap()
-    };
+    pub static ref NPC_DATA: DataTable<HashMap<u32, Npc>> =
+        DataTable::new("Npc.json", include_str!("../../meter-data/Npc.json"));
+    pub static ref SKILL_DATA: DataTable<HashMap<i32, SkillData>> =
+        DataTable::new("Skill.json", include_str!("../../meter-data/Skill.json"));
+    pub static ref SKILL_EFFECT_DATA: DataTable<HashMap<i32, SkillEffectData>> =
+        DataTable::new("SkillEffect.json", include_str!("../../meter-data/SkillEffect.json"));
+    pub static ref SKILL_BUFF_DATA: DataTable<HashMap<i32, SkillBuffData>> =
+        DataTable::new("SkillBuff.json", include_str!("../../meter-data/SkillBuff.json"));
+    pub static ref COMBAT_EFFECT_DATA: DataTable<HashMap<i32, CombatEffectData>> =
+        DataTable::new("CombatEffect.json", include_str!("../../meter-data/CombatEffect.json"));
+    pub static ref ESTHER_DATA: DataTable<Vec<Esther>> =
+        DataTable::new("Esther.json", include_str!("../../meter-data/Esther.json"));
+    pub static ref RAID_DATA: DataTable<RaidData> =
+        DataTable::new("Raid.json", include_str!("../../meter-data/Raid.json"));
     pub static ref STAT_TYPE_MAP: HashMap<&'static str, u32> = {
         let mut map = HashMap::new();
         map.insert("none", 0);
@@ -0,0 +1,102 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::{TimeZone, Utc};
+use log::{info, warn};
+use meter_core::packets::opcodes::Pkt;
+
+pub const CAPTURE_MAGIC: &[u8; 6] = b"LOACAP";
+pub const CAPTURE_VERSION: u16 = 2;
+pub const CAPTURE_EXTENSION: &str = "loacap";
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// capture file layout, all integers little endian
+// header: magic (6 bytes), version (u16), recording start as unix ms (i64)
+// record: ms since recording start (u64), packet time as unix ms (i64), opcode (u16),
+// payload length (u32), payload
+// the offset is monotonic and only paces replays, version 1 records have no packet time
+pub struct CaptureRecorder {
+    dir: PathBuf,
+    writer: Option<BufWriter<File>>,
+    started: Instant,
+    last_flush: Instant,
+}
+
+impl CaptureRecorder {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir).context("failed to create capture directory")?;
+        let mut recorder = Self {
+            dir,
+            writer: None,
+            started: Instant::now(),
+            last_flush: Instant::now(),
+        };
+        recorder.rotate(Utc::now().timestamp_millis())?;
+        Ok(recorder)
+    }
+
+    // start a new capture file, called whenever we change zones
+    pub fn rotate(&mut self, timestamp: i64) -> Result<()> {
+        self.finish();
+
+        let started = Utc
+            .timestamp_millis_opt(timestamp)
+            .single()
+            .context("invalid capture start time")?;
+        let path = self.dir.join(format!(
+            "{}.{}",
+            started.format("%Y%m%d-%H%M%S-%3f"),
+            CAPTURE_EXTENSION
+        ));
+        let file = File::create(&path).context("failed to create capture file")?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(CAPTURE_MAGIC)?;
+        writer.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        writer.write_all(&timestamp.to_le_bytes())?;
+
+        info!("recording packets to {}", path.display());
+        self.writer = Some(writer);
+        self.started = Instant::now();
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    // timestamp is the packet clock time, so a replay sees the same times as the original run
+    pub fn record(&mut self, timestamp: i64, op: Pkt, data: &[u8]) -> Result<()> {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+
+        writer.write_all(&elapsed.to_le_bytes())?;
+        writer.write_all(&timestamp.to_le_bytes())?;
+        writer.write_all(&(op as u16).to_le_bytes())?;
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(data)?;
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            writer.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            if let Err(e) = writer.flush() {
+                warn!("failed to flush capture file: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for CaptureRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
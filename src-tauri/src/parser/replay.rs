@@ -0,0 +1,236 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use meter_core::packets::opcodes::Pkt;
+
+use crate::parser::recorder::{CAPTURE_EXTENSION, CAPTURE_MAGIC, CAPTURE_VERSION};
+use crate::parser::PacketSource;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    // 1.0 is the original speed of the capture
+    Realtime(f64),
+    Unlimited,
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("max") {
+            return Ok(ReplaySpeed::Unlimited);
+        }
+        let multiplier: f64 = s
+            .trim_end_matches(['x', 'X'])
+            .parse()
+            .with_context(|| format!("invalid replay speed: {}", s))?;
+        if multiplier <= 0.0 || !multiplier.is_finite() {
+            bail!("invalid replay speed: {}", s);
+        }
+        Ok(ReplaySpeed::Realtime(multiplier))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureRecord {
+    pub elapsed_ms: u64,
+    // when the packet was originally received, unix ms
+    pub timestamp: i64,
+    pub opcode: u16,
+    pub data: Vec<u8>,
+}
+
+pub struct CaptureReader {
+    reader: BufReader<File>,
+    version: u16,
+    pub started_at: i64,
+}
+
+impl CaptureReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open capture file {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 6];
+        reader
+            .read_exact(&mut magic)
+            .context("failed to read capture header")?;
+        if &magic != CAPTURE_MAGIC {
+            bail!("{} is not a capture file", path.display());
+        }
+        let version = u16::from_le_bytes(read_array(&mut reader)?);
+        if version > CAPTURE_VERSION {
+            bail!(
+                "capture file version {} is newer than supported version {}",
+                version,
+                CAPTURE_VERSION
+            );
+        }
+        let started_at = i64::from_le_bytes(read_array(&mut reader)?);
+
+        Ok(Self {
+            reader,
+            version,
+            started_at,
+        })
+    }
+
+    // returns None at the end of the file, a truncated last record is treated as the end
+    pub fn next_record(&mut self) -> Result<Option<CaptureRecord>> {
+        let elapsed_ms = match read_array(&mut self.reader) {
+            Ok(bytes) => u64::from_le_bytes(bytes),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let record = (|| -> io::Result<CaptureRecord> {
+            // version 1 only has the offset from the start of the file
+            let timestamp = if self.version >= 2 {
+                i64::from_le_bytes(read_array(&mut self.reader)?)
+            } else {
+                self.started_at + elapsed_ms as i64
+            };
+            let opcode = u16::from_le_bytes(read_array(&mut self.reader)?);
+            let len = u32::from_le_bytes(read_array(&mut self.reader)?) as usize;
+            let mut data = vec![0u8; len];
+            self.reader.read_exact(&mut data)?;
+            Ok(CaptureRecord {
+                elapsed_ms,
+                timestamp,
+                opcode,
+                data,
+            })
+        })();
+
+        match record {
+            Ok(record) => Ok(Some(record)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                warn!("capture file ends with a truncated record");
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// expands a directory into its capture files, sorted by name (which is the recording time)
+pub fn collect_capture_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(path)
+        .with_context(|| format!("failed to read capture directory {}", path.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().map_or(false, |ext| ext == CAPTURE_EXTENSION))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+// mirrors meter_core::start_capture, packets are sent from a background thread
+// along with the time they were originally received
+// the channel is closed once every file has been replayed
+pub fn start_replay(files: Vec<PathBuf>, speed: ReplaySpeed) -> Result<Receiver<(Pkt, Vec<u8>, i64)>> {
+    if files.is_empty() {
+        bail!("no capture files to replay");
+    }
+
+    // open everything up front so a bad path is reported to the caller
+    let readers = files
+        .iter()
+        .map(|path| CaptureReader::open(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for (path, mut reader) in files.into_iter().zip(readers) {
+            info!("replaying {} at {:?}", path.display(), speed);
+            let replay_start = Instant::now();
+            let mut skipped = 0;
+            loop {
+                let record = match reader.next_record() {
+                    Ok(Some(record)) => record,
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("error reading {}: {}", path.display(), e);
+                        break;
+                    }
+                };
+
+                if let ReplaySpeed::Realtime(multiplier) = speed {
+                    let target = Duration::from_secs_f64(record.elapsed_ms as f64 / 1000.0 / multiplier);
+                    let elapsed = replay_start.elapsed();
+                    if target > elapsed {
+                        thread::sleep(target - elapsed);
+                    }
+                }
+
+                let op = match Pkt::try_from(record.opcode) {
+                    Ok(op) => op,
+                    Err(_) => {
+                        skipped += 1;
+                        continue;
+                    }
+                };
+                if tx.send((op, record.data, record.timestamp)).is_err() {
+                    return;
+                }
+            }
+            if skipped > 0 {
+                warn!("skipped {} packets with unknown opcodes", skipped);
+            }
+        }
+        info!("finished replaying captures");
+    });
+
+    Ok(rx)
+}
+
+// replays recorded captures instead of sniffing packets
+// usage: --replay <file or directory> [--speed <multiplier>|max]
+pub fn get_replay_source(args: &[String]) -> Option<PacketSource> {
+    let path = args
+        .iter()
+        .position(|arg| arg == "--replay")
+        .and_then(|i| args.get(i + 1))?;
+    let speed = match args
+        .iter()
+        .position(|arg| arg == "--speed")
+        .and_then(|i| args.get(i + 1))
+    {
+        Some(speed) => match ReplaySpeed::from_str(speed) {
+            Ok(speed) => speed,
+            Err(e) => {
+                warn!("{}, replaying at original speed", e);
+                ReplaySpeed::Realtime(1.0)
+            }
+        },
+        None => ReplaySpeed::Realtime(1.0),
+    };
+
+    match collect_capture_files(Path::new(path)) {
+        Ok(files) => {
+            info!("replay mode, {} capture file(s) from {}", files.len(), path);
+            Some(PacketSource::Replay { files, speed })
+        }
+        Err(e) => {
+            warn!("could not start replay: {}", e);
+            None
+        }
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
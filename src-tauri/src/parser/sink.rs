@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde_json::Value;
+
+pub type EventHandler = Box<dyn Fn(Option<&str>) + Send + 'static>;
+
+// everything the parser needs from the outside world
+// the meter window implements this in the app, headless runs and tests provide their own
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &str, payload: Value) -> Result<()>;
+
+    // handler receives the raw event payload, if any
+    fn listen(&self, event: &str, handler: EventHandler);
+
+    // directory holding encounters.db, captures, etc.
+    fn resource_dir(&self) -> PathBuf;
+}
@@ -1,3 +1,4 @@
+use crate::parser::clock::PacketClock;
 use crate::parser::entity_tracker::Entity;
 use crate::parser::models::{EntityType, SKILL_BUFF_DATA};
 use crate::parser::party_tracker::PartyTracker;
@@ -15,18 +16,25 @@ const TIMEOUT_DELAY_MS: i64 = 1000;
 
 pub type StatusEffectRegistry = HashMap<u32, StatusEffect>;
 
+// (status_effect_id, start, end) in ms for every instance that has ended, per registry and target
+type UptimeLog = HashMap<(StatusEffectTargetType, u64), Vec<(u32, i64, i64)>>;
+
 pub struct StatusTracker {
+    clock: PacketClock,
     party_tracker: Rc<RefCell<PartyTracker>>,
     local_status_effect_registry: HashMap<u64, StatusEffectRegistry>,
     party_status_effect_registry: HashMap<u64, StatusEffectRegistry>,
+    uptime_log: UptimeLog,
 }
 
 impl StatusTracker {
-    pub fn new(party_tracker: Rc<RefCell<PartyTracker>>) -> Self {
+    pub fn new(clock: PacketClock, party_tracker: Rc<RefCell<PartyTracker>>) -> Self {
         Self {
+            clock,
             party_tracker,
             local_status_effect_registry: HashMap::new(),
             party_status_effect_registry: HashMap::new(),
+            uptime_log: HashMap::new(),
         }
     }
 
@@ -45,7 +53,13 @@ impl StatusTracker {
         };
         for sed in pkt.pc_struct.status_effect_datas.into_iter() {
             let source_id = sed.source_id;
-            let status_effect = build_status_effect(sed, target_id, source_id, target_type);
+            let status_effect = build_status_effect(
+                sed,
+                target_id,
+                source_id,
+                target_type,
+                self.clock.now_utc(),
+            );
             self.register_status_effect(status_effect);
         }
     }
@@ -60,15 +74,22 @@ impl StatusTracker {
 
         let ser = registry.get_mut(&se.target_id).unwrap();
         add_status_effect_timeout(&mut se);
-        ser.insert(se.instance_id, se);
+        // the same instance sent again replaces the old one
+        if let Some(replaced) = ser.insert(se.instance_id, se) {
+            self.log_uptime(vec![replaced]);
+        }
     }
 
     pub fn remove_local_object(&mut self, object_id: u64) {
-        self.local_status_effect_registry.remove(&object_id);
+        if let Some(ser) = self.local_status_effect_registry.remove(&object_id) {
+            self.log_uptime(ser.into_values().collect());
+        }
     }
 
     pub fn remove_party_object(&mut self, object_id: u64) {
-        self.party_status_effect_registry.remove(&object_id);
+        if let Some(ser) = self.party_status_effect_registry.remove(&object_id) {
+            self.log_uptime(ser.into_values().collect());
+        }
     }
 
     pub fn remove_status_effects(
@@ -81,11 +102,13 @@ impl StatusTracker {
             StatusEffectTargetType::Local => &mut self.local_status_effect_registry,
             StatusEffectTargetType::Party => &mut self.party_status_effect_registry,
         };
+        let mut removed = Vec::new();
         if let Some(ser) = registry.get_mut(&target_id) {
             for id in instance_id {
-                ser.remove(&id);
+                removed.extend(ser.remove(&id));
             }
         }
+        self.log_uptime(removed);
     }
 
     pub fn update_status_duration(
@@ -114,7 +137,16 @@ impl StatusTracker {
         }
     }
 
-    /*    pub fn sync_status_effect(&mut self, instance_id: u32, character_id: u64, object_id: u64, value: u32, local_character_id: u64) {
+    // updates the value of a status effect instance, for shields this is what they have left to absorb
+    // returns the updated effect and the value it had before
+    pub fn sync_status_effect(
+        &mut self,
+        instance_id: u32,
+        character_id: u64,
+        object_id: u64,
+        value: u32,
+        local_character_id: u64,
+    ) -> Option<(StatusEffect, u32)> {
         let use_party = self.should_use_party_status_effect(character_id, local_character_id);
         let (target_id, sett) = if use_party {
             (character_id, StatusEffectTargetType::Party)
@@ -122,25 +154,18 @@ impl StatusTracker {
             (object_id, StatusEffectTargetType::Local)
         };
         if target_id == 0 {
-            return;
+            return None;
         }
         let registry = match sett {
             StatusEffectTargetType::Local => &mut self.local_status_effect_registry,
             StatusEffectTargetType::Party => &mut self.party_status_effect_registry,
         };
 
-        let ser = match registry.get_mut(&target_id) {
-            Some(ser) => ser,
-            None => return,
-        };
-
-        let se = match ser.get_mut(&instance_id) {
-            Some(se) => se,
-            None => return,
-        };
-
+        let se = registry.get_mut(&target_id)?.get_mut(&instance_id)?;
+        let old_value = se.value;
         se.value = value;
-    }*/
+        Some((se.clone(), old_value))
+    }
 
     pub fn get_status_effects(
         &mut self,
@@ -226,8 +251,11 @@ impl StatusTracker {
             Some(ser) => ser,
             None => return Vec::new(),
         };
-        ser.retain(|_, se| se.expire_at.map_or(true, |expire_at| expire_at > Utc::now()));
-        ser.values().cloned().collect()
+        let timestamp = self.clock.now_utc();
+        let expired = retain_active(ser, timestamp);
+        let active = ser.values().cloned().collect();
+        self.log_uptime(expired);
+        active
     }
 
     pub fn get_status_effects_from_party(
@@ -246,11 +274,12 @@ impl StatusTracker {
             None => return Vec::new(),
         };
 
-        let timestamp = Utc::now();
+        let timestamp = self.clock.now_utc();
         // println!("ser before: {:?}", ser);
-        ser.retain(|_, se| se.expire_at.map_or(true, |expire_at| expire_at > timestamp));
+        let expired = retain_active(ser, timestamp);
         let party_tracker = self.party_tracker.borrow();
-        ser.values()
+        let active = ser
+            .values()
             .filter(|x| {
                 is_valid_for_raid(x)
                     || *party_id
@@ -261,7 +290,10 @@ impl StatusTracker {
                             .unwrap_or(0)
             })
             .cloned()
-            .collect()
+            .collect();
+        drop(party_tracker);
+        self.log_uptime(expired);
+        active
     }
 
     fn should_use_party_status_effect(&self, character_id: u64, local_character_id: u64) -> bool {
@@ -286,12 +318,132 @@ impl StatusTracker {
         }
     }
 
+    // effects still active when the tracker is cleared end now, their uptime is kept until clear_uptime
     pub fn clear(&mut self) {
-        self.local_status_effect_registry.clear();
-        self.party_status_effect_registry.clear();
+        let mut ended: Vec<StatusEffect> = Vec::new();
+        for (_, ser) in self
+            .local_status_effect_registry
+            .drain()
+            .chain(self.party_status_effect_registry.drain())
+        {
+            ended.extend(ser.into_values());
+        }
+        self.log_uptime(ended);
+    }
+
+    fn log_uptime(&mut self, ended: Vec<StatusEffect>) {
+        let now = self.clock.now();
+        for se in ended {
+            self.uptime_log
+                .entry((se.target_type, se.target_id))
+                .or_default()
+                .push((
+                    se.status_effect_id,
+                    se.timestamp.timestamp_millis(),
+                    uptime_end(&se, now),
+                ));
+        }
+    }
+
+    // fraction of start..end each status effect was on the target, overlapping instances count once
+    pub fn get_uptime(
+        &self,
+        target_id: u64,
+        sett: StatusEffectTargetType,
+        start: i64,
+        end: i64,
+    ) -> HashMap<u32, f64> {
+        let registry = match sett {
+            StatusEffectTargetType::Local => &self.local_status_effect_registry,
+            StatusEffectTargetType::Party => &self.party_status_effect_registry,
+        };
+        let now = self.clock.now();
+        let mut intervals: HashMap<u32, Vec<(i64, i64)>> = HashMap::new();
+        for &(id, from, to) in self
+            .uptime_log
+            .get(&(sett, target_id))
+            .into_iter()
+            .flatten()
+        {
+            intervals.entry(id).or_default().push((from, to));
+        }
+        for se in registry
+            .get(&target_id)
+            .into_iter()
+            .flat_map(|ser| ser.values())
+        {
+            intervals
+                .entry(se.status_effect_id)
+                .or_default()
+                .push((se.timestamp.timestamp_millis(), uptime_end(se, now)));
+        }
+
+        intervals
+            .into_iter()
+            .map(|(id, intervals)| (id, covered_fraction(intervals, start, end)))
+            .filter(|(_, uptime)| *uptime > 0.0)
+            .collect()
+    }
+
+    pub fn move_local_uptime(&mut self, from: u64, to: u64) {
+        if from == to {
+            return;
+        }
+        if let Some(intervals) = self
+            .uptime_log
+            .remove(&(StatusEffectTargetType::Local, from))
+        {
+            self.uptime_log
+                .entry((StatusEffectTargetType::Local, to))
+                .or_default()
+                .extend(intervals);
+        }
+    }
+
+    // called once the encounter the uptime was for is done with
+    pub fn clear_uptime(&mut self) {
+        self.uptime_log.clear();
+    }
+}
+
+fn retain_active(ser: &mut StatusEffectRegistry, timestamp: DateTime<Utc>) -> Vec<StatusEffect> {
+    let mut expired = Vec::new();
+    ser.retain(|_, se| {
+        let active = se.expire_at.map_or(true, |expire_at| expire_at > timestamp);
+        if !active {
+            expired.push(se.clone());
+        }
+        active
+    });
+    expired
+}
+
+// expire_at has some slack added so late duration packets still land, uptime ends without it
+fn uptime_end(se: &StatusEffect, now: i64) -> i64 {
+    match se.expire_at {
+        Some(expire_at) => now.min(expire_at.timestamp_millis() - TIMEOUT_DELAY_MS),
+        None => now,
     }
 }
 
+fn covered_fraction(mut intervals: Vec<(i64, i64)>, start: i64, end: i64) -> f64 {
+    if end <= start {
+        return 0.0;
+    }
+    intervals.sort_unstable();
+    let mut covered = 0;
+    let mut covered_until = start;
+    for (from, to) in intervals {
+        let from = from.max(covered_until);
+        let to = to.min(end);
+        if to > from {
+            covered += to - from;
+            covered_until = to;
+        }
+    }
+    covered as f64 / (end - start) as f64
+}
+
 fn is_valid_for_raid(status_effect: &StatusEffect) -> bool {
     (status_effect.buff_category == BattleItem
         || status_effect.buff_category == Bracelet
@@ -316,32 +468,15 @@ pub fn build_status_effect(
     target_id: u64,
     source_id: u64,
     target_type: StatusEffectTargetType,
+    timestamp: DateTime<Utc>,
 ) -> StatusEffect {
-    let c1 = se_data
-        .value
-        .as_ref()
-        .and_then(|v| {
-            v.get(0..4)
-                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
-        })
-        .unwrap_or(0);
-
-    let c2 = se_data
-        .value
-        .as_ref()
-        .and_then(|v| {
-            v.get(4..8)
-                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
-        })
-        .unwrap_or(0);
-
-    let val = c1.min(c2);
+    let val = get_status_effect_value(&se_data.value);
     let mut status_effect_category = StatusEffectCategory::Other;
     let mut buff_category = StatusEffectBuffCategory::Other;
     let mut show_type = StatusEffectShowType::Other;
     let mut status_effect_type = StatusEffectType::Other;
     let mut name = "Unknown".to_string();
-    if let Some(effect) = SKILL_BUFF_DATA.get(&(se_data.status_effect_id as i32)) {
+    if let Some(effect) = SKILL_BUFF_DATA.load().get(&(se_data.status_effect_id as i32)) {
         name = effect.name.to_string();
         if effect.category.as_str() == "debuff" {
             status_effect_category = Debuff
@@ -375,11 +510,32 @@ pub fn build_status_effect(
         expire_at: None,
         end_tick: se_data.end_tick,
         name,
-        timestamp: Utc::now(),
+        timestamp,
     }
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+// values come as two little endian u32s, the smaller one is the effect's current value
+pub fn get_status_effect_value(value: &Option<Vec<u8>>) -> u32 {
+    let c1 = value
+        .as_ref()
+        .and_then(|v| {
+            v.get(0..4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        })
+        .unwrap_or(0);
+
+    let c2 = value
+        .as_ref()
+        .and_then(|v| {
+            v.get(4..8)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        })
+        .unwrap_or(0);
+
+    c1.min(c2)
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum StatusEffectTargetType {
     #[default]
     Party = 0,
@@ -419,15 +575,15 @@ pub enum StatusEffectType {
 #[derive(Debug, Default, Clone)]
 pub struct StatusEffect {
     instance_id: u32,
-    status_effect_id: u32,
-    target_id: u64,
-    source_id: u64,
-    target_type: StatusEffectTargetType,
-    value: u32,
+    pub status_effect_id: u32,
+    pub target_id: u64,
+    pub source_id: u64,
+    pub target_type: StatusEffectTargetType,
+    pub value: u32,
     category: StatusEffectCategory,
     buff_category: StatusEffectBuffCategory,
     show_type: StatusEffectShowType,
-    status_effect_type: StatusEffectType,
+    pub status_effect_type: StatusEffectType,
     expiration_delay: f32,
     expire_at: Option<DateTime<Utc>>,
     end_tick: u64,
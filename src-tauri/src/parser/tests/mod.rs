@@ -0,0 +1,303 @@
+// golden file tests for the parser pipeline
+// each scenario pushes a scripted sequence of packets through the trackers and compares
+// the resulting encounter, emitted events and saved rows against tests/golden/<name>.json
+// run with UPDATE_GOLDEN=1 to rewrite the golden files after an intended change
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Result;
+use meter_core::packets::common::StatPair;
+use meter_core::packets::definitions::*;
+use meter_core::packets::structures::*;
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use serde_json::{json, Map, Value};
+
+use crate::db::setup_db;
+use crate::parser::clock::PacketClock;
+use crate::parser::encounter_state::EncounterState;
+use crate::parser::models::STAT_TYPE_MAP;
+use crate::parser::sink::{EventHandler, EventSink};
+use crate::parser::meter_data::METER_DATA_OVERRIDE_DIR;
+use crate::parser::models::SKILL_BUFF_DATA;
+use crate::parser::{finish_reset, handle_packet, Packet, Trackers};
+
+mod scenarios;
+
+// collects events instead of sending them to a window
+struct RecordingSink {
+    dir: PathBuf,
+    events: Mutex<Vec<Value>>,
+}
+
+impl EventSink for RecordingSink {
+    fn emit(&self, event: &str, payload: Value) -> Result<()> {
+        if event != "encounter-update" && event != "party-update" {
+            self.events
+                .lock()
+                .unwrap()
+                .push(json!({ "event": event, "payload": payload }));
+        }
+        Ok(())
+    }
+
+    fn listen(&self, _event: &str, _handler: EventHandler) {}
+
+    fn resource_dir(&self) -> PathBuf {
+        self.dir.clone()
+    }
+}
+
+// meter data tables are global, scenarios take turns so an override never leaks into another one
+static METER_DATA_LOCK: Mutex<()> = Mutex::new(());
+
+struct MeterDataGuard {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl MeterDataGuard {
+    fn acquire() -> Self {
+        // a failed scenario still resets the tables on drop, so a poisoned lock is safe to reuse
+        Self {
+            _lock: METER_DATA_LOCK.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+}
+
+impl Drop for MeterDataGuard {
+    fn drop(&mut self) {
+        SKILL_BUFF_DATA.reset();
+    }
+}
+
+// same wiring and packet handling as parser::start, minus the ui updates
+pub struct Harness {
+    name: String,
+    clock: PacketClock,
+    sink: Arc<RecordingSink>,
+    trackers: Trackers,
+    state: EncounterState,
+    _meter_data: MeterDataGuard,
+}
+
+impl Harness {
+    pub fn new(name: &str) -> Self {
+        let meter_data = MeterDataGuard::acquire();
+        let dir = env::temp_dir().join(format!("loa-logs-golden-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create test dir");
+        setup_db(dir.clone()).expect("failed to set up test db");
+
+        let sink = Arc::new(RecordingSink {
+            dir,
+            events: Mutex::new(Vec::new()),
+        });
+        let clock = PacketClock::new();
+        let trackers = Trackers::new(&clock);
+        let state = EncounterState::new(
+            sink.clone(),
+            clock.clone(),
+            trackers.status_tracker.clone(),
+            trackers.id_tracker.clone(),
+        );
+
+        Self {
+            name: name.to_string(),
+            clock,
+            sink,
+            trackers,
+            state,
+            _meter_data: meter_data,
+        }
+    }
+
+    pub fn push(&mut self, timestamp: i64, packet: Packet) {
+        self.clock.set(timestamp);
+        handle_packet(&mut self.state, &mut self.trackers, packet, timestamp);
+        finish_reset(&mut self.state, &mut self.trackers);
+    }
+
+    // what parser::start does once the packet source closes
+    pub fn finish(&mut self) {
+        tokio::task::block_in_place(|| self.state.finish_saves());
+    }
+
+    // merges buff data into the loaded table the same way a meter-data-override file would
+    // the table goes back to the embedded data once the scenario is done
+    pub fn override_skill_buffs(&self, buffs: Value) {
+        let dir = self.sink.dir.join(METER_DATA_OVERRIDE_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("SkillBuff.json"), buffs.to_string()).unwrap();
+        SKILL_BUFF_DATA.reload(&self.sink.dir);
+    }
+
+    // compares everything the script produced against the golden file
+    pub async fn check(mut self) {
+        // saves run on spawned tasks, wait for them the same way the end of a replay does
+        self.finish();
+
+        let actual = canonical(json!({
+            "encounter": self.state.encounter,
+            "events": *self.sink.events.lock().unwrap(),
+            "hits": self.state.hits,
+            "rows": saved_rows(&self.sink.dir),
+        }));
+        let _ = fs::remove_dir_all(&self.sink.dir);
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join(format!("{}.json", self.name));
+        let actual = serde_json::to_string_pretty(&actual).unwrap() + "\n";
+
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, actual).expect("failed to write golden file");
+            return;
+        }
+
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "missing golden file {}, run with UPDATE_GOLDEN=1 to create it",
+                path.display()
+            )
+        });
+        assert_eq!(
+            expected.replace("\r\n", "\n"),
+            actual,
+            "{} does not match, run with UPDATE_GOLDEN=1 if the change is intended",
+            path.display()
+        );
+    }
+}
+
+fn saved_rows(dir: &Path) -> Value {
+    let conn = Connection::open(dir.join("encounters.db")).expect("failed to open test db");
+    json!({
+        "encounter": query_rows(&conn, "SELECT * FROM encounter ORDER BY id"),
+        "entity": query_rows(&conn, "SELECT * FROM entity ORDER BY encounter_id, name"),
+        "entity_skill": query_rows(
+            &conn,
+            "SELECT * FROM entity_skill ORDER BY encounter_id, player, skill_id"
+        ),
+        "entity_buff": query_rows(
+            &conn,
+            "SELECT * FROM entity_buff ORDER BY encounter_id, player, buff_id, debuff"
+        ),
+    })
+}
+
+fn query_rows(conn: &Connection, sql: &str) -> Vec<Value> {
+    let mut stmt = conn.prepare(sql).unwrap();
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut rows = stmt.query([]).unwrap();
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        let mut object = Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            let value = match row.get_ref(i).unwrap() {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(i) => json!(i),
+                ValueRef::Real(f) => json!(f),
+                // json columns are compared as json, not as strings
+                ValueRef::Text(t) => {
+                    let text = String::from_utf8_lossy(t).to_string();
+                    serde_json::from_str(&text).unwrap_or(Value::String(text))
+                }
+                ValueRef::Blob(b) => json!(b),
+            };
+            object.insert(column.clone(), value);
+        }
+        result.push(Value::Object(object));
+    }
+    result
+}
+
+// maps are serialized from hash maps, sort their keys so the output is stable
+// party members are collected from a hash map too, so each party is sorted by name
+fn canonical(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| {
+                        let v = if k == "partyInfo" {
+                            sorted_parties(v)
+                        } else {
+                            v
+                        };
+                        (k, canonical(v))
+                    })
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonical).collect()),
+        value => value,
+    }
+}
+
+fn sorted_parties(mut parties: Value) -> Value {
+    if let Value::Object(parties) = &mut parties {
+        for members in parties.values_mut() {
+            if let Value::Array(members) = members {
+                members.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+            }
+        }
+    }
+    parties
+}
+
+pub fn stats(hp: i64, max_hp: i64) -> Vec<StatPair> {
+    vec![
+        StatPair {
+            stat_type: STAT_TYPE_MAP["hp"] as u8,
+            value: hp,
+        },
+        StatPair {
+            stat_type: STAT_TYPE_MAP["max_hp"] as u8,
+            value: max_hp,
+        },
+    ]
+}
+
+pub fn damage(source_id: u64, skill_id: u32, target_id: u64, damage: i64, cur_hp: i64, max_hp: i64) -> Packet {
+    Packet::SkillDamage(PKTSkillDamageNotify {
+        source_id,
+        skill_id,
+        skill_effect_id: 0,
+        skill_damage_events: vec![SkillDamageEvent {
+            target_id,
+            damage,
+            cur_hp,
+            max_hp,
+            ..Default::default()
+        }],
+        ..Default::default()
+    })
+}
+
+// values are sent as two u32s and the smaller one is used, send the same value twice
+pub fn shield(source_id: u64, status_effect_id: u32, instance_id: u32, value: u32) -> StatusEffectData {
+    let mut bytes = value.to_le_bytes().to_vec();
+    bytes.extend(value.to_le_bytes());
+    StatusEffectData {
+        value: Some(bytes),
+        ..status_effect(source_id, status_effect_id, instance_id, 10.0)
+    }
+}
+
+pub fn status_effect(source_id: u64, status_effect_id: u32, instance_id: u32, total_time: f32) -> StatusEffectData {
+    StatusEffectData {
+        source_id,
+        status_effect_id,
+        effect_instance_id: instance_id,
+        total_time,
+        ..Default::default()
+    }
+}
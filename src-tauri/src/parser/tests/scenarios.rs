@@ -0,0 +1,668 @@
+use meter_core::packets::definitions::*;
+use meter_core::packets::structures::*;
+
+use serde_json::json;
+
+use super::{damage, shield, stats, status_effect, Harness, Packet};
+
+const START: i64 = 1_700_000_000_000;
+
+const LOCAL_PLAYER: u64 = 1;
+const PARTY_MEMBER: u64 = 2;
+const BARD: u64 = 3;
+const PALADIN: u64 = 4;
+const PALADIN_RESPAWNED: u64 = 6;
+const BOSS: u64 = 500;
+const ADD: u64 = 501;
+const OTHER_ADD: u64 = 502;
+
+// Valtan
+const BOSS_NPC_ID: u32 = 886052;
+const BOSS_HP: i64 = 1_000_000;
+const ADD_NPC_ID: u32 = 500010;
+// also called Demon Dog
+const OTHER_ADD_NPC_ID: u32 = 600120;
+const ADD_HP: i64 = 50_000;
+
+// berserker and sharpshooter skills
+const POWER_BREAK: u32 = 16030;
+const RAPID_SHOT: u32 = 28020;
+const SALVO: u32 = 28030;
+const BOSS_SLAM: u32 = 480_001;
+const BOSS_SWIPE: u32 = 480_002;
+
+// not in the buff data, so only the tracker's attribution shows up in the output
+const UNKNOWN_DEBUFF: u32 = 999_001;
+const UNKNOWN_PARTY_BUFF: u32 = 999_002;
+const UNKNOWN_SELF_BUFF: u32 = 999_003;
+
+// added to the buff data by the shields scenario
+const PARTY_SHIELD: u32 = 999_101;
+const SELF_SHIELD: u32 = 999_102;
+
+const AP_BUFF: u32 = 999_201;
+const BRAND: u32 = 999_202;
+
+const BARD_AP: u32 = 999_301;
+const BARD_BRAND: u32 = 999_302;
+const PALADIN_AP: u32 = 999_303;
+
+// local berserker and a sharpshooter in the same party, with the boss spawned
+fn setup(h: &mut Harness) {
+    h.push(
+        START,
+        Packet::InitPC(PKTInitPC {
+            player_id: LOCAL_PLAYER,
+            name: "Local".to_string(),
+            class_id: 102,
+            gear_level: 1620.0,
+            character_id: 1001,
+            stat_pair: stats(300_000, 300_000),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START,
+        Packet::NewPC(PKTNewPC {
+            pc_struct: PCStruct {
+                player_id: PARTY_MEMBER,
+                name: "Member".to_string(),
+                class_id: 502,
+                avg_item_level: 1610.0,
+                character_id: 1002,
+                stat_pair: stats(250_000, 250_000),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START,
+        Packet::PartyInfo(PKTPartyInfo {
+            party_instance_id: 10,
+            raid_instance_id: 20,
+            member_datas: vec![
+                PartyMemberData {
+                    name: "Local".to_string(),
+                    class_id: 102,
+                    character_id: 1001,
+                    gear_level: 1620.0,
+                    ..Default::default()
+                },
+                PartyMemberData {
+                    name: "Member".to_string(),
+                    class_id: 502,
+                    character_id: 1002,
+                    gear_level: 1610.0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START,
+        Packet::NewNpc(PKTNewNpc {
+            npc_struct: NpcData {
+                object_id: BOSS,
+                type_id: BOSS_NPC_ID,
+                stat_pair: stats(BOSS_HP, BOSS_HP),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    );
+}
+
+fn skill_start(source_id: u64, skill_id: u32) -> Packet {
+    Packet::SkillStart(PKTSkillStartNotify {
+        source_id,
+        skill_id,
+        ..Default::default()
+    })
+}
+
+fn trigger(trigger_signal_type: u32) -> Packet {
+    Packet::TriggerStart(PKTTriggerStartNotify {
+        trigger_signal_type,
+        ..Default::default()
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clear() {
+    let mut h = Harness::new("clear");
+    setup(&mut h);
+
+    h.push(START + 1_000, skill_start(LOCAL_PLAYER, POWER_BREAK));
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 200_000, 800_000, BOSS_HP));
+    h.push(START + 2_000, skill_start(PARTY_MEMBER, RAPID_SHOT));
+    h.push(START + 2_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 150_000, 650_000, BOSS_HP));
+    h.push(START + 4_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 300_000, 350_000, BOSS_HP));
+    h.push(START + 6_000, skill_start(PARTY_MEMBER, SALVO));
+    h.push(START + 6_000, damage(PARTY_MEMBER, SALVO, BOSS, 350_000, 0, BOSS_HP));
+    h.push(
+        START + 6_000,
+        Packet::Death(PKTDeathNotify {
+            target_id: BOSS,
+            ..Default::default()
+        }),
+    );
+    h.push(START + 6_500, trigger(57));
+    // lands after the clear, should not be counted
+    h.push(START + 7_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 0, BOSS_HP));
+
+    h.check().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wipe() {
+    let mut h = Harness::new("wipe");
+    setup(&mut h);
+
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    h.push(START + 2_000, damage(BOSS, 0, LOCAL_PLAYER, 300_000, 0, 300_000));
+    h.push(START + 3_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 50_000, 850_000, BOSS_HP));
+    h.push(START + 4_000, damage(BOSS, 0, PARTY_MEMBER, 250_000, 0, 250_000));
+    h.push(START + 4_500, trigger(58));
+    // still inside the cooldown after the wipe
+    h.push(START + 6_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    // next pull
+    h.push(START + 10_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+
+    h.check().await;
+}
+
+// the replay runs out mid pull, the encounter so far is still saved
+#[tokio::test(flavor = "multi_thread")]
+async fn unfinished() {
+    let mut h = Harness::new("unfinished");
+    setup(&mut h);
+
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    h.push(START + 2_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 50_000, 850_000, BOSS_HP));
+    h.finish();
+
+    h.check().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn buffs() {
+    let mut h = Harness::new("buffs");
+    setup(&mut h);
+
+    // 3s debuff on the boss, expires 1s after its duration
+    h.push(
+        START + 1_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: BOSS,
+            status_effect_data: status_effect(PARTY_MEMBER, UNKNOWN_DEBUFF, 1, 3.0),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 1_000,
+        Packet::PartyStatusEffectAdd(PKTPartyStatusEffectAddNotify {
+            character_id: 1002,
+            status_effect_datas: vec![status_effect(LOCAL_PLAYER, UNKNOWN_PARTY_BUFF, 2, 10.0)],
+            ..Default::default()
+        }),
+    );
+    // no duration, lasts until removed
+    h.push(
+        START + 1_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_data: status_effect(LOCAL_PLAYER, UNKNOWN_SELF_BUFF, 3, 0.0),
+            ..Default::default()
+        }),
+    );
+
+    h.push(START + 2_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    h.push(START + 2_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 100_000, 800_000, BOSS_HP));
+    h.push(START + 6_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 700_000, BOSS_HP));
+    h.push(
+        START + 7_000,
+        Packet::StatusEffectRemove(PKTStatusEffectRemoveNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_ids: vec![3],
+            ..Default::default()
+        }),
+    );
+    h.push(START + 8_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 600_000, BOSS_HP));
+    h.push(START + 8_000, damage(PARTY_MEMBER, SALVO, BOSS, 100_000, 500_000, BOSS_HP));
+    // party buff has expired
+    h.push(START + 13_000, damage(PARTY_MEMBER, SALVO, BOSS, 100_000, 400_000, BOSS_HP));
+    // leaving the zone saves the unfinished encounter
+    h.push(
+        START + 20_000,
+        Packet::InitEnv(PKTInitEnv {
+            player_id: 3,
+            ..Default::default()
+        }),
+    );
+
+    h.check().await;
+}
+
+fn shield_buff(id: u32, name: &str, target: &str) -> serde_json::Value {
+    skill_buff(id, name, "buff", "shield", target)
+}
+
+fn skill_buff(id: u32, name: &str, category: &str, buff_type: &str, target: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": name,
+        "desc": "",
+        "icon": "",
+        "iconshowtype": "all",
+        "duration": 10,
+        "category": category,
+        "type": buff_type,
+        "buffcategory": "classskill",
+        "target": target,
+        "uniquegroup": 0,
+        "overlapflag": 0,
+        "passiveoption": [],
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shields() {
+    let mut h = Harness::new("shields");
+    h.override_skill_buffs(json!({
+        PARTY_SHIELD.to_string(): shield_buff(PARTY_SHIELD, "Party Shield", "party"),
+        SELF_SHIELD.to_string(): shield_buff(SELF_SHIELD, "Self Shield", "self"),
+    }));
+    setup(&mut h);
+
+    // shields from before the fight starts are not counted
+    h.push(
+        START + 500,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_data: shield(PARTY_MEMBER, PARTY_SHIELD, 9, 99_000),
+            ..Default::default()
+        }),
+    );
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+
+    // member shields the local player, the local player shields the member and themselves
+    h.push(
+        START + 1_500,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_data: shield(PARTY_MEMBER, PARTY_SHIELD, 1, 50_000),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 1_500,
+        Packet::PartyStatusEffectAdd(PKTPartyStatusEffectAddNotify {
+            character_id: 1002,
+            status_effect_datas: vec![shield(LOCAL_PLAYER, PARTY_SHIELD, 2, 30_000)],
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 1_500,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_data: shield(LOCAL_PLAYER, SELF_SHIELD, 3, 20_000),
+            ..Default::default()
+        }),
+    );
+
+    h.push(START + 2_000, damage(BOSS, 0, LOCAL_PLAYER, 45_000, 300_000, 300_000));
+    h.push(
+        START + 2_000,
+        Packet::StatusEffectSync(PKTStatusEffectSyncDataNotify {
+            object_id: LOCAL_PLAYER,
+            effect_instance_id: 1,
+            character_id: 1001,
+            value: 20_000,
+        }),
+    );
+    h.push(
+        START + 2_000,
+        Packet::StatusEffectSync(PKTStatusEffectSyncDataNotify {
+            object_id: LOCAL_PLAYER,
+            effect_instance_id: 3,
+            character_id: 1001,
+            value: 5_000,
+        }),
+    );
+    h.push(START + 3_000, damage(BOSS, 0, PARTY_MEMBER, 20_000, 250_000, 250_000));
+    h.push(
+        START + 3_000,
+        Packet::TroopMemberUpdateMin(PKTTroopMemberUpdateMinNotify {
+            character_id: 1002,
+            status_effect_datas: vec![shield(LOCAL_PLAYER, PARTY_SHIELD, 2, 10_000)],
+            ..Default::default()
+        }),
+    );
+    // a refresh raises the value again, nothing was absorbed
+    h.push(
+        START + 4_000,
+        Packet::StatusEffectSync(PKTStatusEffectSyncDataNotify {
+            object_id: LOCAL_PLAYER,
+            effect_instance_id: 1,
+            character_id: 1001,
+            value: 50_000,
+        }),
+    );
+    h.push(START + 5_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 100_000, 800_000, BOSS_HP));
+    h.push(
+        START + 20_000,
+        Packet::InitEnv(PKTInitEnv {
+            player_id: 3,
+            ..Default::default()
+        }),
+    );
+
+    h.check().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn uptime() {
+    let mut h = Harness::new("uptime");
+    h.override_skill_buffs(json!({
+        AP_BUFF.to_string(): skill_buff(AP_BUFF, "AP Buff", "buff", "dmg", "party"),
+        BRAND.to_string(): skill_buff(BRAND, "Brand", "debuff", "dmg", "party"),
+    }));
+    setup(&mut h);
+
+    // brand from before the pull only counts from the first hit, 1s to 4.5s
+    h.push(
+        START + 500,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: BOSS,
+            status_effect_data: status_effect(PARTY_MEMBER, BRAND, 1, 4.0),
+            ..Default::default()
+        }),
+    );
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    h.push(
+        START + 2_000,
+        Packet::PartyStatusEffectAdd(PKTPartyStatusEffectAddNotify {
+            character_id: 1002,
+            status_effect_datas: vec![status_effect(LOCAL_PLAYER, AP_BUFF, 2, 10.0)],
+            ..Default::default()
+        }),
+    );
+    h.push(START + 3_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 100_000, 800_000, BOSS_HP));
+    h.push(
+        START + 3_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_data: status_effect(LOCAL_PLAYER, AP_BUFF, 3, 0.0),
+            ..Default::default()
+        }),
+    );
+    h.push(START + 4_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 700_000, BOSS_HP));
+    // overlapping brands, 6s to the end of the fight
+    h.push(
+        START + 6_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: BOSS,
+            status_effect_data: status_effect(PARTY_MEMBER, BRAND, 4, 4.0),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 8_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: BOSS,
+            status_effect_data: status_effect(PARTY_MEMBER, BRAND, 5, 4.0),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 8_000,
+        Packet::StatusEffectRemove(PKTStatusEffectRemoveNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_ids: vec![3],
+            ..Default::default()
+        }),
+    );
+    h.push(START + 9_000, damage(BOSS, 0, PARTY_MEMBER, 20_000, 230_000, 250_000));
+    h.push(START + 11_000, damage(PARTY_MEMBER, SALVO, BOSS, 100_000, 600_000, BOSS_HP));
+    h.push(
+        START + 20_000,
+        Packet::InitEnv(PKTInitEnv {
+            player_id: 3,
+            ..Default::default()
+        }),
+    );
+
+    h.check().await;
+}
+
+// a bard and a paladin joining the party from setup, both buffing the local player
+fn setup_supports(h: &mut Harness) {
+    setup(h);
+    for (player_id, name, class_id, character_id) in [(BARD, "Bard", 204, 1003), (PALADIN, "Paladin", 105, 1004)] {
+        h.push(
+            START,
+            Packet::NewPC(PKTNewPC {
+                pc_struct: PCStruct {
+                    player_id,
+                    name: name.to_string(),
+                    class_id,
+                    avg_item_level: 1600.0,
+                    character_id,
+                    stat_pair: stats(200_000, 200_000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        );
+    }
+    let members = [("Local", 102, 1001), ("Member", 502, 1002), ("Bard", 204, 1003), ("Paladin", 105, 1004)];
+    h.push(
+        START,
+        Packet::PartyInfo(PKTPartyInfo {
+            party_instance_id: 10,
+            raid_instance_id: 20,
+            member_datas: members
+                .into_iter()
+                .map(|(name, class_id, character_id)| PartyMemberData {
+                    name: name.to_string(),
+                    class_id,
+                    character_id,
+                    gear_level: 1600.0,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }),
+    );
+}
+
+fn support_buff(id: u32, name: &str, category: &str, buff_type: &str, source_skill: u32) -> serde_json::Value {
+    let mut buff = skill_buff(id, name, category, buff_type, "party");
+    buff["sourceskill"] = json!(source_skill);
+    buff
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn supports() {
+    let mut h = Harness::new("supports");
+    h.override_skill_buffs(json!({
+        BARD_AP.to_string(): support_buff(BARD_AP, "Heavenly Tune", "buff", "attack_power_amplify", 21160),
+        BARD_BRAND.to_string(): support_buff(BARD_BRAND, "Sonic Vibration", "debuff", "beattacked_damage_amplify", 21170),
+        PALADIN_AP.to_string(): support_buff(PALADIN_AP, "Heavenly Blessings", "buff", "attack_power_amplify", 36200),
+    }));
+    setup_supports(&mut h);
+
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    h.push(
+        START + 2_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: BOSS,
+            status_effect_data: status_effect(BARD, BARD_BRAND, 1, 10.0),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 2_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_data: status_effect(BARD, BARD_AP, 2, 10.0),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 2_000,
+        Packet::StatusEffectAdd(PKTStatusEffectAddNotify {
+            object_id: LOCAL_PLAYER,
+            status_effect_data: status_effect(PALADIN, PALADIN_AP, 3, 10.0),
+            ..Default::default()
+        }),
+    );
+    h.push(
+        START + 2_000,
+        Packet::PartyStatusEffectAdd(PKTPartyStatusEffectAddNotify {
+            character_id: 1002,
+            status_effect_datas: vec![status_effect(PALADIN, PALADIN_AP, 4, 10.0)],
+            ..Default::default()
+        }),
+    );
+
+    // local player under both supports, member only under the paladin, everyone under the brand
+    h.push(START + 3_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 800_000, BOSS_HP));
+    h.push(START + 3_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 100_000, 700_000, BOSS_HP));
+    h.push(START + 3_000, damage(BARD, 0, BOSS, 50_000, 650_000, BOSS_HP));
+    h.push(START + 3_000, damage(PALADIN, 0, BOSS, 50_000, 600_000, BOSS_HP));
+    // the paladin respawns with a new id, their buffs still carry the old one
+    h.push(
+        START + 4_000,
+        Packet::NewPC(PKTNewPC {
+            pc_struct: PCStruct {
+                player_id: PALADIN_RESPAWNED,
+                name: "Paladin".to_string(),
+                class_id: 105,
+                avg_item_level: 1600.0,
+                character_id: 1004,
+                stat_pair: stats(200_000, 200_000),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    );
+    h.push(START + 5_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 100_000, 500_000, BOSS_HP));
+
+    // every support is credited with what the players they buffed count for them
+    let entities = &h.state.encounter.entities;
+    for support in entities.values() {
+        let buffed: i64 = entities
+            .values()
+            .filter_map(|e| e.damage_stats.buffed_by_supports.get(&support.name))
+            .sum();
+        let debuffed: i64 = entities
+            .values()
+            .filter_map(|e| e.damage_stats.debuffed_by_supports.get(&support.name))
+            .sum();
+        assert_eq!(support.damage_stats.support_buffed_damage, buffed, "{}", support.name);
+        assert_eq!(support.damage_stats.support_debuffed_damage, debuffed, "{}", support.name);
+    }
+    // the member was only ever buffed by the paladin, so nothing overlaps
+    let member = &entities["Member"].damage_stats;
+    assert_eq!(member.buffed_by_supports.values().sum::<i64>(), member.buffed_by_support);
+    assert_eq!(member.buffed_by_support, 200_000);
+
+    h.push(
+        START + 20_000,
+        Packet::InitEnv(PKTInitEnv {
+            player_id: 9,
+            ..Default::default()
+        }),
+    );
+
+    h.check().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn targets() {
+    let mut h = Harness::new("targets");
+    setup(&mut h);
+    h.push(
+        START,
+        Packet::NewNpc(PKTNewNpc {
+            npc_struct: NpcData {
+                object_id: ADD,
+                type_id: ADD_NPC_ID,
+                stat_pair: stats(ADD_HP, ADD_HP),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    );
+
+    // a different npc with the same name is a separate target
+    h.push(
+        START,
+        Packet::NewNpc(PKTNewNpc {
+            npc_struct: NpcData {
+                object_id: OTHER_ADD,
+                type_id: OTHER_ADD_NPC_ID,
+                stat_pair: stats(ADD_HP, ADD_HP),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    );
+
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    h.push(START + 2_000, damage(LOCAL_PLAYER, POWER_BREAK, ADD, 30_000, 20_000, ADD_HP));
+    h.push(START + 2_000, damage(PARTY_MEMBER, RAPID_SHOT, ADD, 20_000, 0, ADD_HP));
+    h.push(START + 2_500, damage(PARTY_MEMBER, RAPID_SHOT, OTHER_ADD, 5_000, 45_000, ADD_HP));
+    h.push(START + 3_000, damage(PARTY_MEMBER, SALVO, BOSS, 200_000, 700_000, BOSS_HP));
+    // only damage dealt by players is split by target
+    h.push(START + 3_000, damage(BOSS, 0, LOCAL_PLAYER, 10_000, 290_000, 300_000));
+    h.push(
+        START + 20_000,
+        Packet::InitEnv(PKTInitEnv {
+            player_id: 3,
+            ..Default::default()
+        }),
+    );
+
+    h.check().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn damage_taken() {
+    let mut h = Harness::new("damage_taken");
+    setup(&mut h);
+    h.push(
+        START,
+        Packet::NewNpc(PKTNewNpc {
+            npc_struct: NpcData {
+                object_id: ADD,
+                type_id: ADD_NPC_ID,
+                stat_pair: stats(ADD_HP, ADD_HP),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    );
+
+    h.push(START + 1_000, damage(LOCAL_PLAYER, POWER_BREAK, BOSS, 100_000, 900_000, BOSS_HP));
+    // the local player eats the slam twice, the member once, split from the swipe and the add
+    h.push(START + 2_000, damage(BOSS, BOSS_SLAM, LOCAL_PLAYER, 40_000, 260_000, 300_000));
+    h.push(START + 2_000, damage(BOSS, BOSS_SLAM, PARTY_MEMBER, 35_000, 215_000, 250_000));
+    h.push(START + 3_000, damage(BOSS, BOSS_SWIPE, LOCAL_PLAYER, 10_000, 250_000, 300_000));
+    h.push(START + 4_000, damage(ADD, 0, LOCAL_PLAYER, 2_000, 248_000, 300_000));
+    h.push(START + 5_000, damage(BOSS, BOSS_SLAM, LOCAL_PLAYER, 60_000, 188_000, 300_000));
+    h.push(START + 6_000, damage(PARTY_MEMBER, RAPID_SHOT, BOSS, 100_000, 800_000, BOSS_HP));
+    h.push(
+        START + 20_000,
+        Packet::InitEnv(PKTInitEnv {
+            player_id: 3,
+            ..Default::default()
+        }),
+    );
+
+    h.check().await;
+}
@@ -1,36 +1 @@
-export const encounterMap: { [key: string]: { [key: string]: Array<string> }} = {
-    Valtan: {
-        "Valtan G1": ["Dark Mountain Predator", "Destroyer Lucas", "Leader Lugaru"],
-        "Valtan G2": ["Demon Beast Commander Valtan", "Ravaged Tyrant of Beasts"]
-    },
-    Vykas: {
-        "Vykas G1": ["Incubus Morphe", "Nightmarish Morphe"],
-        "Vykas G2": ["Covetous Devourer Vykas"],
-        "Vykas G3": ["Covetous Legion Commander Vykas"]
-    },
-    Clown: {
-        "Clown G1": ["Saydon"],
-        "Clown G2": ["Kakul"],
-        "Clown G3": ["Kakul-Saydon", "Encore-Desiring Kakul-Saydon"]
-    },
-    Brelshaza: {
-        "Brelshaza G1": ["Gehenna Helkasirs"],
-        "Brelshaza G2": ["Prokel", "Prokel's Spiritual Echo", "Ashtarot"],
-        "Brelshaza G3": ["Primordial Nightmare"],
-        "Brelshaza G4": ["Phantom Legion Commander Brelshaza"],
-        "Brelshaza G5": ["Brelshaza, Monarch of Nightmares", "Imagined Primordial Nightmare", "Pseudospace Primordial Nightmare"],
-        "Brelshaza G6": ["Phantom Legion Commander Brelshaza"]
-    },
-    Kayangel: {
-        "Kayangel G1": ["Tienis"],
-        "Kayangel G2": ["Prunya"],
-        "Kayangel G3": ["Lauriel"]
-    },
-    Akkan: {
-        "Akkan G1": ["Griefbringer Maurug", "Evolved Maurug"],
-        "Akkan G2": ["Lord of Degradation Akkan"],
-        "Akkan G3": ["Plague Legion Commander Akkan", "Lord of Kartheon Akkan"]
-    },
-};
-
 export const difficultyMap: Array<string> = ["Normal", "Hard", "Inferno", "Challenge", "Trial"];
\ No newline at end of file
@@ -8,6 +8,41 @@ export interface PartyEvent {
     payload?: PartyInfo;
 }
 
+export interface PersonalBestEvent {
+    event: string;
+    payload: Array<PersonalBest>;
+}
+
+export type RecordKind = "dps" | "maxHit" | "critRate" | "clearTime";
+
+export const recordKindNames: { [kind in RecordKind]: string } = {
+    dps: "DPS",
+    maxHit: "Max Hit",
+    critRate: "Crit Rate",
+    clearTime: "Clear Time"
+};
+
+export interface PersonalBest {
+    character: string;
+    boss: string;
+    difficulty: string;
+    kind: RecordKind;
+    value: number;
+    previous?: number;
+    encounterId: number;
+    skillId?: number;
+}
+
+export interface CharacterRecord {
+    boss: string;
+    difficulty: string;
+    kind: RecordKind;
+    value: number;
+    encounterId: number;
+    skillId?: number;
+    fightStart: number;
+}
+
 export interface Encounter {
     lastCombatPacket: number;
     fightStart: number;
@@ -22,6 +57,8 @@ export interface Encounter {
     favorite: boolean;
     cleared: boolean;
     bossOnlyDamage: boolean;
+    notes?: string;
+    imported: boolean;
 }
 
 export interface EncountersOverview {
@@ -52,6 +89,10 @@ export interface EncounterDamageStats {
     mostDamageTakenEntity: MostDamageTakenEntity;
     buffs: { [key: number]: StatusEffect };
     debuffs: { [key: number]: StatusEffect };
+    totalShielding: number;
+    totalEffectiveShielding: number;
+    appliedShieldBuffs: { [key: number]: StatusEffect };
+    damageByTarget: { [key: number]: TargetDamage };
     misc?: EncounterMisc;
 }
 
@@ -91,6 +132,7 @@ export interface MostDamageTakenEntity {
 export interface Entity {
     lastUpdate: number;
     id: number;
+    characterId: number;
     npcId: number;
     name: string;
     entityType: EntityType;
@@ -105,6 +147,21 @@ export interface Entity {
     skillStats: SkillStats;
 }
 
+export interface DamageTakenSkill {
+    id: number;
+    name: string;
+    icon: string;
+    damage: number;
+    hits: number;
+    maxHit: number;
+}
+
+// keyed by npc id, adds can share a name
+export interface TargetDamage {
+    name: string;
+    damage: number;
+}
+
 export interface Skill {
     id: number;
     name: string;
@@ -115,6 +172,7 @@ export interface Skill {
     debuffedBy: { [key: number]: number };
     buffedBySupport: number;
     debuffedBySupport: number;
+    damageByTarget: { [key: number]: TargetDamage };
     casts: number;
     hits: number;
     crits: number;
@@ -134,12 +192,27 @@ export interface DamageStats {
     debuffedBy: { [key: number]: number };
     buffedBySupport: number;
     debuffedBySupport: number;
+    damageByTarget: { [key: number]: TargetDamage };
+    damageTakenBySkill: { [key: string]: { [key: number]: DamageTakenSkill } };
+    buffedBySupports: { [key: string]: number };
+    debuffedBySupports: { [key: string]: number };
+    supportBuffedDamage: number;
+    supportDebuffedDamage: number;
     backAttackDamage: number;
     frontAttackDamage: number;
     critDamage: number;
     deaths: number;
     deathTime: number;
     dps: number;
+    shieldsGiven: number;
+    shieldsGivenBy: { [key: number]: number };
+    damageAbsorbedOnOthers: number;
+    damageAbsorbedOnOthersBy: { [key: number]: number };
+    shieldsReceived: number;
+    shieldsReceivedBy: { [key: number]: number };
+    damageAbsorbed: number;
+    damageAbsorbedBy: { [key: number]: number };
+    uptime: { [key: number]: number };
     dpsAverage: [number, number];
     dpsRolling10sAvg: [number, number];
 }
@@ -243,7 +316,8 @@ export enum MeterTab {
     IDENTITY,
     STAGGER,
     DETAILS,
-    BOSS
+    BOSS,
+    SHIELDS
 }
 
 export enum ChartType {
@@ -290,6 +364,7 @@ export class Buff {
     sourceIcon?: string;
     percentage: string;
     bubbles?: number;
+    uptime?: string;
 
     constructor(icon: string, percentage: string, sourceIcon?: string) {
         this.icon = icon;
@@ -336,6 +411,12 @@ export class SearchFilter {
     cleared: boolean;
     difficulty: string;
     bossOnlyDamage: boolean;
+    fromDate: string;
+    toDate: string;
+    localPlayerOnly: boolean;
+    partyMembers: string;
+    sort: string;
+    order: string;
 
     constructor(minDuration = -1) {
         this.bosses = new Set();
@@ -347,6 +428,12 @@ export class SearchFilter {
         this.cleared = false;
         this.difficulty = "";
         this.bossOnlyDamage = false;
+        this.fromDate = "";
+        this.toDate = "";
+        this.localPlayerOnly = false;
+        this.partyMembers = "";
+        this.sort = "date";
+        this.order = "desc";
     }
 }
 
@@ -355,4 +442,104 @@ export interface PartyBuffs {
     partyGroupedSynergies: Map<string, Set<string>>,
     partyPercentages: Array<number[]>,
     partyBuffs: Map<string, Map<string, Array<BuffDetails>>>,
-}
\ No newline at end of file
+}
+export interface EncounterComparison {
+    player: string;
+    base: PlayerSummary;
+    other: PlayerSummary;
+    dpsDelta: number;
+    skills: Array<SkillComparison>;
+    buffs: Array<BuffComparison>;
+}
+
+export interface PlayerSummary {
+    encounterId: number;
+    bossName: string;
+    fightStart: number;
+    duration: number;
+    class: string;
+    damageDealt: number;
+    dps: number;
+    castsPerMinute: number;
+    critRate: number;
+    backAttackRate: number;
+    frontAttackRate: number;
+    identityAverage?: number;
+}
+
+export interface SkillComparison {
+    skillId: number;
+    name: string;
+    icon: string;
+    base?: SkillSummary;
+    other?: SkillSummary;
+}
+
+export interface SkillSummary {
+    damage: number;
+    damageShare: number;
+    dps: number;
+    casts: number;
+    castsPerMinute: number;
+    critRate: number;
+    backAttackRate: number;
+    frontAttackRate: number;
+}
+
+export interface BuffComparison {
+    buffId: number;
+    name: string;
+    debuff: boolean;
+    base: number;
+    other: number;
+}
+
+export interface HistoryPoint {
+    encounterId: number;
+    fightStart: number;
+    bossName: string;
+    difficulty?: string;
+    cleared: boolean;
+    class: string;
+    gearScore: number;
+    dps: number;
+    damagePercent: number;
+    deaths: number;
+    supportBuffedPercent: number;
+    rollingDps: number;
+    rollingDamagePercent: number;
+    rollingSupportBuffedPercent: number;
+}
+
+export interface Character {
+    characterId: number;
+    name: string;
+    classId: number;
+    class: string;
+    firstSeen: number;
+    lastSeen: number;
+    names: Array<CharacterName>;
+    gearScores: Array<GearScorePoint>;
+}
+
+export interface CharacterName {
+    name: string;
+    firstSeen: number;
+    lastSeen: number;
+}
+
+export interface GearScorePoint {
+    encounterId: number;
+    fightStart: number;
+    gearScore: number;
+}
+
+export interface Raid {
+    name: string;
+    gates: Array<RaidGate>;
+}
+
+export interface RaidGate {
+    gate: number;
+    bosses: Array<string>;
+}
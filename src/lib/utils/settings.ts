@@ -32,6 +32,7 @@ export const defaultSettings = {
         scale: "1",
         alwaysOnTop: true,
         bossOnlyDamage: false,
+        recordPackets: false,
     },
     shortcuts: {
         hideMeter: {
@@ -1,6 +1,6 @@
-import { encounterMap } from "$lib/constants/encounters";
-import { SearchFilter } from "$lib/types";
-import { readable, writable } from "svelte/store";
+import { SearchFilter, type Raid } from "$lib/types";
+import { invoke } from "@tauri-apps/api";
+import { derived, readable, writable } from "svelte/store";
 
 export const takingScreenshot = writable(false);
 
@@ -17,18 +17,39 @@ export const searchFilter = writable(new SearchFilter());
 
 export const selectedEncounters = writable(new Set<number>());
 
-export const raidGates = readable(new Map<string, string>(), (set) => {
-    const newMap = new Map<string, string>();
+// raids come from meter-data so new ones don't need an app update
+export const raids = readable(new Array<Raid>(), (set) => {
+    invoke<Array<Raid>>("get_raids").then(set);
+
+    return () => {};
+});
 
-    Object.values(encounterMap).forEach((raid) => {
-        Object.entries(raid).forEach(([gate, bosses]) => {
-            bosses.forEach((boss) => {
-                newMap.set(boss, gate);
-            });
+export function gateName(raid: string, gate: number) {
+    return `${raid} G${gate}`;
+}
+
+// gate name to the bosses fought in it
+export const gateBosses = derived(raids, ($raids) => {
+    const newMap = new Map<string, Array<string>>();
+
+    $raids.forEach((raid) => {
+        raid.gates.forEach((gate) => {
+            newMap.set(gateName(raid.name, gate.gate), gate.bosses);
         });
     });
 
-    set(newMap);
+    return newMap;
+});
 
-    return () => {};
+// boss name to the gate it is fought in
+export const raidGates = derived(gateBosses, ($gateBosses) => {
+    const newMap = new Map<string, string>();
+
+    $gateBosses.forEach((bosses, gate) => {
+        bosses.forEach((boss) => {
+            newMap.set(boss, gate);
+        });
+    });
+
+    return newMap;
 });
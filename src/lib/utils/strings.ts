@@ -6,6 +6,17 @@ export function isValidName(word: string) {
     return /^\p{Lu}/u.test(word);
 }
 
+// for names and other strings from the db that end up in html tooltips,
+// imported logs can carry anything
+export function escapeHtml(input: string) {
+    return input
+        .replaceAll("&", "&amp;")
+        .replaceAll("<", "&lt;")
+        .replaceAll(">", "&gt;")
+        .replaceAll('"', "&quot;")
+        .replaceAll("'", "&#39;");
+}
+
 export function removeUnknownHtmlTags(input: string) {
     input = input.replace(/<\$TABLE_SKILLFEATURE[^>]*\/>/g, "??");
     input = input.replace(/<\$CALC[^>]*\/>/g, "??");
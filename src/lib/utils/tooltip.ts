@@ -1,10 +1,11 @@
-import type { BuffDetails, StatusEffect } from "$lib/types";
+import type { Buff, BuffDetails, Entity, StatusEffect } from "$lib/types";
 import { createTippy } from "svelte-tippy";
 import "tippy.js/animations/perspective-subtle.css";
 import "tippy.js/dist/svg-arrow.css";
-import { getSkillIcon, removeUnknownHtmlTags } from "./strings";
+import { escapeHtml, getSkillIcon, removeUnknownHtmlTags } from "./strings";
 import { roundArrow } from "tippy.js";
 import { classesMap } from "$lib/constants/classes";
+import { abbreviateNumber, round } from "./numbers";
 
 export const tooltip = createTippy({
     allowHTML: true,
@@ -42,11 +43,13 @@ export function generateTooltipContent(buffs: BuffDetails, iconPath: string) {
                 str += `[${buff.bubbles*5}<span class="text-3xs text-gray-300">%</span>] `;
             }
             str += `${buff.percentage}<span class="text-3xs text-gray-300">%</span>`;
+            str += uptimeTooltip(buff);
             str += `</div>`;
         } else {
             str += `<div class="flex items-center">`;
             str += `<img src=${iconPath + getSkillIcon(buff.icon)} alt="buff_icon" class="w-5 h-5 rounded mr-1"/>`;
             str += `${buff.percentage}<span class="text-3xs text-gray-300">%</span>`;
+            str += uptimeTooltip(buff);
             str += `</div>`;
         }
     }
@@ -54,14 +57,89 @@ export function generateTooltipContent(buffs: BuffDetails, iconPath: string) {
     return str;
 }
 
+function uptimeTooltip(buff: Buff) {
+    if (!buff.uptime) {
+        return "";
+    }
+    return `<span class="pl-1 text-3xs text-gray-400">(${buff.uptime}% uptime)</span>`;
+}
+
+// total damage, split by target when more than one was hit
+export function generateDamageTooltip(entity: Entity) {
+    const total = entity.damageStats.damageDealt.toLocaleString();
+    const targets = Object.values(entity.damageStats.damageByTarget ?? {}).sort((a, b) => b.damage - a.damage);
+    if (targets.length < 2) {
+        return total;
+    }
+    let str = `<div class="font-normal text-xs flex flex-col space-y-1 py-px">`;
+    str += `<div>${total}</div>`;
+    for (const { name, damage } of targets) {
+        str += `<div class="text-gray-300">${escapeHtml(name)}: ${abbreviateNumber(damage)}`;
+        str += ` (${round((damage / entity.damageStats.damageDealt) * 100)}%)</div>`;
+    }
+    str += "</div>";
+    return str;
+}
+
+// enemy skills that hit a player, hardest hitting first
+export function generateDamageTakenTooltip(player: Entity, iconPath: string) {
+    const total = player.damageStats.damageTaken.toLocaleString();
+    const skills = Object.entries(player.damageStats.damageTakenBySkill ?? {})
+        .flatMap(([source, skills]) => Object.values(skills).map((skill) => ({ source, skill })))
+        .sort((a, b) => b.skill.damage - a.skill.damage);
+    if (skills.length === 0) {
+        return total;
+    }
+    let str = `<div class="font-normal text-xs flex flex-col space-y-1 py-px">`;
+    str += `<div>${total}</div>`;
+    for (const { source, skill } of skills) {
+        str += `<div class="flex items-center text-gray-300">`;
+        if (skill.icon) {
+            str += `<img src="${escapeHtml(iconPath + getSkillIcon(skill.icon))}" alt="skill_icon" class="w-5 h-5 rounded mr-1"/>`;
+        }
+        str += `${escapeHtml(source)}: ${escapeHtml(skill.name || String(skill.id))} - ${abbreviateNumber(skill.damage)}`;
+        str += ` (${skill.hits} hits, max ${abbreviateNumber(skill.maxHit)})</div>`;
+    }
+    str += "</div>";
+    return str;
+}
+
+// per support share of a player's damage, and for supports the party damage under their buffs
+export function generateSupportTooltip(player: Entity, playerName: string) {
+    const stats = player.damageStats;
+    const lines: Array<string> = [];
+    for (const [name, damage] of Object.entries(stats.buffedBySupports ?? {})) {
+        lines.push(`Buffed by ${escapeHtml(name)}: ${round((damage / stats.damageDealt) * 100)}%`);
+    }
+    for (const [name, damage] of Object.entries(stats.debuffedBySupports ?? {})) {
+        lines.push(`Branded by ${escapeHtml(name)}: ${round((damage / stats.damageDealt) * 100)}%`);
+    }
+    if (stats.supportBuffedDamage) {
+        lines.push(`Party damage buffed: ${abbreviateNumber(stats.supportBuffedDamage)}`);
+    }
+    if (stats.supportDebuffedDamage) {
+        lines.push(`Party damage branded: ${abbreviateNumber(stats.supportDebuffedDamage)}`);
+    }
+    if (lines.length === 0) {
+        return escapeHtml(playerName);
+    }
+    let str = `<div class="font-normal text-xs flex flex-col space-y-1 py-px">`;
+    str += `<div>${escapeHtml(playerName)}</div>`;
+    for (const line of lines) {
+        str += `<div class="text-gray-300">${line}</div>`;
+    }
+    str += "</div>";
+    return str;
+}
+
 export function generateHeaderTooltip(buff: StatusEffect, iconPath: string) {
     let str = `<div class="font-normal text-sm py-1">`;
     if (buff.source.skill) {
         str += `<div class="flex">`;
         str += `${classesMap[buff.source.skill.classId]}:`;
-        str += `<img src=${iconPath + getSkillIcon(buff.source.skill.icon)} alt=${
+        str += `<img src=${iconPath + getSkillIcon(buff.source.skill.icon)} alt="${escapeHtml(
             buff.source.skill.name
-        } class="w-5 h-5 mx-1"/>`;
+        )}" class="w-5 h-5 mx-1"/>`;
         str += buff.source.skill.name;
         str += `</div>`;
     } else {
@@ -87,7 +165,7 @@ export function generateHeaderTooltip(buff: StatusEffect, iconPath: string) {
         str += `</div>`;
     }
     str += `<div class="flex tracking-tight items-center">`;
-    str += `<img src=${iconPath + getSkillIcon(buff.source.icon)} alt=${buff.name} class="w-5 h-5 mr-1"/>`;
+    str += `<img src=${iconPath + getSkillIcon(buff.source.icon)} alt="${escapeHtml(buff.name)}" class="w-5 h-5 mr-1"/>`;
     str += `<div class="">`;
     str += removeUnknownHtmlTags(buff.source.desc);
     str += `</div></div></div>`;